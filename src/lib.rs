@@ -1,314 +1,8107 @@
 pub mod interpreter {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::fmt;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Condvar, LazyLock, Mutex, OnceLock, RwLock, Weak};
 
-    #[derive(Debug, PartialEq, Clone)]
+    use rand::{RngExt, SeedableRng};
+
+    // Symbol names are interned once per process, so copying or comparing a symbol is an integer operation
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct SymbolId(u32);
+
+    #[derive(Default)]
+    struct SymbolInterner {
+        ids: HashMap<&'static str, SymbolId>,
+        names: Vec<&'static str>,
+    }
+
+    static SYMBOLS: LazyLock<RwLock<SymbolInterner>> = LazyLock::new(Default::default);
+
+    impl SymbolId {
+        pub fn intern(name: &str) -> SymbolId {
+            if let Some(&id) = SYMBOLS.read().unwrap().ids.get(name) {
+                return id;
+            }
+
+            let mut symbols = SYMBOLS.write().unwrap();
+            if let Some(&id) = symbols.ids.get(name) {
+                return id;
+            }
+
+            // Interned names live for the rest of the program
+            let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+            let id = SymbolId(symbols.names.len() as u32);
+            symbols.names.push(name);
+            symbols.ids.insert(name, id);
+            id
+        }
+
+        pub fn as_str(self) -> &'static str {
+            SYMBOLS.read().unwrap().names[self.0 as usize]
+        }
+    }
+
+    impl From<&str> for SymbolId {
+        fn from(name: &str) -> Self {
+            SymbolId::intern(name)
+        }
+    }
+
+    impl PartialEq<str> for SymbolId {
+        fn eq(&self, other: &str) -> bool {
+            self.as_str() == other
+        }
+    }
+
+    impl PartialEq<&str> for SymbolId {
+        fn eq(&self, other: &&str) -> bool {
+            self.as_str() == *other
+        }
+    }
+
+    impl fmt::Debug for SymbolId {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.as_str())
+        }
+    }
+
+    impl fmt::Display for SymbolId {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            // A gensym prints without the NUL that keeps it from being read back
+            write!(f, "{}", self.as_str().trim_start_matches('\0'))
+        }
+    }
+
+    #[derive(Debug, Clone)]
     pub enum Expr {
-        Symbol(String),
+        Symbol(SymbolId),
         Number(f64),
+        Bool(bool),
+        Str(String),
+        Char(char),
         List(Vec<Expr>),
+        Values(Vec<Expr>),
+        Builtin(String),
+        Lambda(Arc<Lambda>),
+        Port(Arc<Mutex<Port>>),
+        TimePoint(std::time::SystemTime),
+        Duration(f64),
+        RandomSource(Arc<Mutex<RandomState>>),
+        Regexp(Arc<regex::Regex>),
+        Thread(Arc<Mutex<LispThread>>),
+        Mutex(Arc<LispMutex>),
+        Coroutine(Arc<Mutex<Coroutine>>),
+        Memoized(Arc<Memoized>),
+        Void,
+        Parameter(Arc<Parameter>),
+        Vector(Arc<Mutex<Vec<Expr>>>),
+        Bytevector(Arc<Mutex<Vec<u8>>>),
+        ErrorObject(Arc<ErrorObject>),
+        Macro(Arc<Macro>),
+        CharSet(Arc<CharSet>),
+        Environment(Arc<EnvironmentValue>),
+        Eof,
+        HashTable(Arc<Mutex<HashMap<Expr, Expr>>>),
+        // A ring of elements and where in it the list starts; its cdr starts one further along
+        CircularList(Arc<Vec<Expr>>, usize),
+        WeakPair(Arc<WeakPair>),
+    }
+
+    // Mutable objects compare by identity, everything else by value. Vectors and bytevectors are the
+    // exception: like equal?, they compare element by element
+    impl PartialEq for Expr {
+        fn eq(&self, other: &Self) -> bool {
+            match (self, other) {
+                (Expr::Symbol(a), Expr::Symbol(b)) => a == b,
+                (Expr::Number(a), Expr::Number(b)) | (Expr::Duration(a), Expr::Duration(b)) => a == b,
+                (Expr::Bool(a), Expr::Bool(b)) => a == b,
+                (Expr::Str(a), Expr::Str(b)) | (Expr::Builtin(a), Expr::Builtin(b)) => a == b,
+                (Expr::Char(a), Expr::Char(b)) => a == b,
+                (Expr::List(a), Expr::List(b)) | (Expr::Values(a), Expr::Values(b)) => a == b,
+                (Expr::Lambda(a), Expr::Lambda(b)) => a == b,
+                (Expr::Port(a), Expr::Port(b)) => Arc::ptr_eq(a, b),
+                (Expr::TimePoint(a), Expr::TimePoint(b)) => a == b,
+                (Expr::RandomSource(a), Expr::RandomSource(b)) => Arc::ptr_eq(a, b),
+                (Expr::Regexp(a), Expr::Regexp(b)) => a.as_str() == b.as_str(),
+                (Expr::Thread(a), Expr::Thread(b)) => Arc::ptr_eq(a, b),
+                (Expr::Mutex(a), Expr::Mutex(b)) => Arc::ptr_eq(a, b),
+                (Expr::Coroutine(a), Expr::Coroutine(b)) => Arc::ptr_eq(a, b),
+                (Expr::Memoized(a), Expr::Memoized(b)) => Arc::ptr_eq(a, b),
+                (Expr::Void, Expr::Void) | (Expr::Eof, Expr::Eof) => true,
+                (Expr::Parameter(a), Expr::Parameter(b)) => Arc::ptr_eq(a, b),
+                (Expr::Vector(a), Expr::Vector(b)) => {
+                    // A pair of vectors met again while comparing it is assumed equal, so vectors
+                    // that contain themselves compare in finite time
+                    let key = (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize);
+                    if Arc::ptr_eq(a, b) || reopen(&OPEN_COMPARISONS, key).is_some() {
+                        return true;
+                    }
+                    let (a, b) = (a.lock().unwrap().clone(), b.lock().unwrap().clone());
+                    with_open(&OPEN_COMPARISONS, key, || a == b).0
+                }
+                (Expr::ErrorObject(a), Expr::ErrorObject(b)) => Arc::ptr_eq(a, b),
+                (Expr::Macro(a), Expr::Macro(b)) => Arc::ptr_eq(a, b),
+                (Expr::CharSet(a), Expr::CharSet(b)) => a == b,
+                (Expr::Environment(a), Expr::Environment(b)) => Arc::ptr_eq(a, b),
+                (Expr::HashTable(a), Expr::HashTable(b)) => Arc::ptr_eq(a, b),
+                (Expr::WeakPair(a), Expr::WeakPair(b)) => Arc::ptr_eq(a, b),
+                (Expr::CircularList(a, i), Expr::CircularList(b, j)) => {
+                    a.len() == b.len() && ring_order(a, *i).eq(ring_order(b, *j))
+                }
+                (Expr::Bytevector(a), Expr::Bytevector(b)) => {
+                    Arc::ptr_eq(a, b) || *a.lock().unwrap() == *b.lock().unwrap()
+                }
+                _ => false,
+            }
+        }
+    }
+
+    // The elements of a circular list once around, beginning at `start`
+    fn ring_order(items: &[Expr], start: usize) -> impl Iterator<Item = &Expr> {
+        items[start..].iter().chain(&items[..start])
+    }
+
+    // Lets values be used as hash keys with the same notion of equality as equal?. NaN is the one
+    // value that is not equal to itself, so it never finds its own entry
+    impl Eq for Expr {}
+
+    impl std::hash::Hash for Expr {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            std::mem::discriminant(self).hash(state);
+            match self {
+                Expr::Symbol(id) => id.hash(state),
+                Expr::Number(n) | Expr::Duration(n) => {
+                    // 0.0 and -0.0 are equal, so they must hash alike
+                    let n = if *n == 0.0 { 0.0 } else { *n };
+                    n.to_bits().hash(state)
+                }
+                Expr::Bool(b) => b.hash(state),
+                Expr::Str(s) | Expr::Builtin(s) => s.hash(state),
+                Expr::Char(c) => c.hash(state),
+                Expr::List(items) | Expr::Values(items) => items.hash(state),
+                Expr::Lambda(lambda) => std::ptr::hash(Arc::as_ptr(lambda), state),
+                Expr::Port(port) => std::ptr::hash(Arc::as_ptr(port), state),
+                Expr::TimePoint(time) => time.hash(state),
+                Expr::RandomSource(source) => std::ptr::hash(Arc::as_ptr(source), state),
+                Expr::Regexp(regex) => regex.as_str().hash(state),
+                Expr::Thread(thread) => std::ptr::hash(Arc::as_ptr(thread), state),
+                Expr::Mutex(mutex) => std::ptr::hash(Arc::as_ptr(mutex), state),
+                Expr::Coroutine(coroutine) => std::ptr::hash(Arc::as_ptr(coroutine), state),
+                Expr::Memoized(memoized) => std::ptr::hash(Arc::as_ptr(memoized), state),
+                Expr::Void | Expr::Eof => {}
+                Expr::Parameter(parameter) => std::ptr::hash(Arc::as_ptr(parameter), state),
+                Expr::Vector(items) => {
+                    let key = Arc::as_ptr(items) as usize;
+                    if reopen(&OPEN_HASHES, key).is_none() {
+                        let items = items.lock().unwrap().clone();
+                        with_open(&OPEN_HASHES, key, || items.hash(state));
+                    }
+                }
+                Expr::Bytevector(bytes) => bytes.lock().unwrap().hash(state),
+                Expr::ErrorObject(error) => std::ptr::hash(Arc::as_ptr(error), state),
+                Expr::Macro(transformer) => std::ptr::hash(Arc::as_ptr(transformer), state),
+                Expr::CharSet(set) => set.ranges().hash(state),
+                Expr::Environment(environment) => std::ptr::hash(Arc::as_ptr(environment), state),
+                Expr::HashTable(table) => std::ptr::hash(Arc::as_ptr(table), state),
+                Expr::WeakPair(pair) => std::ptr::hash(Arc::as_ptr(pair), state),
+                Expr::CircularList(items, start) => ring_order(items, *start).for_each(|item| item.hash(state)),
+            }
+        }
+    }
+
+    // The vectors being written, compared or hashed on this thread, outermost first, each with
+    // whether it has been met again inside itself. The elements are copied out before recursing, so
+    // no vector's lock is held while another is taken, and a vector that contains itself is met as
+    // a back reference instead of being entered again
+    type OpenVectors<K> = std::cell::RefCell<Vec<(K, bool)>>;
+
+    thread_local! {
+        static OPEN_WRITES: OpenVectors<usize> = const { std::cell::RefCell::new(Vec::new()) };
+        static OPEN_COMPARISONS: OpenVectors<(usize, usize)> = const { std::cell::RefCell::new(Vec::new()) };
+        static OPEN_HASHES: OpenVectors<usize> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    // Runs `f` with `key` open, and says whether `f` met it again
+    fn with_open<K: PartialEq, R>(
+        open: &'static std::thread::LocalKey<OpenVectors<K>>,
+        key: K,
+        f: impl FnOnce() -> R,
+    ) -> (R, bool) {
+        open.with(|open| open.borrow_mut().push((key, false)));
+        let result = f();
+        let (_, met) = open.with(|open| open.borrow_mut().pop().unwrap());
+        (result, met)
+    }
+
+    // Where `key` is among the open vectors, marking it as met again
+    fn reopen<K: PartialEq>(open: &'static std::thread::LocalKey<OpenVectors<K>>, key: K) -> Option<usize> {
+        open.with(|open| {
+            let mut open = open.borrow_mut();
+            let position = open.iter().position(|(open_key, _)| *open_key == key)?;
+            open[position].1 = true;
+            Some(position)
+        })
+    }
+
+    // A set of characters as sorted, disjoint, non-adjacent inclusive ranges of code points, so the
+    // large Unicode classes stay small. The predefined sets test membership with their predicate and
+    // only build the ranges when a set operation needs them, since that scans every code point
+    #[derive(Debug)]
+    pub struct CharSet {
+        ranges: OnceLock<Vec<(u32, u32)>>,
+        predicate: Option<fn(char) -> bool>,
+    }
+
+    impl PartialEq for CharSet {
+        fn eq(&self, other: &Self) -> bool {
+            self.ranges() == other.ranges()
+        }
+    }
+
+    impl CharSet {
+        fn new(ranges: Vec<(u32, u32)>) -> CharSet {
+            CharSet { ranges: OnceLock::from(ranges), predicate: None }
+        }
+
+        fn from_ranges(mut ranges: Vec<(u32, u32)>) -> CharSet {
+            ranges.sort();
+            let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+            for (start, end) in ranges {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            CharSet::new(merged)
+        }
+
+        fn from_predicate(predicate: fn(char) -> bool) -> CharSet {
+            CharSet { ranges: OnceLock::new(), predicate: Some(predicate) }
+        }
+
+        fn ranges(&self) -> &[(u32, u32)] {
+            self.ranges.get_or_init(|| {
+                let predicate = self.predicate.unwrap_or(|_| false);
+                let mut ranges: Vec<(u32, u32)> = Vec::new();
+                for c in (0..=char::MAX as u32).filter_map(char::from_u32).filter(|&c| predicate(c)) {
+                    match ranges.last_mut() {
+                        Some(last) if last.1 + 1 == c as u32 => last.1 = c as u32,
+                        _ => ranges.push((c as u32, c as u32)),
+                    }
+                }
+                ranges
+            })
+        }
+
+        fn contains(&self, c: char) -> bool {
+            if let Some(predicate) = self.predicate {
+                return predicate(c);
+            }
+            let c = c as u32;
+            self.ranges()
+                .binary_search_by(|&(start, end)| {
+                    if end < c {
+                        std::cmp::Ordering::Less
+                    } else if start > c {
+                        std::cmp::Ordering::Greater
+                    } else {
+                        std::cmp::Ordering::Equal
+                    }
+                })
+                .is_ok()
+        }
+
+        fn size(&self) -> u32 {
+            self.ranges().iter().map(|(start, end)| end - start + 1).sum()
+        }
+
+        fn intersection(&self, other: &CharSet) -> CharSet {
+            let (left, right) = (self.ranges(), other.ranges());
+            let mut ranges = Vec::new();
+            let (mut i, mut j) = (0, 0);
+            while i < left.len() && j < right.len() {
+                let (a, b) = (left[i], right[j]);
+                let (start, end) = (a.0.max(b.0), a.1.min(b.1));
+                if start <= end {
+                    ranges.push((start, end));
+                }
+                if a.1 < b.1 {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+            CharSet::new(ranges)
+        }
+
+        // Every code point that is not in the set, surrogates included
+        fn gaps(&self) -> CharSet {
+            let mut ranges = Vec::new();
+            let mut next = 0;
+            for &(start, end) in self.ranges() {
+                if start > next {
+                    ranges.push((next, start - 1));
+                }
+                next = end + 1;
+            }
+            if next <= char::MAX as u32 {
+                ranges.push((next, char::MAX as u32));
+            }
+            CharSet::new(ranges)
+        }
+
+        fn difference(&self, other: &CharSet) -> CharSet {
+            self.intersection(&other.gaps())
+        }
+    }
+
+    // Macros are bound like variables so let-syntax can scope them
+    #[derive(Debug)]
+    pub enum Macro {
+        Rules { literals: Vec<SymbolId>, rules: Vec<(Expr, Expr)> },
+        // identifier-syntax: `template` stands in for the identifier, and `setter` is the pattern
+        // variable and template of an optional (set! id v) clause
+        Identifier { template: Expr, setter: Option<(SymbolId, Expr)> },
+        // make-variable-transformer: the procedure receives the whole form, which is the bare
+        // identifier, (id arg ...) or (set! id value), and returns its expansion
+        Variable(Expr),
+    }
+
+    // Raised by `error`, and what handlers receive for errors from the interpreter itself. `kind` is
+    // the error_kind of the original error, or "error" for ones made by `error`
+    #[derive(Debug)]
+    pub struct ErrorObject {
+        message: String,
+        irritants: Vec<Expr>,
+        kind: &'static str,
+    }
+
+    impl ErrorObject {
+        fn report(&self) -> String {
+            let mut report = self.message.clone();
+            for irritant in &self.irritants {
+                report.push(' ');
+                report.push_str(&irritant.to_string());
+            }
+            report
+        }
+    }
+
+    // Called with no arguments to get its current value, which parameterize rebinds for a dynamic extent
+    #[derive(Debug)]
+    pub struct Parameter {
+        value: Mutex<Expr>,
+        converter: Option<Expr>,
+    }
+
+    // A procedure whose results are cached by argument list
+    #[derive(Debug)]
+    pub struct Memoized {
+        procedure: Expr,
+        cache: Mutex<HashMap<Vec<Expr>, Expr>>,
+    }
+
+    #[derive(Debug)]
+    pub enum LispThread {
+        New(Expr),
+        Running(std::thread::JoinHandle<Result<Expr, LispError>>),
+        Finished(Result<Expr, LispError>),
+    }
+
+    // A coroutine body runs on its own thread, but control is handed back and forth over channels
+    // so only one side is ever running. There is no call/cc to save a continuation with, so a
+    // suspended coroutine is a parked thread
+    #[derive(Debug)]
+    pub enum Coroutine {
+        New(Expr),
+        Suspended(SuspendedCoroutine),
+        Running,
+        Finished,
+    }
+
+    #[derive(Debug)]
+    pub struct SuspendedCoroutine {
+        resume: std::sync::mpsc::Sender<Expr>,
+        events: std::sync::mpsc::Receiver<CoroutineEvent>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    // Dropping a coroutine before it finishes cancels it: without a sender, its pending yield
+    // fails with CoroutineAbandoned, which no handler catches, so the body unwinds and the thread
+    // is joined. A coroutine that stays reachable, for example through a top-level definition,
+    // keeps its thread parked until it is resumed to the end or the program exits
+    impl Drop for SuspendedCoroutine {
+        fn drop(&mut self) {
+            self.resume = std::sync::mpsc::channel().0;
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum CoroutineEvent {
+        Yielded(Expr),
+        Finished(Result<Expr, LispError>),
+    }
+
+    // How code running inside a coroutine reaches the caller of `resume`
+    struct CoroutineLink {
+        events: std::sync::mpsc::Sender<CoroutineEvent>,
+        resumes: std::sync::mpsc::Receiver<Expr>,
+    }
+
+    // Locked and unlocked by separate calls, so the guard cannot be held across them. Only the
+    // thread holding the mutex can unlock it. A recursive mutex can be locked again by that thread
+    // and is released by as many unlocks
+    #[derive(Debug, Default)]
+    pub struct LispMutex {
+        state: Mutex<MutexState>,
+        released: Condvar,
+        recursive: bool,
+    }
+
+    #[derive(Debug, Default)]
+    struct MutexState {
+        owner: Option<std::thread::ThreadId>,
+        depth: usize,
+    }
+
+    impl LispMutex {
+        fn recursive() -> Self {
+            LispMutex { recursive: true, ..Default::default() }
+        }
+
+        fn lock(&self) {
+            let current = std::thread::current().id();
+            let mut state = self.state.lock().unwrap();
+            if !(self.recursive && state.depth > 0 && state.owner == Some(current)) {
+                while state.depth > 0 {
+                    state = self.released.wait(state).unwrap();
+                }
+                state.owner = Some(current);
+            }
+            state.depth += 1;
+        }
+
+        // False if the current thread does not hold the mutex, which is then left as it is
+        fn unlock(&self) -> bool {
+            let mut state = self.state.lock().unwrap();
+            if state.depth == 0 || state.owner != Some(std::thread::current().id()) {
+                return false;
+            }
+            state.depth -= 1;
+            if state.depth == 0 {
+                state.owner = None;
+                self.released.notify_one();
+            }
+            true
+        }
+
+        fn is_locked(&self) -> bool {
+            self.state.lock().unwrap().depth > 0
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum Port {
+        StringOutput(String),
+        StringInput { text: Vec<char>, position: usize },
+        Stdin,
+        Stdout,
+        Stderr,
+        Closed,
+    }
+
+    fn console_port(port: Port) -> Expr {
+        Expr::Port(Arc::new(Mutex::new(port)))
+    }
+
+    // What current-input-port, current-output-port and current-error-port return
+    #[derive(Clone)]
+    struct CurrentPorts {
+        input: Expr,
+        output: Expr,
+        error: Expr,
+    }
+
+    impl Default for CurrentPorts {
+        fn default() -> Self {
+            CurrentPorts { input: console_port(Port::Stdin), output: console_port(Port::Stdout), error: console_port(Port::Stderr) }
+        }
+    }
+
+    pub struct Lambda {
+        pub params: Expr,
+        pub body: Vec<Expr>,
+        scope: Arc<Mutex<Scope>>,
+        // The top-level form being evaluated when the lambda was made, and the name it was first
+        // defined under or given by set-procedure-name!
+        source: Option<SourceLocation>,
+        name: Mutex<Option<SymbolId>>,
+    }
+
+    impl Lambda {
+        fn name(&self) -> Option<SymbolId> {
+            *self.name.lock().unwrap()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SourceLocation {
+        pub file: String,
+        pub line: usize,
+        pub column: usize,
+    }
+
+    impl fmt::Display for SourceLocation {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}:{}:{}", self.file, self.line, self.column)
+        }
+    }
+
+    // One procedure call an error unwound through, innermost first
+    #[derive(Debug, Clone)]
+    pub struct Frame {
+        pub name: Option<String>,
+        pub location: Option<SourceLocation>,
+    }
+
+    impl fmt::Display for Frame {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "in {}", self.name.as_deref().unwrap_or("anonymous procedure"))?;
+            match &self.location {
+                Some(location) => write!(f, " defined at {}", location),
+                None => Ok(()),
+            }
+        }
+    }
+
+    // The captured scope is left out: it may contain the lambda itself
+    impl fmt::Debug for Lambda {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Lambda")
+                .field("params", &self.params)
+                .field("body", &self.body)
+                .finish()
+        }
+    }
+
+    impl PartialEq for Lambda {
+        fn eq(&self, other: &Self) -> bool {
+            std::ptr::eq(self, other)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone)]
+    pub enum LispError {
+        Read(String),
+        Eval(String),
+        FileError(String),
+        AssertionFailed(String),
+        Raised(Expr),
+        SandboxTimeout(u64),
+        SandboxMemoryExceeded(usize),
+        CoroutineAbandoned,
+        Interrupted,
+    }
+
+    impl fmt::Display for LispError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                LispError::Read(message) | LispError::Eval(message) | LispError::FileError(message) => {
+                    write!(f, "{}", message)
+                }
+                LispError::AssertionFailed(message) => write!(f, "Assertion failed: {}", message),
+                LispError::Raised(Expr::ErrorObject(error)) => write!(f, "{}", error.report()),
+                LispError::Raised(obj) => write!(f, "Uncaught exception: {}", obj),
+                LispError::SandboxTimeout(ms) => write!(f, "Sandboxed evaluation exceeded its time limit of {}ms", ms),
+                LispError::SandboxMemoryExceeded(bytes) => {
+                    write!(f, "Sandboxed evaluation exceeded its memory limit of {} bytes", bytes)
+                }
+                LispError::CoroutineAbandoned => write!(f, "Coroutine was abandoned"),
+                LispError::Interrupted => write!(f, "Interrupted"),
+            }
+        }
+    }
+
+    impl std::error::Error for LispError {}
+
+    impl From<String> for LispError {
+        fn from(message: String) -> Self {
+            LispError::Eval(message)
+        }
+    }
+
+    impl From<&str> for LispError {
+        fn from(message: &str) -> Self {
+            LispError::Eval(message.to_string())
+        }
+    }
+
+    pub type Function = fn(&[Expr], &mut Environment) -> Result<Expr, LispError>;
+
+    pub enum StepCommand {
+        Next,
+        Continue,
+        Up,
+        Eval(String),
+        Print(String),
+    }
+
+    // Called before each step with the expression about to be evaluated and its nesting depth
+    pub type Debugger = dyn FnMut(&Expr, usize) -> StepCommand + Send;
+
+    // Stepping stops at any evaluation nested no deeper than `stop_depth`
+    struct StepState {
+        depth: usize,
+        stop_depth: usize,
+    }
+
+    // Time is only taken for the outermost active call of a procedure, so recursion is not double counted
+    #[derive(Default)]
+    struct ProfileEntry {
+        calls: usize,
+        time: std::time::Duration,
+        active: usize,
+    }
+
+    // Which native procedures code evaluated in an environment value can see
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Natives {
+        All,
+        Standard,
+        None,
+    }
+
+    // A first-class environment for eval: a scope and the natives visible from it
+    pub struct EnvironmentValue {
+        scope: Arc<Mutex<Scope>>,
+        natives: Natives,
+    }
+
+    impl fmt::Debug for EnvironmentValue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("EnvironmentValue").field("natives", &self.natives).finish()
+        }
+    }
+
+    // The R7RS procedures this interpreter provides, which are all scheme-report-environment offers
+    const STANDARD_PROCEDURES: &[&str] = &[
+        "+", "-", "*", "/", "=", "<", ">", "<=", ">=", "not", "car", "cdr", "cons", "list", "apply", "values",
+        "call-with-values", "floor/", "equal?", "odd?", "even?", "square", "abs", "sqrt", "exact-integer-sqrt",
+        "exact-integer?", "number->string", "integer?", "rational?", "real?", "complex?", "number?", "write", "display",
+        "newline", "open-output-string", "get-output-string", "close-port", "open-input-string", "read-line",
+        "read-char", "peek-char", "eof-object", "eof-object?", "string-ref", "string-length", "string-append", "string",
+        "make-string", "string->list", "list->string", "current-input-port", "current-output-port",
+        "current-error-port", "char-alphabetic?", "char-numeric?", "char-whitespace?",
+        "char-upper-case?", "char-lower-case?", "char-upcase", "char-downcase", "features", "command-line",
+        "get-environment-variable", "get-environment-variables", "exit", "emergency-exit", "file-exists?",
+        "delete-file", "make-parameter", "vector", "make-vector", "vector?", "vector-length", "vector-ref",
+        "vector-set!", "vector-append", "vector-map", "vector-for-each", "vector-copy", "vector-copy!", "list->vector",
+        "vector->list", "string->vector", "vector->string", "string-map", "string-for-each", "string-copy",
+        "bytevector", "make-bytevector", "bytevector?", "bytevector-length", "bytevector-u8-ref",
+        "bytevector-u8-set!", "bytevector-copy", "bytevector-copy!", "bytevector-append", "utf8->string",
+        "string->utf8", "with-exception-handler", "raise", "raise-continuable", "error", "error-object?",
+        "error-object-message", "error-object-irritants", "read-error?", "file-error?", "eval",
+        "interaction-environment", "scheme-report-environment", "null-environment",
+    ];
+
+    pub type NativeClosure = dyn Fn(&[Expr], &mut Environment) -> Result<Expr, LispError> + Send + Sync;
+
+    #[derive(Default)]
+    struct Scope {
+        symbols: HashMap<SymbolId, Expr>,
+        parent: Option<Arc<Mutex<Scope>>>,
+    }
+
+    impl Scope {
+        fn child(parent: &Arc<Mutex<Scope>>) -> Self {
+            Scope {
+                symbols: HashMap::new(),
+                parent: Some(parent.clone()),
+            }
+        }
+
+        fn lookup(&self, name: SymbolId) -> Option<Expr> {
+            match self.symbols.get(&name) {
+                Some(value) => Some(value.clone()),
+                None => self.parent.as_ref().and_then(|parent| parent.lock().unwrap().lookup(name)),
+            }
+        }
+
+        // Updates the binding in whichever scope defines it; false if no scope does
+        fn assign(&mut self, name: SymbolId, value: Expr) -> bool {
+            if let Some(slot) = self.symbols.get_mut(&name) {
+                *slot = value;
+                return true;
+            }
+
+            match &self.parent {
+                Some(parent) => parent.lock().unwrap().assign(name, value),
+                None => false,
+            }
+        }
+
+        fn collect_names(&self, names: &mut HashSet<String>) {
+            names.extend(self.symbols.keys().map(|name| name.to_string()));
+            if let Some(parent) = &self.parent {
+                parent.lock().unwrap().collect_names(names);
+            }
+        }
+    }
+
+    // A reference that does not keep heap objects alive. Values without identity, like numbers and
+    // symbols, can never be collected and are held as they are
+    #[derive(Debug)]
+    enum WeakValue {
+        Strong(Expr),
+        Lambda(Weak<Lambda>),
+        Vector(Weak<Mutex<Vec<Expr>>>),
+        Bytevector(Weak<Mutex<Vec<u8>>>),
+        HashTable(Weak<Mutex<HashMap<Expr, Expr>>>),
+        Port(Weak<Mutex<Port>>),
+        Coroutine(Weak<Mutex<Coroutine>>),
+        ErrorObject(Weak<ErrorObject>),
+    }
+
+    impl WeakValue {
+        fn new(expr: &Expr) -> WeakValue {
+            match expr {
+                Expr::Lambda(lambda) => WeakValue::Lambda(Arc::downgrade(lambda)),
+                Expr::Vector(items) => WeakValue::Vector(Arc::downgrade(items)),
+                Expr::Bytevector(bytes) => WeakValue::Bytevector(Arc::downgrade(bytes)),
+                Expr::HashTable(table) => WeakValue::HashTable(Arc::downgrade(table)),
+                Expr::Port(port) => WeakValue::Port(Arc::downgrade(port)),
+                Expr::Coroutine(coroutine) => WeakValue::Coroutine(Arc::downgrade(coroutine)),
+                Expr::ErrorObject(error) => WeakValue::ErrorObject(Arc::downgrade(error)),
+                _ => WeakValue::Strong(expr.clone()),
+            }
+        }
+
+        fn upgrade(&self) -> Option<Expr> {
+            match self {
+                WeakValue::Strong(expr) => Some(expr.clone()),
+                WeakValue::Lambda(lambda) => lambda.upgrade().map(Expr::Lambda),
+                WeakValue::Vector(items) => items.upgrade().map(Expr::Vector),
+                WeakValue::Bytevector(bytes) => bytes.upgrade().map(Expr::Bytevector),
+                WeakValue::HashTable(table) => table.upgrade().map(Expr::HashTable),
+                WeakValue::Port(port) => port.upgrade().map(Expr::Port),
+                WeakValue::Coroutine(coroutine) => coroutine.upgrade().map(Expr::Coroutine),
+                WeakValue::ErrorObject(error) => error.upgrade().map(Expr::ErrorObject),
+            }
+        }
+    }
+
+    // Whether anything besides this one reference keeps a collectable object alive
+    fn referenced_elsewhere(expr: &Expr) -> bool {
+        match expr {
+            Expr::Lambda(lambda) => Arc::strong_count(lambda) > 1,
+            Expr::Vector(items) => Arc::strong_count(items) > 1,
+            Expr::Bytevector(bytes) => Arc::strong_count(bytes) > 1,
+            Expr::HashTable(table) => Arc::strong_count(table) > 1,
+            Expr::Port(port) => Arc::strong_count(port) > 1,
+            Expr::Coroutine(coroutine) => Arc::strong_count(coroutine) > 1,
+            Expr::ErrorObject(error) => Arc::strong_count(error) > 1,
+            _ => true,
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct WeakPair {
+        car: WeakValue,
+        cdr: Expr,
+    }
+
+    // A registry of the scopes created while evaluating, for the collector to walk. Values are
+    // still ordinary owned Exprs; there is no arena behind it
+    #[derive(Default)]
+    struct Heap {
+        scopes: Vec<Weak<Mutex<Scope>>>,
+        prune_at: usize,
+        collections: usize,
+        freed_scopes: usize,
+    }
+
+    impl Heap {
+        fn alloc_scope(&mut self, scope: Scope) -> Arc<Mutex<Scope>> {
+            // Forget scopes that have already been dropped once the registry doubles in size
+            if self.scopes.len() >= self.prune_at {
+                self.scopes.retain(|scope| scope.strong_count() > 0);
+                self.prune_at = (self.scopes.len() * 2).max(256);
+            }
+
+            let scope = Arc::new(Mutex::new(scope));
+            self.scopes.push(Arc::downgrade(&scope));
+            scope
+        }
+    }
+
+    // Weak hash tables hold their keys strongly until a collection drops the entries nothing else
+    // refers to. Forks share the registry, so a table made anywhere is swept by any (gc)
+    type WeakTables = Arc<Mutex<Vec<Weak<Mutex<HashMap<Expr, Expr>>>>>>;
+
+    #[derive(Default)]
+    pub struct Environment {
+        scope: Arc<Mutex<Scope>>,
+        heap: Heap,
+        weak_tables: WeakTables,
+        functions: HashMap<String, Function>,
+        closures: HashMap<String, Arc<NativeClosure>>,
+        features: HashSet<String>,
+        command_line: Vec<String>,
+        exit_handlers: Vec<Expr>,
+        random_source: Arc<Mutex<RandomState>>,
+        coroutine: Option<CoroutineLink>,
+        stepping: Option<StepState>,
+        debugger: Option<Box<Debugger>>,
+        interrupt: Arc<AtomicBool>,
+        profiling: bool,
+        profile_stats: HashMap<SymbolId, ProfileEntry>,
+        handlers: Vec<Expr>,
+        location: Option<SourceLocation>,
+        backtrace: Vec<Frame>,
+        // The thread's allocation count when the limit was set, and the limit in bytes
+        memory_limit: Option<(isize, usize)>,
+        // How deeply evaluations are nested, and the most sandbox-eval allows
+        depth: usize,
+        depth_limit: Option<usize>,
+        // Set by sandbox-eval when its time limit passes; unlike `interrupt` it stays set, so
+        // every later evaluation step fails too
+        timed_out: Option<(Arc<AtomicBool>, u64)>,
+        ports: CurrentPorts,
+        // Signature and description of native procedures, for describe
+        docs: HashMap<String, &'static str>,
+    }
+
+    impl fmt::Debug for Environment {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Environment")
+                .field("symbols", &self.scope.lock().unwrap().symbols)
+                .field("functions", &"Function HashMap") // Use a string description for the functions field
+                .field("closures", &"Closure HashMap")
+                .field("features", &self.features)
+                .field("command_line", &self.command_line)
+                .field("exit_handlers", &self.exit_handlers)
+                .finish()
+        }
+    }
+
+    impl fmt::Display for Expr {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Expr::Symbol(s) => write!(f, "{}", s),
+                Expr::Number(n) => write!(f, "{}", n),
+                Expr::Bool(b) => write!(f, "{}", if *b { "#t" } else { "#f" }),
+                Expr::Str(s) => {
+                    write!(f, "\"")?;
+                    for c in s.chars() {
+                        match c {
+                            '"' => write!(f, "\\\"")?,
+                            '\\' => write!(f, "\\\\")?,
+                            '\n' => write!(f, "\\n")?,
+                            '\t' => write!(f, "\\t")?,
+                            '\r' => write!(f, "\\r")?,
+                            _ => write!(f, "{}", c)?,
+                        }
+                    }
+                    write!(f, "\"")
+                }
+                Expr::Char(c) => match CHAR_NAMES.iter().find(|(_, named)| named == c) {
+                    Some((name, _)) => write!(f, "#\\{}", name),
+                    None => write!(f, "#\\{}", c),
+                },
+                Expr::List(l) => {
+                    let inner: Vec<String> = l.iter().map(|e| e.to_string()).collect();
+                    write!(f, "({})", inner.join(" "))
+                }
+                Expr::Values(v) => {
+                    let inner: Vec<String> = v.iter().map(|e| e.to_string()).collect();
+                    write!(f, "{}", inner.join(" "))
+                }
+                Expr::Builtin(name) => write!(f, "#<procedure {}>", name),
+                Expr::Lambda(_) => write!(f, "#<procedure>"),
+                Expr::Port(_) => write!(f, "#<port>"),
+                Expr::TimePoint(t) => write!(f, "#<time {}>", seconds_since_epoch(*t)),
+                Expr::Duration(seconds) => write!(f, "#<duration {}s>", seconds),
+                Expr::RandomSource(_) => write!(f, "#<random-source>"),
+                Expr::Thread(_) => write!(f, "#<thread>"),
+                Expr::Mutex(_) => write!(f, "#<mutex>"),
+                Expr::Coroutine(_) => write!(f, "#<coroutine>"),
+                Expr::Memoized(_) => write!(f, "#<procedure memoized>"),
+                Expr::Void => write!(f, "#<void>"),
+                Expr::Eof => write!(f, "#<eof>"),
+                Expr::Parameter(_) => write!(f, "#<parameter>"),
+                // A vector that contains itself is written with datum labels, like a circular list
+                Expr::Vector(items) => {
+                    let key = Arc::as_ptr(items) as usize;
+                    if let Some(label) = reopen(&OPEN_WRITES, key) {
+                        return write!(f, "#{}#", label);
+                    }
+                    let items = items.lock().unwrap().clone();
+                    let label = OPEN_WRITES.with(|open| open.borrow().len());
+                    let (inner, met) =
+                        with_open(&OPEN_WRITES, key, || items.iter().map(|e| e.to_string()).collect::<Vec<_>>());
+                    if met {
+                        write!(f, "#{}=", label)?;
+                    }
+                    write!(f, "#({})", inner.join(" "))
+                }
+                Expr::ErrorObject(error) => write!(f, "#<error {}>", error.report()),
+                Expr::Macro(_) => write!(f, "#<macro>"),
+                Expr::CharSet(set) => write!(f, "#<char-set {}>", set.size()),
+                Expr::Environment(_) => write!(f, "#<environment>"),
+                Expr::HashTable(_) => write!(f, "#<hash-table>"),
+                Expr::WeakPair(_) => write!(f, "#<weak-pair>"),
+                // Datum-label notation, as write would show the shared structure
+                Expr::CircularList(items, start) => {
+                    let inner: Vec<String> = ring_order(items, *start).map(|item| item.to_string()).collect();
+                    write!(f, "#0=({} . #0#)", inner.join(" "))
+                }
+                Expr::Bytevector(bytes) => {
+                    let inner: Vec<String> = bytes.lock().unwrap().iter().map(|b| b.to_string()).collect();
+                    write!(f, "#u8({})", inner.join(" "))
+                }
+                Expr::Regexp(regex) => write!(f, "#<regexp {}>", regex.as_str()),
+            }
+        }
+    }
+
+    pub fn tokenize(input: &str) -> Vec<String> {
+        tokenize_with_lines(input).into_iter().map(|(token, _)| token).collect()
+    }
+
+    // Each token paired with the 1-based line it starts on
+    pub fn tokenize_with_lines(input: &str) -> Vec<(String, usize)> {
+        tokenize_with_positions(input).into_iter().map(|(token, line, _)| (token, line)).collect()
+    }
+
+    // Tracks the 1-based line and column of the next character
+    struct Cursor<'a> {
+        chars: std::iter::Peekable<std::str::Chars<'a>>,
+        line: usize,
+        column: usize,
+    }
+
+    impl Cursor<'_> {
+        fn next(&mut self) -> Option<char> {
+            let c = self.chars.next()?;
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            Some(c)
+        }
+
+        fn peek(&mut self) -> Option<&char> {
+            self.chars.peek()
+        }
+    }
+
+    // Each token with the 1-based line and column it starts at
+    pub fn tokenize_with_positions(input: &str) -> Vec<(String, usize, usize)> {
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let mut current_start = (1, 1);
+        let mut cursor = Cursor { chars: input.chars().peekable(), line: 1, column: 1 };
+
+        loop {
+            let start = (cursor.line, cursor.column);
+            let c = match cursor.next() {
+                Some(c) => c,
+                None => break,
+            };
+            let mut push = |token: String, (line, column): (usize, usize)| tokens.push((token, line, column));
+
+            if c == '(' && current == "#u8" {
+                push("#u8(".to_string(), current_start);
+                current.clear();
+                continue;
+            }
+            if !current.is_empty() && (matches!(c, '(' | ')' | '\'' | '"' | ';') || c.is_whitespace()) {
+                push(std::mem::take(&mut current), current_start);
+            }
+            if current.is_empty() {
+                current_start = start;
+            }
+
+            match c {
+                '(' | ')' | '\'' => push(c.to_string(), start),
+                '"' => {
+                    // String tokens keep their quotes and escapes; `parse` decodes them
+                    let mut literal = String::from('"');
+                    while let Some(c) = cursor.next() {
+                        literal.push(c);
+                        if c == '\\' {
+                            if let Some(escaped) = cursor.next() {
+                                literal.push(escaped);
+                            }
+                        } else if c == '"' {
+                            break;
+                        }
+                    }
+                    push(literal, start);
+                }
+                ';' => {
+                    // Comments run to the end of the line
+                    while cursor.peek().is_some_and(|&c| c != '\n') {
+                        cursor.next();
+                    }
+                }
+                c if c.is_whitespace() => {}
+                '#' if current.is_empty() && cursor.peek() == Some(&'(') => {
+                    push("#(".to_string(), start);
+                    cursor.next();
+                }
+                '#' if current.is_empty() && cursor.peek() == Some(&'\\') => {
+                    // The character after `#\` belongs to the literal even if it is a delimiter
+                    current.push_str("#\\");
+                    cursor.next();
+                    if let Some(c) = cursor.next() {
+                        current.push(c);
+                    }
+                }
+                _ => current.push(c),
+            }
+        }
+
+        if !current.is_empty() {
+            tokens.push((current, current_start.0, current_start.1));
+        }
+
+        tokens
+    }
+
+    fn parse_string_literal(token: &str) -> Result<String, LispError> {
+        let mut value = String::new();
+        let mut chars = token.chars().skip(1);
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('0') => value.push('\0'),
+                    Some(escaped) => value.push(escaped),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+
+        Err(LispError::Read(format!("Unterminated string: {}", token)))
+    }
+
+    const CHAR_NAMES: [(&str, char); 10] = [
+        ("space", ' '),
+        ("newline", '\n'),
+        ("tab", '\t'),
+        ("return", '\r'),
+        ("nul", '\0'),
+        ("null", '\0'),
+        ("alarm", '\x07'),
+        ("backspace", '\x08'),
+        ("escape", '\x1b'),
+        ("delete", '\x7f'),
+    ];
+
+    fn parse_char_literal(token: &str) -> Result<char, LispError> {
+        let body = &token[2..];
+
+        let mut chars = body.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(c);
+        }
+
+        if let Some((_, c)) = CHAR_NAMES.iter().find(|(name, _)| *name == body) {
+            return Ok(*c);
+        }
+
+        if let Some(hex) = body.strip_prefix('x') {
+            if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                return Ok(c);
+            }
+        }
+
+        Err(LispError::Read(format!("Invalid character literal: {}", token)))
+    }
+
+    // A numeric literal, optionally prefixed with a radix (#b #o #d #x) and an exactness (#e #i) in
+    // either order. Every number is a float, so exactness is accepted but changes nothing
+    // Every number is a double, so #i changes nothing and #e only admits integers, the one kind of
+    // double that is exact
+    fn parse_number(token: &str) -> Option<f64> {
+        let mut digits = token;
+        let mut radix = None;
+        let mut exactness = None;
+        while let Some(prefix) = digits.strip_prefix('#') {
+            let mut chars = prefix.chars();
+            match chars.next()?.to_ascii_lowercase() {
+                'b' if radix.is_none() => radix = Some(2),
+                'o' if radix.is_none() => radix = Some(8),
+                'd' if radix.is_none() => radix = Some(10),
+                'x' if radix.is_none() => radix = Some(16),
+                'e' | 'i' if exactness.is_none() => exactness = Some(prefix.starts_with(['e', 'E'])),
+                _ => return None,
+            }
+            digits = chars.as_str();
+        }
+
+        let number = match radix {
+            None | Some(10) => digits.parse::<f64>().ok(),
+            Some(radix) => {
+                // from_str_radix would also take a sign after a sign, so the sign is split off first
+                let (sign, magnitude) = match digits.strip_prefix('-') {
+                    Some(magnitude) => (-1.0, magnitude),
+                    None => (1.0, digits.strip_prefix('+').unwrap_or(digits)),
+                };
+                if magnitude.is_empty() || !magnitude.chars().all(|c| c.is_digit(radix)) {
+                    return None;
+                }
+                u64::from_str_radix(magnitude, radix).ok().map(|n| sign * n as f64)
+            }
+        };
+        number.filter(|n| exactness != Some(true) || n.fract() == 0.0)
+    }
+
+    // A literal like #e1.5, which is a number but not one that can be exact
+    fn is_inexact_exact_literal(token: &str) -> bool {
+        let lower = token.to_ascii_lowercase();
+        lower.starts_with('#') && lower.contains("#e") && parse_number(&lower.replacen("#e", "", 1)).is_some()
+    }
+
+    // Every datum in `input`; comments and whitespace between them are skipped by the tokenizer
+    pub fn parse_all(input: &str) -> Result<Vec<Expr>, LispError> {
+        let tokens = tokenize(input);
+        let mut remaining_tokens = &tokens[..];
+        let mut exprs = Vec::new();
+
+        while !remaining_tokens.is_empty() {
+            let (expr, rest) = parse(remaining_tokens)?;
+            exprs.push(expr);
+            remaining_tokens = rest;
+        }
+
+        Ok(exprs)
+    }
+
+    pub fn parse(tokens: &[String]) -> Result<(Expr, &[String]), LispError> {
+        if tokens.is_empty() {
+            return Err(LispError::Read("Unexpected EOF".to_string()));
+        }
+
+        let (token, rest) = tokens.split_first().unwrap();
+
+        let expr = match &token[..] {
+            "(" | "#(" | "#u8(" => {
+                let mut list = Vec::new();
+                let mut remaining_tokens = rest;
+
+                while let Some(token) = remaining_tokens.first() {
+                    if token == ")" {
+                        break;
+                    }
+
+                    let (parsed_expr, new_remaining_tokens) = parse(remaining_tokens)?;
+                    list.push(parsed_expr);
+                    remaining_tokens = new_remaining_tokens;
+                }
+
+                if remaining_tokens.is_empty() {
+                    return Err(LispError::Read("Unexpected EOF".to_string()));
+                }
+
+                let (_, new_remaining_tokens) = remaining_tokens.split_first().unwrap();
+                if token == "#(" {
+                    (make_vector_value(list), new_remaining_tokens)
+                } else if token == "#u8(" {
+                    let bytes = list.iter().map(byte_value).collect::<Result<Vec<u8>, LispError>>()?;
+                    (make_bytevector_value(bytes), new_remaining_tokens)
+                } else {
+                    (Expr::List(list), new_remaining_tokens)
+                }
+            }
+            ")" => {
+                return Err(LispError::Read("Unexpected )".to_string()));
+            }
+            "'" => {
+                let (quoted, new_remaining_tokens) = parse(rest)?;
+                (
+                    Expr::List(vec![Expr::Symbol("quote".into()), quoted]),
+                    new_remaining_tokens,
+                )
+            }
+            _ => {
+                let atom = if token.starts_with('"') {
+                    Expr::Str(parse_string_literal(token)?)
+                } else if token.starts_with("#\\") {
+                    Expr::Char(parse_char_literal(token)?)
+                } else if let Some(number) = parse_number(token) {
+                    Expr::Number(number)
+                } else if is_inexact_exact_literal(token) {
+                    return Err(LispError::Read(format!("Cannot read {} as an exact number: only integers are exact", token)));
+                } else if token == "#t" || token == "#true" {
+                    Expr::Bool(true)
+                } else if token == "#f" || token == "#false" {
+                    Expr::Bool(false)
+                } else {
+                    Expr::Symbol(SymbolId::intern(token))
+                };
+
+                (atom, rest)
+            }
+        };
+
+        Ok(expr)
+    }
+
+
+    fn add(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut sum = 0.0;
+
+        for arg in args {
+            match arg {
+                Expr::Number(n) => sum += n,
+                _ => return Err("Invalid argument type for addition".into()),
+            }
+        }
+
+        Ok(Expr::Number(sum))
+    }
+
+    fn subtract(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.is_empty() {
+            return Err("At least one argument is required for subtraction".into());
+        }
+
+        let mut args_iter = args.iter();
+        let first_arg = args_iter.next().unwrap();
+
+        let mut difference = match first_arg {
+            Expr::Number(n) => *n,
+            _ => return Err("Invalid argument type for subtraction".into()),
+        };
+
+        for arg in args_iter {
+            match arg {
+                Expr::Number(n) => difference -= n,
+                _ => return Err("Invalid argument type for subtraction".into()),
+            }
+        }
+
+        Ok(Expr::Number(difference))
+    }
+
+    fn multiply(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut product = 1.0;
+
+        for arg in args {
+            match arg {
+                Expr::Number(n) => product *= n,
+                _ => return Err("Invalid argument type for multiplication".into()),
+            }
+        }
+
+        Ok(Expr::Number(product))
+    }
+
+    fn divide(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut numbers = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg {
+                Expr::Number(n) => numbers.push(*n),
+                _ => return Err("Invalid argument type for division".into()),
+            }
+        }
+
+        // A single argument gives its reciprocal
+        let (first, divisors) = match numbers.split_first() {
+            Some((first, [])) => (1.0, std::slice::from_ref(first)),
+            Some((first, divisors)) => (*first, divisors),
+            None => return Err("At least one argument is required for division".into()),
+        };
+
+        if divisors.contains(&0.0) {
+            return Err("Division by zero".into());
+        }
+        Ok(Expr::Number(divisors.iter().fold(first, |quotient, d| quotient / d)))
+    }
+
+    fn equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 2 {
+            return Err("Equality function requires exactly 2 arguments".into());
+        }
+    
+        let a = match args[0] {
+            Expr::Number(n) => n,
+            _ => return Err("Invalid argument type for equality function".into()),
+        };
+        let b = match args[1] {
+            Expr::Number(n) => n,
+            _ => return Err("Invalid argument type for equality function".into()),
+        };
+    
+        Ok(Expr::Bool(a == b))
+    }
+
+    // Each argument must stand in `holds` relation to the next, as in `(< 1 2 3)`
+    fn compare_numbers(args: &[Expr], name: &str, holds: fn(f64, f64) -> bool) -> Result<Expr, LispError> {
+        if args.len() < 2 {
+            return Err(format!("'{}' requires at least 2 arguments", name).into());
+        }
+
+        let mut numbers = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg {
+                Expr::Number(n) => numbers.push(*n),
+                _ => return Err(format!("Invalid argument type for '{}'", name).into()),
+            }
+        }
+
+        Ok(Expr::Bool(numbers.windows(2).all(|pair| holds(pair[0], pair[1]))))
+    }
+
+    fn less_than(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        compare_numbers(args, "<", |a, b| a < b)
+    }
+
+    fn greater_than(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        compare_numbers(args, ">", |a, b| a > b)
+    }
+
+    fn less_or_equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        compare_numbers(args, "<=", |a, b| a <= b)
+    }
+
+    fn greater_or_equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        compare_numbers(args, ">=", |a, b| a >= b)
+    }
+
+    fn not(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(!is_truthy(arg))),
+            _ => Err("'not' requires exactly 1 argument".into()),
+        }
+    }
+    
+
+    fn car(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("Expected exactly one argument for car".into());
+        }
+
+        match &args[0] {
+            Expr::List(list) if !list.is_empty() => Ok(list[0].clone()),
+            Expr::CircularList(items, start) => Ok(items[*start].clone()),
+            _ => Err("Invalid argument type for car".into()),
+        }
+    }
+
+    fn cdr(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("Expected exactly one argument for cdr".into());
+        }
+
+        match &args[0] {
+            // The cdr of a dotted pair (a . b) is b itself
+            Expr::List(list) if list.len() == 3 && is_dotted(list) => Ok(list[2].clone()),
+            Expr::List(list) => Ok(Expr::List(list[1..].to_vec())),
+            Expr::CircularList(items, start) => Ok(Expr::CircularList(items.clone(), (start + 1) % items.len())),
+            _ => Err("Invalid argument type for cdr".into()),
+        }
+    }
+
+    fn list_length(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("List length function requires exactly 1 argument".into());
+        }
+    
+        let list = match &args[0] {
+            Expr::List(l) if is_dotted(l) => return Err("A dotted list has no length".into()),
+            Expr::List(l) => l,
+            Expr::CircularList(..) => return Err("A circular list has no length".into()),
+            _ => return Err("Invalid argument type for list length function".into()),
+        };
+    
+        Ok(Expr::Number(list.len() as f64))
+    }
+    
+    fn list_sum(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("List sum function requires exactly 1 argument".into());
+        }
+    
+        let list = match &args[0] {
+            Expr::List(l) => l,
+            _ => return Err("Invalid argument type for list sum function".into()),
+        };
+    
+        let mut sum = 0.0;
+        for item in list {
+            match item {
+                Expr::Number(n) => sum += n,
+                _ => return Err("Invalid element type for list sum function".into()),
+            }
+        }
+    
+        Ok(Expr::Number(sum))
+    }
+
+    fn define(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 2 {
+            return Err("Exactly 2 arguments are required for 'define'".into());
+        }
+    
+        let symbol = match &args[0] {
+            Expr::Symbol(s) => s,
+            _ => return Err("First argument of 'define' must be a symbol".into()),
+        };
+    
+        let value = eval(&args[1], env)?;
+    
+        env.define(*symbol, value.clone());
+    
+        Ok(value)
+    }
+
+    fn print(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("Exactly 1 argument is required for 'print'".into());
+        }
+    
+        let value = eval(&args[0], env)?;
+        write_to_port(&format!("{}\n", value), None, "print", env)?;
+    
+        Ok(value)
+    }
+
+    // Human-readable form used by `display`: strings and characters appear without quoting
+    pub fn display_string(expr: &Expr) -> String {
+        match expr {
+            Expr::Str(s) => s.clone(),
+            Expr::Char(c) => c.to_string(),
+            Expr::List(l) => {
+                let inner: Vec<String> = l.iter().map(display_string).collect();
+                format!("({})", inner.join(" "))
+            }
+            Expr::Values(v) => {
+                let inner: Vec<String> = v.iter().map(display_string).collect();
+                inner.join(" ")
+            }
+            _ => expr.to_string(),
+        }
+    }
+
+    pub fn pretty_print(expr: &Expr, width: usize) -> String {
+        pretty_print_indented(expr, width, 2)
+    }
+
+    // `indent` is how far the body of a special form, or operands that cannot follow the
+    // operator, sit in from the opening paren
+    pub fn pretty_print_indented(expr: &Expr, width: usize, indent: usize) -> String {
+        let mut out = String::new();
+        pretty_print_into(expr, width, indent, &mut out);
+        out
+    }
+
+    // COLUMNS when the shell exports it, otherwise the traditional 80
+    pub fn terminal_width() -> usize {
+        std::env::var("COLUMNS").ok().and_then(|columns| columns.trim().parse().ok()).filter(|w| *w > 0).unwrap_or(80)
+    }
+
+    // How many operands of a special form stay on the keyword's line, ahead of its indented body
+    fn special_form_header(keyword: &str, operands: &[Expr]) -> Option<usize> {
+        match keyword {
+            "begin" => Some(0),
+            "let" if matches!(operands.first(), Some(Expr::Symbol(_))) => Some(2),
+            "define" | "lambda" | "let" | "let*" | "letrec" | "letrec*" | "let-values" | "let*-values"
+            | "when" | "unless" | "case" | "parameterize" | "define-syntax" | "let-syntax" | "letrec-syntax"
+            | "syntax-rules" | "fluid-let" | "guard" | "define-values" | "when-let" | "if-let" => Some(1),
+            "do" | "receive" => Some(2),
+            _ => None,
+        }
+    }
+
+    fn current_column(out: &str) -> usize {
+        out.rsplit('\n').next().map_or(0, |line| line.chars().count())
+    }
+
+    fn pretty_print_into(expr: &Expr, width: usize, indent: usize, out: &mut String) {
+        let column = current_column(out);
+        let flat = expr.to_string();
+        let items = match expr {
+            Expr::List(items) if !items.is_empty() && column + flat.chars().count() > width => items,
+            _ => {
+                out.push_str(&flat);
+                return;
+            }
+        };
+
+        out.push('(');
+        pretty_print_into(&items[0], width, indent, out);
+        let operands = &items[1..];
+
+        let break_line = |out: &mut String, at: usize| {
+            out.push('\n');
+            out.push_str(&" ".repeat(at));
+        };
+
+        // Special forms keep their header by the keyword and indent the body
+        let header = match &items[0] {
+            Expr::Symbol(keyword) => special_form_header(keyword.as_str(), operands),
+            _ => None,
+        };
+        if let Some(header) = header {
+            let header = header.min(operands.len());
+            for item in &operands[..header] {
+                out.push(' ');
+                pretty_print_into(item, width, indent, out);
+            }
+            for item in &operands[header..] {
+                break_line(out, column + indent);
+                pretty_print_into(item, width, indent, out);
+            }
+            out.push(')');
+            return;
+        }
+
+        // Calls line their operands up under the first, when it fits beside the operator. A list
+        // of lists, like a let's bindings, is lined up under its first element
+        let align = current_column(out) + 1;
+        let nested = matches!(items[0], Expr::List(_));
+        let beside = !nested && operands.first().is_some_and(|first| align + first.to_string().chars().count() <= width);
+        for (i, item) in operands.iter().enumerate() {
+            if i == 0 && beside {
+                out.push(' ');
+            } else if nested {
+                break_line(out, column + 1);
+            } else {
+                break_line(out, if beside { align } else { column + indent });
+            }
+            pretty_print_into(item, width, indent, out);
+        }
+        out.push(')');
+    }
+
+    fn pretty_print_builtin(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let width = match args {
+            [_] => 80,
+            [_, Expr::Number(n)] if *n > 0.0 && n.fract() == 0.0 => *n as usize,
+            _ => return Err("'pretty-print' requires an expression and an optional width".into()),
+        };
+
+        write_to_port(&format!("{}\n", pretty_print(&args[0], width)), None, "pretty-print", env)?;
+        Ok(Expr::Void)
+    }
+
+    // (pretty-print-to-string expr [width [indent]])
+    fn pretty_print_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let size = |n: &Expr| match n {
+            Expr::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        };
+        let (width, indent) = match args {
+            [_] => (Some(terminal_width()), Some(2)),
+            [_, width] => (size(width), Some(2)),
+            [_, width, indent] => (size(width), size(indent)),
+            _ => return Err("'pretty-print-to-string' requires an expression, an optional width and indent".into()),
+        };
+
+        match (width, indent) {
+            (Some(width), Some(indent)) if width > 0 => Ok(Expr::Str(pretty_print_indented(&args[0], width, indent))),
+            _ => Err("Invalid width or indent for pretty-print-to-string".into()),
+        }
+    }
+
+    fn pp(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [expr] => {
+                write_to_port(&format!("{}\n", pretty_print(expr, terminal_width())), None, "pp", env)?;
+                Ok(Expr::Void)
+            }
+            _ => Err("'pp' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn write_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("'write-to-string' requires exactly 1 argument".into());
+        }
+
+        Ok(Expr::Str(args[0].to_string()))
+    }
+
+    fn read_from_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let input = match args {
+            [Expr::Str(s)] => s,
+            _ => return Err("'read-from-string' requires exactly 1 string argument".into()),
+        };
+
+        let tokens = tokenize(input);
+        let (expr, rest) = parse(&tokens)?;
+        if !rest.is_empty() {
+            return Err(LispError::Read(format!("Unexpected input after datum: {}", rest.join(" "))));
+        }
+
+        Ok(expr)
+    }
+
+    // Without an explicit port, output goes wherever current-output-port points
+    fn write_to_port(text: &str, port: Option<&Expr>, name: &str, env: &Environment) -> Result<Expr, LispError> {
+        match port.unwrap_or(&env.ports.output) {
+            Expr::Port(port) => match &mut *port.lock().unwrap() {
+                Port::StringOutput(buffer) => buffer.push_str(text),
+                Port::Stdout => print!("{}", text),
+                Port::Stderr => eprint!("{}", text),
+                Port::StringInput { .. } | Port::Stdin => return Err(format!("Cannot {} to an input port", name).into()),
+                Port::Closed => return Err(format!("Cannot {} to a closed port", name).into()),
+            },
+            _ => return Err(format!("Invalid port argument for {}", name).into()),
+        }
+
+        Ok(Expr::Void)
+    }
+
+    fn write(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] | [obj, _] => write_to_port(&obj.to_string(), args.get(1), "write", env),
+            _ => Err("'write' requires an object and an optional port".into()),
+        }
+    }
+
+    fn display(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] | [obj, _] => write_to_port(&display_string(obj), args.get(1), "display", env),
+            _ => Err("'display' requires an object and an optional port".into()),
+        }
+    }
+
+    fn newline(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() > 1 {
+            return Err("'newline' takes an optional port".into());
+        }
+
+        write_to_port("\n", args.first(), "newline", env)
+    }
+
+    fn open_input_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(text)] => Ok(console_port(Port::StringInput { text: text.chars().collect(), position: 0 })),
+            _ => Err("'open-input-string' requires a string".into()),
+        }
+    }
+
+    // Reads from `port`, or current-input-port when none is given. `take` gets the unread characters
+    // and returns the result along with how many of them it consumed
+    fn read_from_port(
+        args: &[Expr],
+        env: &Environment,
+        name: &str,
+        take: fn(&[char]) -> (Expr, usize),
+    ) -> Result<Expr, LispError> {
+        let port = match args {
+            [] => &env.ports.input,
+            [port] => port,
+            _ => return Err(format!("'{}' takes an optional port", name).into()),
+        };
+
+        match port {
+            Expr::Port(port) => match &mut *port.lock().unwrap() {
+                Port::StringInput { text, position } => {
+                    let (result, consumed) = take(&text[*position..]);
+                    *position += consumed;
+                    Ok(result)
+                }
+                Port::Stdin => {
+                    // The console is read a line at a time; a character read drops the rest of the line
+                    let mut line = String::new();
+                    std::io::stdin().read_line(&mut line).map_err(|e| format!("Cannot read from stdin: {}", e))?;
+                    let chars: Vec<char> = line.chars().collect();
+                    Ok(take(&chars).0)
+                }
+                Port::StringOutput(_) | Port::Stdout | Port::Stderr => {
+                    Err(format!("Cannot {} from an output port", name).into())
+                }
+                Port::Closed => Err(format!("Cannot {} from a closed port", name).into()),
+            },
+            _ => Err(format!("Invalid port argument for {}", name).into()),
+        }
+    }
+
+    fn read_line(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        read_from_port(args, env, "read-line", |chars| match chars.iter().position(|c| *c == '\n') {
+            _ if chars.is_empty() => (Expr::Eof, 0),
+            Some(end) => (Expr::Str(chars[..end].iter().collect()), end + 1),
+            None => (Expr::Str(chars.iter().collect()), chars.len()),
+        })
+    }
+
+    fn read_char(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        read_from_port(args, env, "read-char", |chars| match chars.first() {
+            Some(c) => (Expr::Char(*c), 1),
+            None => (Expr::Eof, 0),
+        })
+    }
+
+    fn peek_char(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        read_from_port(args, env, "peek-char", |chars| (chars.first().map_or(Expr::Eof, |c| Expr::Char(*c)), 0))
+    }
+
+    fn eof_object(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [] => Ok(Expr::Eof),
+            _ => Err("'eof-object' takes no arguments".into()),
+        }
+    }
+
+    fn is_eof_object(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] => Ok(Expr::Bool(*obj == Expr::Eof)),
+            _ => Err("'eof-object?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn current_input_port(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [] => Ok(env.ports.input.clone()),
+            _ => Err("'current-input-port' takes no arguments".into()),
+        }
+    }
+
+    fn current_output_port(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [] => Ok(env.ports.output.clone()),
+            _ => Err("'current-output-port' takes no arguments".into()),
+        }
+    }
+
+    fn current_error_port(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [] => Ok(env.ports.error.clone()),
+            _ => Err("'current-error-port' takes no arguments".into()),
+        }
+    }
+
+    // Calls `thunk` with one of the current ports pointing at `port`, putting the old one back
+    // whether the thunk returns or raises
+    fn with_redirection(
+        args: &[Expr],
+        env: &mut Environment,
+        name: &str,
+        slot: fn(&mut CurrentPorts) -> &mut Expr,
+    ) -> Result<Expr, LispError> {
+        let (port, thunk) = match args {
+            [port @ Expr::Port(_), thunk] if is_procedure(thunk) => (port.clone(), thunk),
+            _ => return Err(format!("'{}' requires a port and a thunk", name).into()),
+        };
+
+        let saved = std::mem::replace(slot(&mut env.ports), port);
+        let result = apply_procedure(thunk, &[], env);
+        *slot(&mut env.ports) = saved;
+        result
+    }
+
+    fn with_input_redirection(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        with_redirection(args, env, "with-input-redirection", |ports| &mut ports.input)
+    }
+
+    fn with_output_redirection(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        with_redirection(args, env, "with-output-redirection", |ports| &mut ports.output)
+    }
+
+    fn with_error_redirection(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        with_redirection(args, env, "with-error-redirection", |ports| &mut ports.error)
+    }
+
+    fn open_output_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'open-output-string' takes no arguments".into());
+        }
+
+        Ok(Expr::Port(Arc::new(Mutex::new(Port::StringOutput(String::new())))))
+    }
+
+    fn get_output_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Port(port)] => match &*port.lock().unwrap() {
+                Port::StringOutput(buffer) => Ok(Expr::Str(buffer.clone())),
+                Port::Closed => Err("Cannot read the output of a closed port".into()),
+                _ => Err("'get-output-string' requires a string output port".into()),
+            },
+            _ => Err("'get-output-string' requires a string output port".into()),
+        }
+    }
+
+    fn close_port(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Port(port)] => {
+                *port.lock().unwrap() = Port::Closed;
+                Ok(Expr::Void)
+            }
+            _ => Err("'close-port' requires a port".into()),
+        }
+    }
+
+    fn call_with_string_output_port(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("'call-with-string-output-port' requires exactly 1 argument".into());
+        }
+
+        let port = Arc::new(Mutex::new(Port::StringOutput(String::new())));
+        apply_procedure(&args[0], &[Expr::Port(port.clone())], env)?;
+
+        // The port is closed once `proc` returns, so stray writes to it fail loudly
+        let contents = std::mem::replace(&mut *port.lock().unwrap(), Port::Closed);
+        match contents {
+            Port::StringOutput(buffer) => Ok(Expr::Str(buffer)),
+            _ => Err("String output port was closed before it could be read".into()),
+        }
+    }
+
+    // Lists are vectors, so a dotted pair `(a . b)` is kept as a three-element list, the way the
+    // reader leaves it. A proper list whose second to last element is the symbol `.` cannot be
+    // told apart from one
+    fn make_pair(first: Expr, second: Expr) -> Expr {
+        Expr::List(vec![first, Expr::Symbol(".".into()), second])
+    }
+
+    // Whether a list is a dotted pair or ends in a dotted tail, like `(a b . c)`
+    fn is_dotted(list: &[Expr]) -> bool {
+        list.len() >= 3 && list[list.len() - 2] == Expr::Symbol(".".into())
+    }
+
+    // Objects become hash tables keyed by strings, so an empty object stays distinct from an empty array
+    fn from_json(value: serde_json::Value) -> Expr {
+        match value {
+            serde_json::Value::Null => Expr::Symbol("null".into()),
+            serde_json::Value::Bool(b) => Expr::Bool(b),
+            serde_json::Value::Number(n) => Expr::Number(n.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(s) => Expr::Str(s),
+            serde_json::Value::Array(items) => Expr::List(items.into_iter().map(from_json).collect()),
+            serde_json::Value::Object(fields) => make_hash_table_value(
+                fields.into_iter().map(|(key, value)| (Expr::Str(key), from_json(value))).collect(),
+            ),
+        }
+    }
+
+    // An association list entry of the form `(key . value)` with a string or symbol key
+    fn json_object_entry(expr: &Expr) -> Option<(String, &Expr)> {
+        match expr {
+            Expr::List(parts) if parts.len() == 3 && is_dotted(parts) => {
+                match &parts[0] {
+                    Expr::Str(key) => Some((key.clone(), &parts[2])),
+                    Expr::Symbol(key) => Some((key.to_string(), &parts[2])),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn json_key(key: &Expr) -> Result<String, LispError> {
+        match key {
+            Expr::Str(key) => Ok(key.clone()),
+            Expr::Symbol(key) => Ok(key.to_string()),
+            _ => Err(format!("Cannot use {} as a JSON object key", key).into()),
+        }
+    }
+
+    // `open` holds the hash tables and vectors being converted, so a structure that contains
+    // itself is an error instead of endless recursion
+    fn to_json(expr: &Expr, open: &mut Vec<usize>) -> Result<serde_json::Value, LispError> {
+        let identity = match expr {
+            Expr::HashTable(table) => Some(Arc::as_ptr(table) as usize),
+            Expr::Vector(items) => Some(Arc::as_ptr(items) as usize),
+            _ => None,
+        };
+        if let Some(identity) = identity {
+            if open.contains(&identity) {
+                return Err("Cannot convert a circular structure to JSON".into());
+            }
+            open.push(identity);
+        }
+
+        let value = match expr {
+            // Integral numbers are written without a fractional part
+            Expr::Number(n) if n.fract() == 0.0 && n.abs() < 9007199254740992.0 => serde_json::Value::from(*n as i64),
+            Expr::Number(n) => match serde_json::Number::from_f64(*n) {
+                Some(n) => serde_json::Value::Number(n),
+                None => return Err(format!("Cannot convert {} to JSON", n).into()),
+            },
+            Expr::Bool(b) => serde_json::Value::Bool(*b),
+            Expr::Str(s) => serde_json::Value::String(s.clone()),
+            Expr::Char(c) => serde_json::Value::String(c.to_string()),
+            Expr::Symbol(s) if s == "null" || s == "nil" => serde_json::Value::Null,
+            Expr::HashTable(table) => {
+                let entries: Vec<(Expr, Expr)> =
+                    table.lock().unwrap().iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+                let mut fields = serde_json::Map::new();
+                for (key, value) in &entries {
+                    fields.insert(json_key(key)?, to_json(value, open)?);
+                }
+                serde_json::Value::Object(fields)
+            }
+            Expr::Vector(items) => {
+                let items = items.lock().unwrap().clone();
+                serde_json::Value::Array(items.iter().map(|item| to_json(item, open)).collect::<Result<_, _>>()?)
+            }
+            Expr::List(items) if !items.is_empty() && items.iter().all(|item| json_object_entry(item).is_some()) => {
+                let mut fields = serde_json::Map::new();
+                for item in items {
+                    let (key, value) = json_object_entry(item).unwrap();
+                    fields.insert(key, to_json(value, open)?);
+                }
+                serde_json::Value::Object(fields)
+            }
+            Expr::List(items) => {
+                serde_json::Value::Array(items.iter().map(|item| to_json(item, open)).collect::<Result<_, _>>()?)
+            }
+            _ => return Err(format!("Cannot convert {} to JSON", expr).into()),
+        };
+
+        if identity.is_some() {
+            open.pop();
+        }
+        Ok(value)
+    }
+
+    fn json_to_lisp(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let input = match args {
+            [Expr::Str(s)] => s,
+            _ => return Err("'json->lisp' requires exactly 1 string argument".into()),
+        };
+
+        serde_json::from_str(input)
+            .map(from_json)
+            .map_err(|e| format!("Invalid JSON: {}", e).into())
+    }
+
+    fn lisp_to_json(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("'lisp->json' requires exactly 1 argument".into());
+        }
+
+        Ok(Expr::Str(to_json(&args[0], &mut Vec::new())?.to_string()))
+    }
+
+    // Shared so that connections are reused; the timeouts keep an unresponsive server from
+    // hanging the interpreter
+    static HTTP_AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+        ureq::Agent::config_builder()
+            .timeout_connect(Some(std::time::Duration::from_secs(10)))
+            .timeout_global(Some(std::time::Duration::from_secs(30)))
+            .http_status_as_error(false)
+            .build()
+            .into()
+    });
+
+    type HttpResult = Result<ureq::http::Response<ureq::Body>, ureq::Error>;
+
+    fn http_response_to_expr(url: &str, response: HttpResult) -> Result<Expr, LispError> {
+        let mut response = response.map_err(|e| LispError::from(format!("HTTP request to {} failed: {}", url, e)))?;
+        let status = response.status().as_u16();
+        if !(200..300).contains(&status) {
+            return Err(format!("HTTP request to {} failed with status {}", url, status).into());
+        }
+
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                make_pair(Expr::Str(name.to_string()), Expr::Str(String::from_utf8_lossy(value.as_bytes()).to_string()))
+            })
+            .collect();
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| LispError::from(format!("HTTP request to {} failed: {}", url, e)))?;
+
+        Ok(Expr::List(vec![Expr::Number(status as f64), Expr::Str(body), Expr::List(headers)]))
+    }
+
+    fn http_get(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let url = match args {
+            [Expr::Str(url)] => url,
+            _ => return Err("'http-get' requires exactly 1 string argument".into()),
+        };
+
+        http_response_to_expr(url, HTTP_AGENT.get(url.as_str()).call())
+    }
+
+    fn http_post(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (url, body) = match args {
+            [Expr::Str(url), Expr::Str(body)] => (url, body),
+            _ => return Err("'http-post' requires a URL string and a body string".into()),
+        };
+
+        http_response_to_expr(url, HTTP_AGENT.post(url.as_str()).send(body.as_str()))
+    }
+
+    fn path_argument<'a>(args: &'a [Expr], name: &str) -> Result<&'a str, LispError> {
+        match args {
+            [Expr::Str(path)] => Ok(path),
+            _ => Err(format!("'{}' requires exactly 1 path string", name).into()),
+        }
+    }
+
+    fn file_error(action: &str, path: &str, error: std::io::Error) -> LispError {
+        LispError::FileError(format!("Cannot {} {}: {}", action, path, error))
+    }
+
+    fn file_exists(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "file-exists?")?;
+        Ok(Expr::Bool(std::path::Path::new(path).exists()))
+    }
+
+    fn delete_file(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "delete-file")?;
+        std::fs::remove_file(path).map_err(|e| file_error("delete", path, e))?;
+        Ok(Expr::Void)
+    }
+
+    fn rename_file(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (from, to) = match args {
+            [Expr::Str(from), Expr::Str(to)] => (from, to),
+            _ => return Err("'rename-file' requires two path strings".into()),
+        };
+
+        std::fs::rename(from, to).map_err(|e| file_error("rename", from, e))?;
+        Ok(Expr::Void)
+    }
+
+    fn directory_files(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "directory-files")?;
+
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(path).map_err(|e| file_error("list", path, e))? {
+            let entry = entry.map_err(|e| file_error("list", path, e))?;
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+
+        Ok(Expr::List(names.into_iter().map(Expr::Str).collect()))
+    }
+
+    fn make_directory(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "make-directory")?;
+        std::fs::create_dir(path).map_err(|e| file_error("create directory", path, e))?;
+        Ok(Expr::Void)
+    }
+
+    fn file_size(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "file-size")?;
+        let metadata = std::fs::metadata(path).map_err(|e| file_error("stat", path, e))?;
+        Ok(Expr::Number(metadata.len() as f64))
+    }
+
+    // Seconds since the Unix epoch
+    fn file_modification_time(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "file-modification-time")?;
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| file_error("stat", path, e))?;
+
+        let seconds = modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or(0.0);
+        Ok(Expr::Number(seconds))
+    }
+
+    fn command_line(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'command-line' takes no arguments".into());
+        }
+
+        Ok(Expr::List(env.command_line.iter().cloned().map(Expr::Str).collect()))
+    }
+
+    fn get_environment_variable(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let name = match args {
+            [Expr::Str(name)] => name,
+            _ => return Err("'get-environment-variable' requires exactly 1 string argument".into()),
+        };
+
+        Ok(match std::env::var(name) {
+            Ok(value) => Expr::Str(value),
+            Err(_) => Expr::Bool(false),
+        })
+    }
+
+    fn get_environment_variables(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'get-environment-variables' takes no arguments".into());
+        }
+
+        let mut vars: Vec<(String, String)> = std::env::vars().collect();
+        vars.sort();
+
+        Ok(Expr::List(
+            vars.into_iter()
+                .map(|(name, value)| make_pair(Expr::Str(name), Expr::Str(value)))
+                .collect(),
+        ))
+    }
+
+    fn exit_code(args: &[Expr], name: &str) -> Result<i32, LispError> {
+        match args {
+            [] | [Expr::Bool(true)] => Ok(0),
+            [Expr::Bool(false)] => Ok(1),
+            [Expr::Number(n)] if n.fract() == 0.0 => Ok(*n as i32),
+            _ => Err(format!("'{}' takes an optional integer or boolean exit code", name).into()),
+        }
+    }
+
+    fn at_exit(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [thunk] if is_procedure(thunk) => {
+                env.exit_handlers.push(thunk.clone());
+                Ok(Expr::Void)
+            }
+            _ => Err("'at-exit' requires a procedure of no arguments".into()),
+        }
+    }
+
+    // Runs the `at-exit` thunks, most recently registered first, before leaving the process
+    fn exit(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let code = exit_code(args, "exit")?;
+        env.run_exit_handlers();
+        std::process::exit(code)
+    }
+
+    fn emergency_exit(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let code = exit_code(args, "emergency-exit")?;
+        std::process::exit(code)
+    }
+
+    fn seconds_since_epoch(time: std::time::SystemTime) -> f64 {
+        match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs_f64(),
+            Err(before_epoch) => -before_epoch.duration().as_secs_f64(),
+        }
+    }
+
+    fn time_from_seconds(seconds: f64) -> std::time::SystemTime {
+        let offset = std::time::Duration::from_secs_f64(seconds.abs());
+        if seconds >= 0.0 {
+            std::time::UNIX_EPOCH + offset
+        } else {
+            std::time::UNIX_EPOCH - offset
+        }
+    }
+
+    fn current_time(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'current-time' takes no arguments".into());
+        }
+
+        Ok(Expr::TimePoint(std::time::SystemTime::now()))
+    }
+
+    fn time_difference(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::TimePoint(later), Expr::TimePoint(earlier)] => Ok(Expr::Duration(
+                seconds_since_epoch(*later) - seconds_since_epoch(*earlier),
+            )),
+            _ => Err("'time-difference' requires two time objects".into()),
+        }
+    }
+
+    fn time_to_seconds(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::TimePoint(t)] => Ok(Expr::Number(seconds_since_epoch(*t))),
+            [Expr::Duration(seconds)] => Ok(Expr::Number(*seconds)),
+            _ => Err("'time->seconds' requires a time or duration object".into()),
+        }
+    }
+
+    // Dates are in UTC: (date->time year month day [hour minute second])
+    fn date_to_time(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() < 3 || args.len() > 6 {
+            return Err("'date->time' requires year, month, day and optional hour, minute, second".into());
+        }
+
+        let mut fields = [0.0; 6];
+        for (field, arg) in fields.iter_mut().zip(args) {
+            match arg {
+                Expr::Number(n) => *field = *n,
+                _ => return Err("Invalid argument type for date->time".into()),
+            }
+        }
+
+        let [year, month, day, hour, minute, second] = fields;
+        let date = match chrono::NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32) {
+            Some(date) if month.fract() == 0.0 && day.fract() == 0.0 => date,
+            _ => return Err(format!("Invalid date for date->time: {}-{}-{}", year, month, day).into()),
+        };
+
+        let midnight = date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp() as f64;
+        let seconds = midnight + hour * 3600.0 + minute * 60.0 + second;
+        Ok(Expr::TimePoint(time_from_seconds(seconds)))
+    }
+
+    fn time_to_date(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        use chrono::{Datelike, Timelike};
+
+        let time: chrono::DateTime<chrono::Utc> = match args {
+            [Expr::TimePoint(t)] => (*t).into(),
+            _ => return Err("'time->date' requires a time object".into()),
+        };
+
+        Ok(Expr::List(vec![
+            Expr::Number(time.year() as f64),
+            Expr::Number(time.month() as f64),
+            Expr::Number(time.day() as f64),
+            Expr::Number(time.hour() as f64),
+            Expr::Number(time.minute() as f64),
+            Expr::Number(time.second() as f64 + time.nanosecond() as f64 / 1e9),
+        ]))
+    }
+
+    // A random source's generator. New sources start from the same fixed state, as in SRFI-27
+    #[derive(Debug)]
+    pub struct RandomState(rand::rngs::StdRng);
+
+    impl Default for RandomState {
+        fn default() -> Self {
+            RandomState(rand::rngs::StdRng::seed_from_u64(0))
+        }
+    }
+
+    impl RandomState {
+        fn randomized() -> Self {
+            RandomState(rand::rngs::StdRng::from_rng(&mut rand::rng()))
+        }
+    }
+
+    fn source_argument(arg: Option<&Expr>, env: &Environment, name: &str) -> Result<Arc<Mutex<RandomState>>, LispError> {
+        match arg {
+            None => Ok(env.random_source.clone()),
+            Some(Expr::RandomSource(source)) => Ok(source.clone()),
+            Some(_) => Err(format!("Invalid random source for {}", name).into()),
+        }
+    }
+
+    // (random n [source]): an integer in [0, n), or a real when n is not an integer
+    fn random(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let limit = match args {
+            [Expr::Number(n)] | [Expr::Number(n), _] if *n > 0.0 => *n,
+            _ => return Err("'random' requires a positive number and an optional random source".into()),
+        };
+        let source = source_argument(args.get(1), env, "random")?;
+        let mut state = source.lock().unwrap();
+
+        if limit.fract() != 0.0 {
+            return Ok(Expr::Number(state.0.random::<f64>() * limit));
+        }
+        Ok(Expr::Number(state.0.random_range(0..limit as u64) as f64))
+    }
+
+    fn random_real(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() > 1 {
+            return Err("'random-real' takes an optional random source".into());
+        }
+
+        let source = source_argument(args.first(), env, "random-real")?;
+        let value = source.lock().unwrap().0.random::<f64>();
+        Ok(Expr::Number(value))
+    }
+
+    fn make_random_source(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'make-random-source' takes no arguments".into());
+        }
+
+        Ok(Expr::RandomSource(Arc::new(Mutex::new(RandomState::default()))))
+    }
+
+    fn random_source_randomize(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::RandomSource(source)] => {
+                *source.lock().unwrap() = RandomState::randomized();
+                Ok(Expr::Void)
+            }
+            _ => Err("'random-source-randomize!' requires a random source".into()),
+        }
+    }
+
+    fn random_source_make_integers(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [source @ Expr::RandomSource(_)] => {
+                let call = Expr::List(vec![
+                    Expr::Symbol("random".into()),
+                    Expr::Symbol("n".into()),
+                    Expr::Symbol("source".into()),
+                ]);
+                Ok(make_closure(&["n"], call, vec![("source", source.clone())]))
+            }
+            _ => Err("'random-source-make-integers' requires a random source".into()),
+        }
+    }
+
+    // The `i` flag is written into the pattern as (?i), so two regexps are equal when their
+    // compiled sources are
+    fn compile_regexp(pattern: &str, case_insensitive: bool) -> Result<Arc<regex::Regex>, LispError> {
+        let source = if case_insensitive { format!("(?i){}", pattern) } else { pattern.to_string() };
+        regex::Regex::new(&source)
+            .map(Arc::new)
+            .map_err(|e| format!("Invalid regexp {:?}: {}", pattern, e).into())
+    }
+
+    // Character index of a byte offset, since strings are indexed by character everywhere else
+    fn char_index(s: &str, byte_offset: usize) -> usize {
+        s[..byte_offset].chars().count()
+    }
+
+    fn regexp_argument(arg: &Expr, name: &str) -> Result<Arc<regex::Regex>, LispError> {
+        match arg {
+            Expr::Regexp(regex) => Ok(regex.clone()),
+            Expr::Str(pattern) => compile_regexp(pattern, false),
+            _ => Err(format!("Invalid pattern argument for {}", name).into()),
+        }
+    }
+
+    fn make_regexp(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (pattern, flags) = match args {
+            [Expr::Str(pattern)] => (pattern, ""),
+            [Expr::Str(pattern), Expr::Str(flags)] => (pattern, flags.as_str()),
+            _ => return Err("'make-regexp' requires a pattern string and an optional flags string".into()),
+        };
+
+        if let Some(flag) = flags.chars().find(|&c| c != 'i') {
+            return Err(format!("Unknown regexp flag: {}", flag).into());
+        }
+
+        Ok(Expr::Regexp(compile_regexp(pattern, flags.contains('i'))?))
+    }
+
+    fn is_regexp(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Regexp(_)))),
+            _ => Err("'regexp?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn string_contains_regexp(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, pattern) = match args {
+            [Expr::Str(s), pattern] => (s, pattern),
+            _ => return Err("'string-contains-regexp' requires a string and a pattern".into()),
+        };
+
+        let regex = regexp_argument(pattern, "string-contains-regexp")?;
+        Ok(match regex.find(s) {
+            Some(found) => Expr::Number(char_index(s, found.start()) as f64),
+            None => Expr::Bool(false),
+        })
+    }
+
+    fn regexp_match(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (pattern, s) = match args {
+            [pattern, Expr::Str(s)] => (pattern, s),
+            _ => return Err("'regexp-match' requires a pattern and a string".into()),
+        };
+
+        let regex = regexp_argument(pattern, "regexp-match")?;
+        Ok(match regex.captures(s) {
+            Some(caps) => Expr::List(
+                caps.iter()
+                    .map(|cap| match cap {
+                        Some(cap) => Expr::Str(cap.as_str().to_string()),
+                        None => Expr::Bool(false),
+                    })
+                    .collect(),
+            ),
+            None => Expr::Bool(false),
+        })
+    }
+
+    // `\0`..`\9` in the replacement insert the corresponding capture
+    fn expand_replacement(replacement: &str, caps: &regex::Captures, out: &mut String) {
+        let mut rep = replacement.chars();
+        while let Some(c) = rep.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match rep.next() {
+                Some(d) if d.is_ascii_digit() => {
+                    if let Some(cap) = caps.get(d as usize - '0' as usize) {
+                        out.push_str(cap.as_str());
+                    }
+                }
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        }
+    }
+
+    fn regexp_replace_impl(args: &[Expr], name: &str, all: bool) -> Result<Expr, LispError> {
+        let (pattern, s, replacement) = match args {
+            [pattern, Expr::Str(s), Expr::Str(replacement)] => (pattern, s, replacement),
+            _ => return Err(format!("'{}' requires a pattern, a string and a replacement string", name).into()),
+        };
+
+        let regex = regexp_argument(pattern, name)?;
+        let limit = if all { 0 } else { 1 };
+        let replaced = regex.replacen(s, limit, |caps: &regex::Captures| {
+            let mut out = String::new();
+            expand_replacement(replacement, caps, &mut out);
+            out
+        });
+        Ok(Expr::Str(replaced.into_owned()))
+    }
+
+    fn regexp_replace(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        regexp_replace_impl(args, "regexp-replace", false)
+    }
+
+    fn regexp_replace_all(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        regexp_replace_impl(args, "regexp-replace-all", true)
+    }
+
+    fn for_each_lambda(expr: &Expr, visit: &mut dyn FnMut(&Arc<Lambda>)) {
+        match expr {
+            Expr::Lambda(lambda) => visit(lambda),
+            Expr::List(items) | Expr::Values(items) => {
+                for item in items {
+                    for_each_lambda(item, visit);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Mark-and-sweep over the heap's scopes and the procedures stored in them, and nothing else:
+    // other values are freed by reference counting, so only scope cycles need it. Anything referenced
+    // from outside that graph (the current scope, callers on the Rust stack, host values) is a root;
+    // unreachable scopes are emptied, which breaks the reference cycles that keep them alive
+    fn collect_garbage(env: &mut Environment) -> usize {
+        let scopes: Vec<Arc<Mutex<Scope>>> = env.heap.scopes.iter().filter_map(Weak::upgrade).collect();
+        let index: HashMap<*const Mutex<Scope>, usize> =
+            scopes.iter().enumerate().map(|(i, scope)| (Arc::as_ptr(scope), i)).collect();
+
+        // Count the references each object receives from inside the graph
+        let mut scope_refs = vec![0; scopes.len()];
+        let mut lambdas: HashMap<*const Lambda, (Arc<Lambda>, usize)> = HashMap::new();
+        for scope in &scopes {
+            let scope = scope.lock().unwrap();
+            if let Some(&i) = scope.parent.as_ref().and_then(|parent| index.get(&Arc::as_ptr(parent))) {
+                scope_refs[i] += 1;
+            }
+            for value in scope.symbols.values() {
+                for_each_lambda(value, &mut |lambda| {
+                    lambdas.entry(Arc::as_ptr(lambda)).or_insert_with(|| (lambda.clone(), 0)).1 += 1;
+                });
+            }
+        }
+        for (lambda, _) in lambdas.values() {
+            if let Some(&i) = index.get(&Arc::as_ptr(&lambda.scope)) {
+                scope_refs[i] += 1;
+            }
+        }
+
+        // The clones held in `scopes` and `lambdas` account for one strong count each
+        let mut marked = vec![false; scopes.len()];
+        let mut pending: Vec<usize> = (0..scopes.len())
+            .filter(|&i| Arc::strong_count(&scopes[i]) - 1 > scope_refs[i])
+            .collect();
+        pending.extend(
+            lambdas
+                .values()
+                .filter(|(lambda, refs)| Arc::strong_count(lambda) - 1 > *refs)
+                .filter_map(|(lambda, _)| index.get(&Arc::as_ptr(&lambda.scope)).copied()),
+        );
+
+        while let Some(i) = pending.pop() {
+            if marked[i] {
+                continue;
+            }
+            marked[i] = true;
+
+            let scope = scopes[i].lock().unwrap();
+            if let Some(&parent) = scope.parent.as_ref().and_then(|parent| index.get(&Arc::as_ptr(parent))) {
+                pending.push(parent);
+            }
+            for value in scope.symbols.values() {
+                for_each_lambda(value, &mut |lambda| {
+                    if let Some(&captured) = index.get(&Arc::as_ptr(&lambda.scope)) {
+                        pending.push(captured);
+                    }
+                });
+            }
+        }
+
+        let mut garbage = Vec::new();
+        for (scope, _) in scopes.iter().zip(&marked).filter(|(_, marked)| !**marked) {
+            let mut scope = scope.lock().unwrap();
+            garbage.push((std::mem::take(&mut scope.symbols), scope.parent.take()));
+        }
+        let freed = garbage.len();
+        std::mem::drop(garbage);
+        std::mem::drop(lambdas);
+        std::mem::drop(scopes);
+
+        env.heap.scopes.retain(|scope| scope.strong_count() > 0);
+        let tables: Vec<_> = {
+            let mut weak_tables = env.weak_tables.lock().unwrap();
+            weak_tables.retain(|table| table.strong_count() > 0);
+            weak_tables.iter().filter_map(Weak::upgrade).collect()
+        };
+        for table in tables {
+            // Dropping one entry can release the last reference to another entry's key
+            loop {
+                let mut table = table.lock().unwrap();
+                let before = table.len();
+                table.retain(|key, _| referenced_elsewhere(key));
+                if table.len() == before {
+                    break;
+                }
+            }
+        }
+        env.heap.collections += 1;
+        env.heap.freed_scopes += freed;
+        freed
+    }
+
+    fn gc(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'gc' takes no arguments".into());
+        }
+
+        Ok(Expr::Number(collect_garbage(env) as f64))
+    }
+
+    fn gc_stats(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'gc-stats' takes no arguments".into());
+        }
+
+        let live: Vec<Arc<Mutex<Scope>>> = env.heap.scopes.iter().filter_map(Weak::upgrade).collect();
+        let bindings: usize = live.iter().map(|scope| scope.lock().unwrap().symbols.len()).sum::<usize>()
+            + env.scope.lock().unwrap().symbols.len();
+
+        let stat = |name: &str, value: usize| make_pair(Expr::Symbol(name.into()), Expr::Number(value as f64));
+        Ok(Expr::List(vec![
+            stat("live-scopes", live.len()),
+            stat("bindings", bindings),
+            stat("collections", env.heap.collections),
+            stat("freed-scopes", env.heap.freed_scopes),
+        ]))
+    }
+
+    fn make_thread(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [thunk] if is_procedure(thunk) => Ok(Expr::Thread(Arc::new(Mutex::new(LispThread::New(thunk.clone()))))),
+            _ => Err("'make-thread' requires a procedure of no arguments".into()),
+        }
+    }
+
+    fn thread_start(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let thread = match args {
+            [Expr::Thread(thread)] => thread,
+            _ => return Err("'thread-start!' requires a thread".into()),
+        };
+
+        let mut state = thread.lock().unwrap();
+        let thunk = match &*state {
+            LispThread::New(thunk) => thunk.clone(),
+            _ => return Err("Thread has already been started".into()),
+        };
+
+        // The thread sees the globals of its creator through a forked environment
+        let mut thread_env = env.fork();
+        *state = LispThread::Running(std::thread::spawn(move || apply_procedure(&thunk, &[], &mut thread_env)));
+
+        Ok(args[0].clone())
+    }
+
+    fn thread_join(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let thread = match args {
+            [Expr::Thread(thread)] => thread,
+            _ => return Err("'thread-join!' requires a thread".into()),
+        };
+
+        let mut state = thread.lock().unwrap();
+        let result = match std::mem::replace(&mut *state, LispThread::Finished(Ok(Expr::Void))) {
+            LispThread::New(thunk) => {
+                *state = LispThread::New(thunk);
+                return Err("Thread has not been started".into());
+            }
+            LispThread::Running(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err("Thread panicked".into())),
+            LispThread::Finished(result) => result,
+        };
+
+        *state = LispThread::Finished(result.clone());
+        result
+    }
+
+    fn thread_sleep(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(ms)] if *ms >= 0.0 => {
+                std::thread::sleep(std::time::Duration::from_secs_f64(ms / 1000.0));
+                Ok(Expr::Void)
+            }
+            _ => Err("'thread-sleep!' requires a non-negative number of milliseconds".into()),
+        }
+    }
+
+    fn is_thread(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Thread(_)))),
+            _ => Err("'thread?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn make_mutex(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'make-mutex' takes no arguments".into());
+        }
+
+        Ok(Expr::Mutex(Arc::new(LispMutex::default())))
+    }
+
+    fn mutex_lock(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Mutex(mutex)] => {
+                mutex.lock();
+                Ok(Expr::Bool(true))
+            }
+            _ => Err("'mutex-lock!' requires a mutex".into()),
+        }
+    }
+
+    fn mutex_unlock(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Mutex(mutex)] => {
+                if !mutex.unlock() {
+                    return Err("Mutex is not locked by this thread".into());
+                }
+                Ok(Expr::Bool(true))
+            }
+            _ => Err("'mutex-unlock!' requires a mutex".into()),
+        }
+    }
+
+    fn make_recursive_mutex(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'make-recursive-mutex' takes no arguments".into());
+        }
+
+        Ok(Expr::Mutex(Arc::new(LispMutex::recursive())))
+    }
+
+    fn mutex_locked_p(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Mutex(mutex)] => Ok(Expr::Bool(mutex.is_locked())),
+            _ => Err("'mutex-locked?' requires a mutex".into()),
+        }
+    }
+
+    // The mutex is released however the thunk returns, including by raising. A thunk that unlocks
+    // the mutex itself is an error, unless it has already raised one
+    fn with_mutex(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (mutex, thunk) = match args {
+            [Expr::Mutex(mutex), thunk] if is_procedure(thunk) => (mutex, thunk),
+            _ => return Err("'with-mutex' requires a mutex and a thunk".into()),
+        };
+
+        mutex.lock();
+        let result = apply_procedure(thunk, &[], env);
+        if !mutex.unlock() && result.is_ok() {
+            return Err("The thunk of 'with-mutex' unlocked the mutex".into());
+        }
+        result
+    }
+
+    fn is_mutex(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Mutex(_)))),
+            _ => Err("'mutex?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn make_coroutine(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [thunk] if is_procedure(thunk) => Ok(Expr::Coroutine(Arc::new(Mutex::new(Coroutine::New(thunk.clone()))))),
+            _ => Err("'make-coroutine' requires a procedure of no arguments".into()),
+        }
+    }
+
+    // Runs the coroutine until it yields (returning the yielded value) or finishes (returning #f)
+    fn resume(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (coroutine, sent) = match args {
+            [Expr::Coroutine(coroutine)] => (coroutine, Expr::Void),
+            [Expr::Coroutine(coroutine), sent] => (coroutine, sent.clone()),
+            _ => return Err("'resume' requires a coroutine and an optional value".into()),
+        };
+
+        let state = std::mem::replace(&mut *coroutine.lock().unwrap(), Coroutine::Running);
+        let suspended = match state {
+            Coroutine::New(thunk) => {
+                let (event_sender, events) = std::sync::mpsc::channel();
+                let (resume, resumes) = std::sync::mpsc::channel();
+
+                let mut body_env = env.fork();
+                body_env.coroutine = Some(CoroutineLink {
+                    events: event_sender.clone(),
+                    resumes,
+                });
+                let thread = std::thread::spawn(move || {
+                    let result = apply_procedure(&thunk, &[], &mut body_env);
+                    let _ = event_sender.send(CoroutineEvent::Finished(result));
+                });
+                SuspendedCoroutine { resume, events, thread: Some(thread) }
+            }
+            Coroutine::Suspended(suspended) => {
+                let _ = suspended.resume.send(sent);
+                suspended
+            }
+            Coroutine::Running => return Err("Coroutine is already running".into()),
+            Coroutine::Finished => {
+                *coroutine.lock().unwrap() = Coroutine::Finished;
+                return Ok(Expr::Bool(false));
+            }
+        };
+
+        match suspended.events.recv() {
+            Ok(CoroutineEvent::Yielded(value)) => {
+                *coroutine.lock().unwrap() = Coroutine::Suspended(suspended);
+                Ok(value)
+            }
+            Ok(CoroutineEvent::Finished(result)) => {
+                *coroutine.lock().unwrap() = Coroutine::Finished;
+                result.map(|_| Expr::Bool(false))
+            }
+            Err(_) => {
+                *coroutine.lock().unwrap() = Coroutine::Finished;
+                Err("Coroutine terminated unexpectedly".into())
+            }
+        }
+    }
+
+    fn yield_value(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let value = match args {
+            [] => Expr::Void,
+            [value] => value.clone(),
+            _ => return Err("'yield' takes at most 1 argument".into()),
+        };
+
+        let link = env.coroutine.as_ref().ok_or("'yield' used outside of a coroutine")?;
+        link.events
+            .send(CoroutineEvent::Yielded(value))
+            .map_err(|_| LispError::CoroutineAbandoned)?;
+
+        // The sender goes away when the coroutine is dropped, which unwinds the body
+        link.resumes.recv().map_err(|_| LispError::CoroutineAbandoned)
+    }
+
+    fn is_coroutine(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Coroutine(_)))),
+            _ => Err("'coroutine?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn symbol(name: &str) -> Expr {
+        Expr::Symbol(name.into())
+    }
+
+    fn prepend(head: Expr, rest: &[Expr]) -> Expr {
+        let mut items = vec![head];
+        items.extend_from_slice(rest);
+        Expr::List(items)
+    }
+
+    fn binding_pairs<'a>(bindings: &'a Expr, form: &str) -> Result<Vec<(SymbolId, &'a Expr)>, LispError> {
+        match bindings {
+            Expr::List(bindings) => bindings
+                .iter()
+                .map(|binding| {
+                    if let Expr::List(pair) = binding {
+                        if let [Expr::Symbol(name), value] = pair.as_slice() {
+                            return Ok((*name, value));
+                        }
+                    }
+                    Err(format!("Invalid binding in '{}': {}", form, binding).into())
+                })
+                .collect(),
+            _ => Err(format!("'{}' requires a list of bindings", form).into()),
+        }
+    }
+
+    fn expand_cond(clauses: &[Expr]) -> Result<Expr, LispError> {
+        let Some((clause, rest)) = clauses.split_first() else {
+            return Ok(Expr::List(vec![symbol("begin")]));
+        };
+
+        let parts = match clause {
+            Expr::List(parts) if !parts.is_empty() => parts,
+            _ => return Err(format!("Invalid clause in 'cond': {}", clause).into()),
+        };
+
+        if parts[0] == symbol("else") {
+            return Ok(prepend(symbol("begin"), &parts[1..]));
+        }
+
+        let otherwise = prepend(symbol("cond"), rest);
+        Ok(match &parts[1..] {
+            [] => Expr::List(vec![symbol("or"), parts[0].clone(), otherwise]),
+            [arrow, receiver] if *arrow == symbol("=>") => {
+                // The name cannot be produced by the reader, so user code never captures it
+                let value = symbol("cond value");
+                Expr::List(vec![
+                    symbol("let"),
+                    Expr::List(vec![Expr::List(vec![value.clone(), parts[0].clone()])]),
+                    Expr::List(vec![
+                        symbol("if"),
+                        value.clone(),
+                        Expr::List(vec![receiver.clone(), value]),
+                        otherwise,
+                    ]),
+                ])
+            }
+            body => Expr::List(vec![symbol("if"), parts[0].clone(), prepend(symbol("begin"), body), otherwise]),
+        })
+    }
+
+    // Rewrites one use of a derived form into simpler forms, or returns None if `form` is not one
+    fn expand_once(form: &Expr, env: &mut Environment) -> Result<Option<Expr>, LispError> {
+        let list = match form {
+            Expr::List(list) => list,
+            _ => return Ok(None),
+        };
+        let head = match list.first() {
+            Some(Expr::Symbol(head)) => *head,
+            _ => return Ok(None),
+        };
+        // User macros take precedence over the built-in derived forms
+        if let Some(Expr::Macro(transformer)) = env.lookup(head) {
+            return match &*transformer {
+                Macro::Rules { literals, rules } => expand_macro(literals, rules, list).map(Some),
+                Macro::Identifier { template, .. } => Ok(Some(prepend(template.clone(), &list[1..]))),
+                Macro::Variable(procedure) => apply_procedure(procedure, std::slice::from_ref(form), env).map(Some),
+            };
+        }
+        let head = head.as_str();
+        let args = &list[1..];
+
+        let expansion = match head {
+            "define" => match args.first() {
+                Some(Expr::List(signature)) if !signature.is_empty() => {
+                    let lambda = Expr::List(
+                        [symbol("lambda"), Expr::List(signature[1..].to_vec())]
+                            .into_iter()
+                            .chain(args[1..].iter().cloned())
+                            .collect(),
+                    );
+                    Expr::List(vec![symbol("define"), signature[0].clone(), lambda])
+                }
+                _ => return Ok(None),
+            },
+            "and" => match args {
+                [] => Expr::Bool(true),
+                [only] => only.clone(),
+                [first, rest @ ..] => Expr::List(vec![
+                    symbol("if"),
+                    first.clone(),
+                    prepend(symbol("and"), rest),
+                    Expr::Bool(false),
+                ]),
+            },
+            "or" => match args {
+                [] => Expr::Bool(false),
+                [only] => only.clone(),
+                [first, rest @ ..] => {
+                    let value = symbol("or value");
+                    Expr::List(vec![
+                        symbol("let"),
+                        Expr::List(vec![Expr::List(vec![value.clone(), first.clone()])]),
+                        Expr::List(vec![symbol("if"), value.clone(), value, prepend(symbol("or"), rest)]),
+                    ])
+                }
+            },
+            "when" | "unless" => {
+                let (test, body) = args
+                    .split_first()
+                    .ok_or_else(|| LispError::from(format!("'{}' requires a test", head)))?;
+                let body = prepend(symbol("begin"), body);
+                let nothing = Expr::List(vec![symbol("begin")]);
+                if head == "when" {
+                    Expr::List(vec![symbol("if"), test.clone(), body, nothing])
+                } else {
+                    Expr::List(vec![symbol("if"), test.clone(), nothing, body])
+                }
+            }
+            // Each binding is tested in turn, and later ones can see the earlier variables
+            "when-let" | "if-let" => {
+                let usage = if head == "when-let" { "bindings and a body" } else { "bindings, a consequent and an optional alternative" };
+                let (bindings, rest) = match args.split_first() {
+                    Some((bindings, rest)) if head == "when-let" || (1..=2).contains(&rest.len()) => (bindings, rest),
+                    _ => return Err(format!("'{}' requires {}", head, usage).into()),
+                };
+                let pairs = binding_pairs(bindings, head)?;
+                let (success, failure) = if head == "when-let" {
+                    (prepend(symbol("let"), &[&[Expr::List(vec![])], rest].concat()), Expr::List(vec![symbol("begin")]))
+                } else {
+                    (rest[0].clone(), rest.get(1).cloned().unwrap_or_else(|| Expr::List(vec![symbol("begin")])))
+                };
+                pairs.iter().rev().fold(success, |inner, (name, value)| {
+                    Expr::List(vec![
+                        symbol("let"),
+                        Expr::List(vec![Expr::List(vec![Expr::Symbol(*name), (*value).clone()])]),
+                        Expr::List(vec![symbol("if"), Expr::Symbol(*name), inner, failure.clone()]),
+                    ])
+                })
+            }
+            "let" => match args {
+                // Named let: the body can call `name` to loop
+                [name @ Expr::Symbol(_), bindings, body @ ..] if !body.is_empty() => {
+                    let pairs = binding_pairs(bindings, "let")?;
+                    let params = Expr::List(pairs.iter().map(|(name, _)| Expr::Symbol(*name)).collect());
+                    let procedure = Expr::List(
+                        [symbol("lambda"), params].into_iter().chain(body.iter().cloned()).collect(),
+                    );
+                    let call = prepend(name.clone(), &pairs.iter().map(|(_, value)| (*value).clone()).collect::<Vec<_>>());
+                    Expr::List(vec![
+                        Expr::List(vec![
+                            symbol("lambda"),
+                            Expr::List(Vec::new()),
+                            Expr::List(vec![symbol("define"), name.clone(), procedure]),
+                            call,
+                        ]),
+                    ])
+                }
+                [bindings, body @ ..] if !body.is_empty() => {
+                    let pairs = binding_pairs(bindings, "let")?;
+                    let params = Expr::List(pairs.iter().map(|(name, _)| Expr::Symbol(*name)).collect());
+                    let procedure = Expr::List(
+                        [symbol("lambda"), params].into_iter().chain(body.iter().cloned()).collect(),
+                    );
+                    prepend(procedure, &pairs.iter().map(|(_, value)| (*value).clone()).collect::<Vec<_>>())
+                }
+                _ => return Err("'let' requires bindings and a body".into()),
+            },
+            "let*" => match args {
+                [bindings, body @ ..] if !body.is_empty() => {
+                    let pairs = binding_pairs(bindings, "let*")?;
+                    match pairs.split_first() {
+                        None => prepend(symbol("let"), args),
+                        Some(((name, value), rest)) => {
+                            let inner_bindings = Expr::List(
+                                rest.iter()
+                                    .map(|(name, value)| Expr::List(vec![Expr::Symbol(*name), (*value).clone()]))
+                                    .collect(),
+                            );
+                            Expr::List(vec![
+                                symbol("let"),
+                                Expr::List(vec![Expr::List(vec![Expr::Symbol(*name), (*value).clone()])]),
+                                prepend(symbol("let*"), &[&[inner_bindings], body].concat()),
+                            ])
+                        }
+                    }
+                }
+                _ => return Err("'let*' requires bindings and a body".into()),
+            },
+            "cond" => expand_cond(args)?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(expansion))
+    }
+
+    fn macroexpand_1(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [form] => Ok(expand_once(form, env)?.unwrap_or_else(|| form.clone())),
+            _ => Err("'macroexpand-1' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn macroexpand(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [form] => expand_all(form, env),
+            _ => Err("'macroexpand' requires exactly 1 argument".into()),
+        }
+    }
+
+    // Expands `form` and then every subform, leaving quoted data alone
+    fn expand_all(form: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        let mut form = form.clone();
+        while let Some(expansion) = expand_once(&form, env)? {
+            form = expansion;
+        }
+
+        match form {
+            Expr::List(items) if items.first() != Some(&symbol("quote")) => Ok(Expr::List(
+                items.iter().map(|item| expand_all(item, env)).collect::<Result<_, _>>()?,
+            )),
+            other => Ok(other),
+        }
+    }
+
+    fn eval_assert(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (condition, message) = match args {
+            [condition] => (condition, None),
+            [condition, message] => (condition, Some(message)),
+            _ => return Err("'assert' requires an expression and an optional message".into()),
+        };
+
+        if is_truthy(&eval(condition, env)?) {
+            return Ok(Expr::Bool(true));
+        }
+
+        // Without a message the failing expression itself is reported
+        let message = match message {
+            Some(message) => display_string(&eval(message, env)?),
+            None => condition.to_string(),
+        };
+        Err(LispError::AssertionFailed(message))
+    }
+
+    fn assert_equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [expected, actual] if expected == actual => Ok(Expr::Bool(true)),
+            [expected, actual] => Err(LispError::AssertionFailed(format!("expected {} but got {}", expected, actual))),
+            _ => Err("'assert-equal?' requires exactly 2 arguments".into()),
+        }
+    }
+
+    fn error_kind(error: &LispError) -> &'static str {
+        match error {
+            LispError::Read(_) => "read",
+            LispError::Eval(_) | LispError::CoroutineAbandoned | LispError::Interrupted => "eval",
+            LispError::FileError(_) => "file",
+            LispError::AssertionFailed(_) => "assertion",
+            LispError::Raised(_) => "raise",
+            LispError::SandboxTimeout(_) | LispError::SandboxMemoryExceeded(_) => "sandbox",
+        }
+    }
+
+    // `kind` is one of read, eval, file, assertion, raise or sandbox; error and any accept every error
+    fn assert_raises(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (kind, thunk) = match args {
+            [Expr::Symbol(kind), thunk] if is_procedure(thunk) => (kind.as_str(), thunk),
+            _ => return Err("'assert-raises?' requires an error type symbol and a thunk".into()),
+        };
+
+        let any = kind == "error" || kind == "any";
+        match apply_procedure(thunk, &[], env) {
+            Err(error) if any || error_kind(&error) == kind => Ok(Expr::Bool(true)),
+            Err(error) => Err(LispError::AssertionFailed(format!(
+                "expected an error of type {} but got one of type {}: {}",
+                kind,
+                error_kind(&error),
+                error
+            ))),
+            Ok(value) if any => Err(LispError::AssertionFailed(format!("expected an error but got {}", value))),
+            Ok(value) => Err(LispError::AssertionFailed(format!(
+                "expected an error of type {} but got {}",
+                kind, value
+            ))),
+        }
+    }
+
+    fn is_equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [a, b] => Ok(Expr::Bool(a == b)),
+            _ => Err("'equal?' requires exactly 2 arguments".into()),
+        }
+    }
+
+    #[derive(Default)]
+    struct TestResults {
+        name: String,
+        passed: usize,
+        failed: usize,
+    }
+
+    thread_local! {
+        // Suites opened with test-begin, innermost last
+        static TEST_SUITES: std::cell::RefCell<Vec<TestResults>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    fn record_test(name: &str, outcome: Result<(), String>, env: &Environment) -> Result<Expr, LispError> {
+        TEST_SUITES.with(|suites| {
+            let mut suites = suites.borrow_mut();
+            if suites.is_empty() {
+                suites.push(TestResults::default());
+            }
+            let suite = suites.last_mut().unwrap();
+
+            match outcome {
+                Ok(()) => {
+                    suite.passed += 1;
+                    Ok(Expr::Void)
+                }
+                Err(reason) => {
+                    suite.failed += 1;
+                    write_to_port(&format!("FAIL {}: {}\n", name, reason), None, "report a test", env)
+                }
+            }
+        })
+    }
+
+    // The optional leading test name of test-equal, test-assert and test-error; unnamed tests are
+    // reported by their tested expression
+    fn test_name(args: &[Expr], arity: usize, env: &mut Environment) -> Result<String, LispError> {
+        if args.len() > arity {
+            Ok(display_string(&eval(&args[0], env)?))
+        } else {
+            Ok(args[args.len() - 1].to_string())
+        }
+    }
+
+    fn eval_test_equal(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 2 && args.len() != 3 {
+            return Err("'test-equal' requires an optional name, an expected value and an expression".into());
+        }
+
+        let name = test_name(args, 2, env)?;
+        let n = args.len();
+        let outcome = match (eval(&args[n - 2], env), eval(&args[n - 1], env)) {
+            (Ok(expected), Ok(actual)) if expected == actual => Ok(()),
+            (Ok(expected), Ok(actual)) => Err(format!("expected {} but got {}", expected, actual)),
+            (Err(error), _) | (_, Err(error)) => Err(format!("raised {}", error)),
+        };
+        record_test(&name, outcome, env)
+    }
+
+    fn eval_test_assert(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 && args.len() != 2 {
+            return Err("'test-assert' requires an optional name and an expression".into());
+        }
+
+        let name = test_name(args, 1, env)?;
+        let outcome = match eval(&args[args.len() - 1], env) {
+            Ok(value) if is_truthy(&value) => Ok(()),
+            Ok(value) => Err(format!("{} returned {}", args[args.len() - 1], value)),
+            Err(error) => Err(format!("raised {}", error)),
+        };
+        record_test(&name, outcome, env)
+    }
+
+    // (test-error [[name] type] expr) passes if `expr` raises, of the given kind if there is one.
+    // As in SRFI-64, a name is only taken along with a type
+    fn eval_test_error(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.is_empty() || args.len() > 3 {
+            return Err("'test-error' requires an optional name, an optional error type and an expression".into());
+        }
+
+        let name = test_name(args, 2, env)?;
+        let kind = match args.len() {
+            1 => None,
+            n => Some(eval(&args[n - 2], env)?),
+        };
+        let outcome = match (eval(&args[args.len() - 1], env), kind) {
+            (Ok(value), _) => Err(format!("expected an error but got {}", value)),
+            (Err(error), Some(Expr::Symbol(kind))) if kind != "error" && kind != error_kind(&error) => {
+                Err(format!("expected an error of type {} but got {}", kind, error))
+            }
+            (Err(_), _) => Ok(()),
+        };
+        record_test(&name, outcome, env)
+    }
+
+    fn test_begin(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let name = match args {
+            [name] => display_string(name),
+            _ => return Err("'test-begin' requires a suite name".into()),
+        };
+
+        TEST_SUITES.with(|suites| suites.borrow_mut().push(TestResults { name, ..TestResults::default() }));
+        Ok(Expr::Void)
+    }
+
+    fn test_end(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let expected = match args {
+            [] => None,
+            [name] => Some(display_string(name)),
+            _ => return Err("'test-end' takes an optional suite name".into()),
+        };
+
+        let suite = TEST_SUITES.with(|suites| -> Result<TestResults, LispError> {
+            let mut suites = suites.borrow_mut();
+            let suite = suites.pop().ok_or("'test-end' without a matching 'test-begin'")?;
+            if expected.as_ref().is_some_and(|name| *name != suite.name) {
+                let message = format!("'test-end' of {} does not match 'test-begin' of {}", expected.unwrap(), suite.name);
+                suites.push(suite);
+                return Err(message.into());
+            }
+
+            // Nested suites count towards the suite that contains them
+            if let Some(outer) = suites.last_mut() {
+                outer.passed += suite.passed;
+                outer.failed += suite.failed;
+            }
+            Ok(suite)
+        })?;
+
+        let summary = format!("%%%% Test suite {}: {} passed, {} failed\n", suite.name, suite.passed, suite.failed);
+        write_to_port(&summary, None, "test-end", env)?;
+        Ok(Expr::List(vec![
+            make_pair(symbol("pass"), Expr::Number(suite.passed as f64)),
+            make_pair(symbol("fail"), Expr::Number(suite.failed as f64)),
+        ]))
+    }
+
+    fn test_runner_current(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'test-runner-current' takes no arguments".into());
+        }
+
+        Ok(TEST_SUITES.with(|suites| match suites.borrow().last() {
+            Some(suite) => Expr::List(vec![
+                make_pair(symbol("suite"), Expr::Str(suite.name.clone())),
+                make_pair(symbol("pass"), Expr::Number(suite.passed as f64)),
+                make_pair(symbol("fail"), Expr::Number(suite.failed as f64)),
+            ]),
+            None => Expr::Bool(false),
+        }))
+    }
+
+    // Calls are counted by the name they were made through; times include the calls they make
+    fn profile(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let thunk = match args {
+            [thunk] if is_procedure(thunk) => thunk,
+            _ => return Err("'profile' requires a procedure of no arguments".into()),
+        };
+
+        let outer = std::mem::replace(&mut env.profiling, true);
+        let start = std::time::Instant::now();
+        let result = apply_procedure(thunk, &[], env);
+        let total = start.elapsed();
+        env.profiling = outer;
+        result?;
+
+        let mut stats: Vec<(&SymbolId, &ProfileEntry)> = env.profile_stats.iter().collect();
+        stats.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then_with(|| a.0.as_str().cmp(b.0.as_str())));
+
+        let calls = stats
+            .iter()
+            .map(|(name, entry)| make_pair(Expr::Symbol(**name), Expr::Number(entry.calls as f64)))
+            .collect();
+        let times = stats
+            .iter()
+            .map(|(name, entry)| make_pair(Expr::Symbol(**name), Expr::Number(entry.time.as_secs_f64() * 1000.0)))
+            .collect();
+
+        Ok(Expr::List(vec![
+            make_pair(symbol("total-ms"), Expr::Number(total.as_secs_f64() * 1000.0)),
+            make_pair(symbol("calls"), Expr::List(calls)),
+            make_pair(symbol("time-ms"), Expr::List(times)),
+        ]))
+    }
+
+    // Every with-timing measurement since startup or the last reset-timings!, by label
+    static TIMINGS: LazyLock<Mutex<HashMap<String, Vec<std::time::Duration>>>> = LazyLock::new(Default::default);
+
+    fn milliseconds(duration: std::time::Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    // Runs `thunk`, reporting how long it took on current-error-port; a thunk that raises is timed too
+    fn with_timing(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (label, thunk) = match args {
+            [Expr::Str(label), thunk] if is_procedure(thunk) => (label, thunk),
+            _ => return Err("'with-timing' requires a label string and a procedure of no arguments".into()),
+        };
+
+        let start = std::time::Instant::now();
+        let result = apply_procedure(thunk, &[], env);
+        let elapsed = start.elapsed();
+
+        TIMINGS.lock().unwrap().entry(label.clone()).or_default().push(elapsed);
+        let error_port = env.ports.error.clone();
+        write_to_port(&format!("{}: {:.2}ms\n", label, milliseconds(elapsed)), Some(&error_port), "with-timing", env)?;
+        result
+    }
+
+    fn timing_report(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'timing-report' takes no arguments".into());
+        }
+
+        let mut rows: Vec<(String, usize, f64, f64, f64, f64)> = TIMINGS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, times)| {
+                let total: std::time::Duration = times.iter().sum();
+                let min = times.iter().min().copied().unwrap_or_default();
+                let max = times.iter().max().copied().unwrap_or_default();
+                let mean = milliseconds(total) / times.len() as f64;
+                (label.clone(), times.len(), milliseconds(total), mean, milliseconds(min), milliseconds(max))
+            })
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0).max("label".len());
+        let mut report = format!(
+            "{:<width$}  {:>6}  {:>10}  {:>10}  {:>10}  {:>10}\n",
+            "label", "calls", "total ms", "mean ms", "min ms", "max ms"
+        );
+        for (label, calls, total, mean, min, max) in rows {
+            report.push_str(&format!(
+                "{:<width$}  {:>6}  {:>10.2}  {:>10.2}  {:>10.2}  {:>10.2}\n",
+                label, calls, total, mean, min, max
+            ));
+        }
+
+        write_to_port(&report, None, "timing-report", env)
+    }
+
+    fn reset_timings(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'reset-timings!' takes no arguments".into());
+        }
+
+        TIMINGS.lock().unwrap().clear();
+        Ok(Expr::Void)
+    }
+
+    fn profile_reset(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'profile-reset!' takes no arguments".into());
+        }
+
+        env.profile_stats.clear();
+        Ok(Expr::Void)
+    }
+
+    fn memoize(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [procedure] if is_procedure(procedure) => Ok(Expr::Memoized(Arc::new(Memoized {
+                procedure: procedure.clone(),
+                cache: Mutex::new(HashMap::new()),
+            }))),
+            _ => Err("'memoize' requires a procedure".into()),
+        }
+    }
+
+    fn is_memoized(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Memoized(_)))),
+            _ => Err("'memoized?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn memoize_clear(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Memoized(memoized)] => {
+                memoized.cache.lock().unwrap().clear();
+                Ok(Expr::Void)
+            }
+            _ => Err("'memoize-clear!' requires a memoized procedure".into()),
+        }
+    }
+
+    fn void(_args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::Void)
+    }
+
+    fn is_void(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Void))),
+            _ => Err("'void?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn make_parameter(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (value, converter) = match args {
+            [value] => (value.clone(), None),
+            [value, converter] if is_procedure(converter) => {
+                (apply_procedure(converter, std::slice::from_ref(value), env)?, Some(converter.clone()))
+            }
+            _ => return Err("'make-parameter' requires a value and an optional converter procedure".into()),
+        };
+
+        Ok(Expr::Parameter(Arc::new(Parameter {
+            value: Mutex::new(value),
+            converter,
+        })))
+    }
+
+    fn eval_parameterize(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (bindings, body) = match args.split_first() {
+            Some((bindings, body)) => (binding_pairs(bindings, "parameterize")?, body),
+            None => return Err("'parameterize' requires a list of bindings".into()),
+        };
+
+        // Every parameter and value is evaluated and converted before any binding takes effect
+        let mut new_values = Vec::new();
+        for (parameter, value) in bindings {
+            let parameter = match eval(&Expr::Symbol(parameter), env)? {
+                Expr::Parameter(parameter) => parameter,
+                other => return Err(format!("Not a parameter: {}", other).into()),
+            };
+            let mut value = eval(value, env)?;
+            if let Some(converter) = &parameter.converter {
+                value = apply_procedure(converter, &[value], env)?;
+            }
+            new_values.push((parameter, value));
+        }
+
+        let saved: Vec<(Arc<Parameter>, Expr)> = new_values
+            .into_iter()
+            .map(|(parameter, value)| {
+                let old = std::mem::replace(&mut *parameter.value.lock().unwrap(), value);
+                (parameter, old)
+            })
+            .collect();
+
+        // The old values come back whether the body returns or raises
+        let result = eval_body(body, env);
+        for (parameter, old) in saved.into_iter().rev() {
+            *parameter.value.lock().unwrap() = old;
+        }
+        result
+    }
+
+    // Assigns the existing bindings for the extent of the body rather than shadowing them, so
+    // procedures that read the variables see the new values too
+    fn eval_fluid_let(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (bindings, body) = match args.split_first() {
+            Some((bindings, body)) => (binding_pairs(bindings, "fluid-let")?, body),
+            None => return Err("'fluid-let' requires a list of bindings".into()),
+        };
+
+        let mut new_values = Vec::new();
+        for (name, value) in bindings {
+            if env.lookup(name).is_none() {
+                return Err(format!("'fluid-let' requires a bound variable: {}", name).into());
+            }
+            new_values.push((name, eval(value, env)?));
+        }
+
+        let mut saved = Vec::new();
+        for (name, value) in new_values {
+            saved.push((name, env.lookup(name).unwrap()));
+            env.assign(name, value)?;
+        }
+
+        // The old values come back whether the body returns or raises
+        let result = eval_body(body, env);
+        for (name, old) in saved.into_iter().rev() {
+            env.assign(name, old)?;
+        }
+        result
+    }
+
+    // (the pred expr) is the value of expr, once (pred value) has been checked to be true
+    fn eval_the(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (predicate_expr, expr) = match args {
+            [predicate, expr] => (predicate, expr),
+            _ => return Err("'the' requires a predicate and an expression".into()),
+        };
+
+        let predicate = eval(predicate_expr, env)?;
+        if !is_procedure(&predicate) {
+            return Err(format!("'the' requires a predicate, not {}", predicate).into());
+        }
+
+        let value = eval(expr, env)?;
+        if !is_truthy(&apply_procedure(&predicate, std::slice::from_ref(&value), env)?) {
+            let described = match expr {
+                Expr::Symbol(_) | Expr::List(_) => format!("{} is {}, which", expr, value),
+                _ => value.to_string(),
+            };
+            return Err(format!("Type check failed: {} does not satisfy {}", described, predicate_expr).into());
+        }
+        Ok(value)
+    }
+
+    fn make_vector_value(items: Vec<Expr>) -> Expr {
+        Expr::Vector(Arc::new(Mutex::new(items)))
+    }
+
+    fn index_value(expr: &Expr, name: &str) -> Result<usize, LispError> {
+        match expr {
+            Expr::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+            _ => Err(format!("Invalid index for {}: {}", name, expr).into()),
+        }
+    }
+
+    fn vector_argument<'a>(expr: &'a Expr, name: &str) -> Result<&'a Arc<Mutex<Vec<Expr>>>, LispError> {
+        match expr {
+            Expr::Vector(items) => Ok(items),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn vector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(make_vector_value(args.to_vec()))
+    }
+
+    fn make_vector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (len, fill) = match args {
+            [len] => (index_value(len, "make-vector")?, Expr::Void),
+            [len, fill] => (index_value(len, "make-vector")?, fill.clone()),
+            _ => return Err("'make-vector' requires a length and an optional fill value".into()),
+        };
+
+        Ok(make_vector_value(vec![fill; len]))
+    }
+
+    fn is_vector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Vector(_)))),
+            _ => Err("'vector?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn vector_length(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [vector] => Ok(Expr::Number(vector_argument(vector, "vector-length")?.lock().unwrap().len() as f64)),
+            _ => Err("'vector-length' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn vector_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (vector, index) = match args {
+            [vector, index] => (vector_argument(vector, "vector-ref")?, index_value(index, "vector-ref")?),
+            _ => return Err("'vector-ref' requires a vector and an index".into()),
+        };
+
+        let items = vector.lock().unwrap();
+        match items.get(index) {
+            Some(item) => Ok(item.clone()),
+            None => Err(format!("Index out of range for vector-ref: {} >= {}", index, items.len()).into()),
+        }
+    }
+
+    fn vector_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (vector, index, value) = match args {
+            [vector, index, value] => (vector_argument(vector, "vector-set!")?, index_value(index, "vector-set!")?, value),
+            _ => return Err("'vector-set!' requires a vector, an index and a value".into()),
+        };
+
+        let mut items = vector.lock().unwrap();
+        let len = items.len();
+        match items.get_mut(index) {
+            Some(item) => *item = value.clone(),
+            None => return Err(format!("Index out of range for vector-set!: {} >= {}", index, len).into()),
+        }
+        Ok(Expr::Void)
+    }
+
+    // The elements are copied out first, so the procedure is free to modify the vectors it walks
+    fn vector_arguments(args: &[Expr], name: &str) -> Result<(Vec<Vec<Expr>>, usize), LispError> {
+        if args.is_empty() {
+            return Err(format!("'{}' requires at least one vector", name).into());
+        }
+
+        let mut vectors = Vec::new();
+        for arg in args {
+            vectors.push(vector_argument(arg, name)?.lock().unwrap().clone());
+        }
+
+        let len = vectors.iter().map(|v| v.len()).min().unwrap();
+        Ok((vectors, len))
+    }
+
+    fn string_arguments(args: &[Expr], name: &str) -> Result<(Vec<Vec<char>>, usize), LispError> {
+        if args.is_empty() {
+            return Err(format!("'{}' requires at least one string", name).into());
+        }
+
+        let mut strings = Vec::new();
+        for arg in args {
+            match arg {
+                Expr::Str(s) => strings.push(s.chars().collect::<Vec<char>>()),
+                _ => return Err(format!("Invalid argument type for {}", name).into()),
+            }
+        }
+
+        let len = strings.iter().map(|s| s.len()).min().unwrap();
+        Ok((strings, len))
+    }
+
+    fn vector_map(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (proc, vectors) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'vector-map' requires a procedure and at least one vector".into()),
+        };
+        let (vectors, len) = vector_arguments(vectors, "vector-map")?;
+
+        let mut results = Vec::with_capacity(len);
+        for i in 0..len {
+            let call_args: Vec<Expr> = vectors.iter().map(|v| v[i].clone()).collect();
+            results.push(apply_procedure(proc, &call_args, env)?);
+        }
+
+        Ok(make_vector_value(results))
+    }
+
+    fn vector_for_each_impl(args: &[Expr], env: &mut Environment, name: &str, indexed: bool) -> Result<Expr, LispError> {
+        let (proc, vectors) = match args.split_first() {
+            Some(split) => split,
+            None => return Err(format!("'{}' requires a procedure and at least one vector", name).into()),
+        };
+        let (vectors, len) = vector_arguments(vectors, name)?;
+
+        for i in 0..len {
+            let mut call_args = Vec::with_capacity(vectors.len() + 1);
+            if indexed {
+                call_args.push(Expr::Number(i as f64));
+            }
+            call_args.extend(vectors.iter().map(|v| v[i].clone()));
+            apply_procedure(proc, &call_args, env)?;
+        }
+
+        Ok(Expr::Void)
+    }
+
+    fn vector_for_each(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        vector_for_each_impl(args, env, "vector-for-each", false)
+    }
+
+    // Like vector-for-each, with the index passed before the elements
+    fn vector_for_each_indexed(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        vector_for_each_impl(args, env, "vector-for-each-indexed", true)
+    }
+
+    fn string_map(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (proc, strings) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'string-map' requires a procedure and at least one string".into()),
+        };
+        let (strings, len) = string_arguments(strings, "string-map")?;
+
+        let mut result = String::with_capacity(len);
+        for i in 0..len {
+            let call_args: Vec<Expr> = strings.iter().map(|s| Expr::Char(s[i])).collect();
+            match apply_procedure(proc, &call_args, env)? {
+                Expr::Char(c) => result.push(c),
+                other => return Err(format!("'string-map' procedure must return a character, got {}", other).into()),
+            }
+        }
+
+        Ok(Expr::Str(result))
+    }
+
+    fn string_for_each(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (proc, strings) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'string-for-each' requires a procedure and at least one string".into()),
+        };
+        let (strings, len) = string_arguments(strings, "string-for-each")?;
+
+        for i in 0..len {
+            let call_args: Vec<Expr> = strings.iter().map(|s| Expr::Char(s[i])).collect();
+            apply_procedure(proc, &call_args, env)?;
+        }
+
+        Ok(Expr::Void)
+    }
+
+    // The optional start and end arguments of R7RS copy and conversion procedures, defaulting to the
+    // whole sequence
+    fn range_arguments(args: &[Expr], len: usize, name: &str) -> Result<(usize, usize), LispError> {
+        let (start, end) = match args {
+            [] => (0, len),
+            [start] => (index_value(start, name)?, len),
+            [start, end] => (index_value(start, name)?, index_value(end, name)?),
+            _ => return Err(format!("Too many arguments for {}", name).into()),
+        };
+
+        if start > end || end > len {
+            return Err(format!("Invalid range for {}: {} to {} of {}", name, start, end, len).into());
+        }
+        Ok((start, end))
+    }
+
+    fn string_copy(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, range) = match args.split_first() {
+            Some((Expr::Str(s), range)) => (s, range),
+            _ => return Err("'string-copy' requires a string and an optional range".into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let (start, end) = range_arguments(range, chars.len(), "string-copy")?;
+        Ok(Expr::Str(chars[start..end].iter().collect()))
+    }
+
+    fn vector_copy_into(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (to, at, from, range) = match args {
+            [to, at, from, range @ ..] => (
+                vector_argument(to, "vector-copy!")?,
+                index_value(at, "vector-copy!")?,
+                vector_argument(from, "vector-copy!")?,
+                range,
+            ),
+            _ => return Err("'vector-copy!' requires a vector, an index and a source vector".into()),
+        };
+
+        // The source range is copied out before writing, so overlapping ranges within one vector
+        // behave like memmove
+        let source = {
+            let items = from.lock().unwrap();
+            let (start, end) = range_arguments(range, items.len(), "vector-copy!")?;
+            items[start..end].to_vec()
+        };
+        let mut items = to.lock().unwrap();
+        if at + source.len() > items.len() {
+            return Err(format!("Destination too short for vector-copy!: {} + {} > {}", at, source.len(), items.len()).into());
+        }
+        items[at..at + source.len()].clone_from_slice(&source);
+        Ok(Expr::Void)
+    }
+
+    fn vector_and_range(args: &[Expr], name: &str) -> Result<Vec<Expr>, LispError> {
+        let (vector, range) = match args.split_first() {
+            Some((vector, range)) => (vector_argument(vector, name)?, range),
+            None => return Err(format!("'{}' requires a vector and an optional range", name).into()),
+        };
+
+        let items = vector.lock().unwrap();
+        let (start, end) = range_arguments(range, items.len(), name)?;
+        Ok(items[start..end].to_vec())
+    }
+
+    fn list_to_vector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::List(items)] => Ok(make_vector_value(items.clone())),
+            // map and for-each go through here, so they fail the same way
+            [Expr::CircularList(..)] => Err("A circular list has no end".into()),
+            _ => Err("'list->vector' requires exactly 1 list".into()),
+        }
+    }
+
+    fn vector_to_list(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::List(vector_and_range(args, "vector->list")?))
+    }
+
+    fn vector_append(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut items = Vec::new();
+        for arg in args {
+            items.extend(vector_argument(arg, "vector-append")?.lock().unwrap().iter().cloned());
+        }
+        Ok(make_vector_value(items))
+    }
+
+    // Unlike vector-copy, both ends of the range are required
+    fn subvector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [_, _, _] => Ok(make_vector_value(vector_and_range(args, "subvector")?)),
+            _ => Err("'subvector' requires a vector, a start and an end".into()),
+        }
+    }
+
+    fn vector_reverse(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [vector] => {
+                let mut items = vector_argument(vector, "vector-reverse")?.lock().unwrap().clone();
+                items.reverse();
+                Ok(make_vector_value(items))
+            }
+            _ => Err("'vector-reverse' requires exactly 1 vector".into()),
+        }
+    }
+
+    fn vector_reverse_in_place(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [vector] => {
+                vector_argument(vector, "vector-reverse!")?.lock().unwrap().reverse();
+                Ok(Expr::Void)
+            }
+            _ => Err("'vector-reverse!' requires exactly 1 vector".into()),
+        }
+    }
+
+    fn vector_copy(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(make_vector_value(vector_and_range(args, "vector-copy")?))
+    }
+
+    fn string_to_vector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, range) = match args.split_first() {
+            Some((Expr::Str(s), range)) => (s, range),
+            _ => return Err("'string->vector' requires a string and an optional range".into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let (start, end) = range_arguments(range, chars.len(), "string->vector")?;
+        Ok(make_vector_value(chars[start..end].iter().map(|&c| Expr::Char(c)).collect()))
+    }
+
+    fn vector_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut result = String::new();
+        for item in vector_and_range(args, "vector->string")? {
+            match item {
+                Expr::Char(c) => result.push(c),
+                other => return Err(format!("'vector->string' requires a vector of characters, got {}", other).into()),
+            }
+        }
+
+        Ok(Expr::Str(result))
+    }
+
+    fn make_bytevector_value(bytes: Vec<u8>) -> Expr {
+        Expr::Bytevector(Arc::new(Mutex::new(bytes)))
+    }
+
+    fn byte_value(expr: &Expr) -> Result<u8, LispError> {
+        match expr {
+            Expr::Number(n) if (0.0..=255.0).contains(n) && n.fract() == 0.0 => Ok(*n as u8),
+            _ => Err(format!("Not a byte: {}", expr).into()),
+        }
+    }
+
+    fn bytevector_argument<'a>(expr: &'a Expr, name: &str) -> Result<&'a Arc<Mutex<Vec<u8>>>, LispError> {
+        match expr {
+            Expr::Bytevector(bytes) => Ok(bytes),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn bytevector_range(bytes: &Expr, range: &[Expr], name: &str) -> Result<Vec<u8>, LispError> {
+        let bytes = bytevector_argument(bytes, name)?.lock().unwrap();
+        let (start, end) = range_arguments(range, bytes.len(), name)?;
+        Ok(bytes[start..end].to_vec())
+    }
+
+    fn bytevector_and_range(args: &[Expr], name: &str) -> Result<Vec<u8>, LispError> {
+        match args.split_first() {
+            Some((bytes, range)) => bytevector_range(bytes, range, name),
+            None => Err(format!("'{}' requires a bytevector and an optional range", name).into()),
+        }
+    }
+
+    fn bytevector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(make_bytevector_value(args.iter().map(byte_value).collect::<Result<Vec<u8>, LispError>>()?))
+    }
+
+    fn make_bytevector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (len, fill) = match args {
+            [len] => (index_value(len, "make-bytevector")?, 0),
+            [len, fill] => (index_value(len, "make-bytevector")?, byte_value(fill)?),
+            _ => return Err("'make-bytevector' requires a length and an optional fill byte".into()),
+        };
+
+        Ok(make_bytevector_value(vec![fill; len]))
+    }
+
+    fn is_bytevector(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::Bytevector(_)))),
+            _ => Err("'bytevector?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn bytevector_length(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [bytes] => Ok(Expr::Number(bytevector_argument(bytes, "bytevector-length")?.lock().unwrap().len() as f64)),
+            _ => Err("'bytevector-length' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn bytevector_copy(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(make_bytevector_value(bytevector_and_range(args, "bytevector-copy")?))
+    }
+
+    fn bytevector_copy_into(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (to, at, source) = match args {
+            [to, at, from, range @ ..] => (
+                bytevector_argument(to, "bytevector-copy!")?,
+                index_value(at, "bytevector-copy!")?,
+                bytevector_range(from, range, "bytevector-copy!")?,
+            ),
+            _ => return Err("'bytevector-copy!' requires a bytevector, an index and a source bytevector".into()),
+        };
+
+        // The source range is copied out first, so overlapping copies within one bytevector are safe
+        let mut bytes = to.lock().unwrap();
+        if at + source.len() > bytes.len() {
+            return Err(format!("Destination too short for bytevector-copy!: {} + {} > {}", at, source.len(), bytes.len()).into());
+        }
+        bytes[at..at + source.len()].copy_from_slice(&source);
+        Ok(Expr::Void)
+    }
+
+    fn bytevector_append(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut result = Vec::new();
+        for arg in args {
+            result.extend_from_slice(&bytevector_argument(arg, "bytevector-append")?.lock().unwrap());
+        }
+
+        Ok(make_bytevector_value(result))
+    }
+
+    fn utf8_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let bytes = bytevector_and_range(args, "utf8->string")?;
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Expr::Str(s)),
+            Err(e) => Err(format!("Invalid UTF-8 in utf8->string: {}", e).into()),
+        }
+    }
+
+    fn string_to_utf8(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, range) = match args.split_first() {
+            Some((Expr::Str(s), range)) => (s, range),
+            _ => return Err("'string->utf8' requires a string and an optional range".into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let (start, end) = range_arguments(range, chars.len(), "string->utf8")?;
+        Ok(make_bytevector_value(chars[start..end].iter().collect::<String>().into_bytes()))
+    }
+
+    #[derive(Clone, Copy)]
+    enum Endianness {
+        Big,
+        Little,
+    }
+
+    fn endianness_argument(args: &[Expr], name: &str) -> Result<Endianness, LispError> {
+        match args {
+            [] => Ok(Endianness::Big),
+            [Expr::Symbol(s)] if *s == "big" => Ok(Endianness::Big),
+            [Expr::Symbol(s)] if *s == "little" => Ok(Endianness::Little),
+            _ => Err(format!("'{}' requires the endianness 'big or 'little", name).into()),
+        }
+    }
+
+    // Reads `width` bytes at `index` as an unsigned integer. Numbers are f64, so 64-bit values above
+    // 2^53 lose precision
+    fn bytevector_uint_ref(args: &[Expr], name: &str, width: usize) -> Result<Expr, LispError> {
+        let (bytes, index, endianness) = match args {
+            [bytes, index, rest @ ..] => (
+                bytevector_argument(bytes, name)?,
+                index_value(index, name)?,
+                endianness_argument(rest, name)?,
+            ),
+            _ => return Err(format!("'{}' requires a bytevector, an index and an optional endianness", name).into()),
+        };
+
+        let bytes = bytes.lock().unwrap();
+        let field = match bytes.get(index..index.saturating_add(width)) {
+            Some(field) => field,
+            None => return Err(format!("Index out of range for {}: {}", name, index).into()),
+        };
+        let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+        let value = match endianness {
+            Endianness::Big => field.iter().fold(0, fold),
+            Endianness::Little => field.iter().rev().fold(0, fold),
+        };
+        Ok(Expr::Number(value as f64))
+    }
+
+    fn bytevector_uint_set(args: &[Expr], name: &str, width: usize) -> Result<Expr, LispError> {
+        let (bytes, index, value, endianness) = match args {
+            [bytes, index, value, rest @ ..] => (
+                bytevector_argument(bytes, name)?,
+                index_value(index, name)?,
+                value,
+                endianness_argument(rest, name)?,
+            ),
+            _ => return Err(format!("'{}' requires a bytevector, an index, a value and an optional endianness", name).into()),
+        };
+
+        let limit = 2f64.powi(8 * width as i32);
+        let value = match value {
+            Expr::Number(n) if *n >= 0.0 && *n < limit && n.fract() == 0.0 => *n as u64,
+            _ => return Err(format!("Value out of range for {}: {}", name, value).into()),
+        };
+
+        let mut bytes = bytes.lock().unwrap();
+        let field = match bytes.get_mut(index..index.saturating_add(width)) {
+            Some(field) => field,
+            None => return Err(format!("Index out of range for {}: {}", name, index).into()),
+        };
+        for (i, byte) in field.iter_mut().enumerate() {
+            let shift = match endianness {
+                Endianness::Big => 8 * (width - 1 - i),
+                Endianness::Little => 8 * i,
+            };
+            *byte = (value >> shift) as u8;
+        }
+        Ok(Expr::Void)
+    }
+
+    fn bytevector_u8_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [_, _] => bytevector_uint_ref(args, "bytevector-u8-ref", 1),
+            _ => Err("'bytevector-u8-ref' requires a bytevector and an index".into()),
+        }
+    }
+
+    fn bytevector_u8_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [_, _, _] => bytevector_uint_set(args, "bytevector-u8-set!", 1),
+            _ => Err("'bytevector-u8-set!' requires a bytevector, an index and a byte".into()),
+        }
+    }
+
+    fn bytevector_s8_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let byte = match args {
+            [_, _] => bytevector_uint_ref(args, "bytevector-s8-ref", 1)?,
+            _ => return Err("'bytevector-s8-ref' requires a bytevector and an index".into()),
+        };
+
+        match byte {
+            Expr::Number(n) => Ok(Expr::Number(n as u8 as i8 as f64)),
+            other => Ok(other),
+        }
+    }
+
+    fn bytevector_u16_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_ref(args, "bytevector-u16-ref", 2)
+    }
+
+    fn bytevector_u16_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_set(args, "bytevector-u16-set!", 2)
+    }
+
+    fn bytevector_u32_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_ref(args, "bytevector-u32-ref", 4)
+    }
+
+    fn bytevector_u32_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_set(args, "bytevector-u32-set!", 4)
+    }
+
+    fn bytevector_u64_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_ref(args, "bytevector-u64-ref", 8)
+    }
+
+    fn bytevector_u64_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        bytevector_uint_set(args, "bytevector-u64-set!", 8)
+    }
+
+    // The object a handler receives: what was raised, or an error object for an error from the interpreter
+    fn condition_object(error: LispError) -> Expr {
+        match error {
+            LispError::Raised(obj) => obj,
+            other => Expr::ErrorObject(Arc::new(ErrorObject {
+                message: other.to_string(),
+                irritants: Vec::new(),
+                kind: error_kind(&other),
+            })),
+        }
+    }
+
+    // Without first-class continuations a handler cannot escape from the raise point, so an
+    // exception from `raise` or from the interpreter unwinds to with-exception-handler and the
+    // handler's value becomes its result. raise-continuable calls the handler in place instead
+    fn with_exception_handler(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (handler, thunk) = match args {
+            [handler, thunk] if is_procedure(handler) && is_procedure(thunk) => (handler, thunk),
+            _ => return Err("'with-exception-handler' requires a handler and a thunk".into()),
+        };
+
+        env.handlers.push(handler.clone());
+        let depth = env.handlers.len();
+        let result = apply_procedure(thunk, &[], env);
+        env.handlers.truncate(depth - 1);
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(error @ (LispError::Raised(_) | LispError::Eval(_) | LispError::Read(_) | LispError::FileError(_))) => {
+                env.backtrace.clear();
+                apply_procedure(handler, &[condition_object(error)], env)
+            }
+            // Outside the sandbox that hit it, a sandbox limit is just how sandbox-eval failed
+            Err(error @ (LispError::SandboxTimeout(_) | LispError::SandboxMemoryExceeded(_)))
+                if env.timed_out.is_none() && env.memory_limit.is_none() =>
+            {
+                env.backtrace.clear();
+                apply_procedure(handler, &[condition_object(error)], env)
+            }
+            // Interrupts, failed assertions, sandbox limits and abandoned coroutines stop the evaluation
+            // as a whole; an abandoned coroutine in particular must unwind to its end, or dropping it
+            // never returns
+            Err(error) => Err(error),
+        }
+    }
+
+    fn raise(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] => Err(LispError::Raised(obj.clone())),
+            _ => Err("'raise' requires exactly 1 argument".into()),
+        }
+    }
+
+    // The handler runs with the outer handlers installed, so raising from it reaches the next one
+    fn raise_continuable(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let obj = match args {
+            [obj] => obj,
+            _ => return Err("'raise-continuable' requires exactly 1 argument".into()),
+        };
+
+        let handler = match env.handlers.pop() {
+            Some(handler) => handler,
+            None => return Err(LispError::Raised(obj.clone())),
+        };
+        let result = apply_procedure(&handler, std::slice::from_ref(obj), env);
+        env.handlers.push(handler);
+        result
+    }
+
+    fn error(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (message, irritants) = match args.split_first() {
+            Some((Expr::Str(message), irritants)) => (message.clone(), irritants.to_vec()),
+            _ => return Err("'error' requires a message string".into()),
+        };
+
+        Err(LispError::Raised(Expr::ErrorObject(Arc::new(ErrorObject {
+            message,
+            irritants,
+            kind: "error",
+        }))))
+    }
+
+    fn error_object_argument<'a>(args: &'a [Expr], name: &str) -> Result<&'a ErrorObject, LispError> {
+        match args {
+            [Expr::ErrorObject(error)] => Ok(error),
+            [_] => Err(format!("Invalid argument type for {}", name).into()),
+            _ => Err(format!("'{}' requires exactly 1 argument", name).into()),
+        }
+    }
+
+    fn error_object_kind_is(args: &[Expr], name: &str, kind: Option<&str>) -> Result<Expr, LispError> {
+        match args {
+            [Expr::ErrorObject(error)] => Ok(Expr::Bool(kind.is_none_or(|kind| error.kind == kind))),
+            [_] => Ok(Expr::Bool(false)),
+            _ => Err(format!("'{}' requires exactly 1 argument", name).into()),
+        }
+    }
+
+    fn is_error_object(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        error_object_kind_is(args, "error-object?", None)
+    }
+
+    fn is_read_error(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        error_object_kind_is(args, "read-error?", Some("read"))
+    }
+
+    fn is_file_error(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        error_object_kind_is(args, "file-error?", Some("file"))
+    }
+
+    fn error_object_message(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::Str(error_object_argument(args, "error-object-message")?.message.clone()))
+    }
+
+    fn error_object_irritants(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::List(error_object_argument(args, "error-object-irritants")?.irritants.clone()))
+    }
+
+    // Any raised object, as the message an uncaught exception would print
+    fn condition_report_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::ErrorObject(error)] => Ok(Expr::Str(error.report())),
+            [Expr::Str(s)] => Ok(Expr::Str(s.clone())),
+            [obj] => Ok(Expr::Str(obj.to_string())),
+            _ => Err("'condition/report-string' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn parse_transformer(spec: &Expr, env: &mut Environment) -> Result<Macro, LispError> {
+        let parts = match spec {
+            Expr::List(parts) => parts,
+            _ => return Err(format!("Invalid macro transformer: {}", spec).into()),
+        };
+
+        match parts.first() {
+            Some(Expr::Symbol(s)) if *s == "syntax-rules" => parse_syntax_rules(parts),
+            Some(Expr::Symbol(s)) if *s == "identifier-syntax" => parse_identifier_syntax(&parts[1..]),
+            Some(Expr::Symbol(s)) if *s == "make-variable-transformer" && parts.len() == 2 => {
+                match eval(&parts[1], env)? {
+                    procedure if is_procedure(&procedure) => Ok(Macro::Variable(procedure)),
+                    other => Err(format!("'make-variable-transformer' requires a procedure, got {}", other).into()),
+                }
+            }
+            _ => Err(format!("Invalid macro transformer: {}", spec).into()),
+        }
+    }
+
+    fn parse_identifier_syntax(args: &[Expr]) -> Result<Macro, LispError> {
+        let (getter, setter) = match args {
+            [template] => return Ok(Macro::Identifier { template: template.clone(), setter: None }),
+            [getter, setter] => (getter, setter),
+            _ => return Err("'identifier-syntax' requires a template or an (id template) and a set! clause".into()),
+        };
+
+        let template = match getter {
+            Expr::List(getter) if getter.len() == 2 && matches!(getter[0], Expr::Symbol(_)) => getter[1].clone(),
+            _ => return Err(format!("Invalid identifier-syntax clause: {}", getter).into()),
+        };
+        let setter = match setter {
+            Expr::List(clause) if clause.len() == 2 => match &clause[0] {
+                Expr::List(pattern) if pattern.len() == 3 && pattern[0] == symbol("set!") => match &pattern[2] {
+                    Expr::Symbol(value) => (*value, clause[1].clone()),
+                    _ => return Err(format!("Invalid identifier-syntax set! pattern: {}", clause[0]).into()),
+                },
+                _ => return Err(format!("Invalid identifier-syntax set! pattern: {}", clause[0]).into()),
+            },
+            _ => return Err(format!("Invalid identifier-syntax set! clause: {}", setter).into()),
+        };
+
+        Ok(Macro::Identifier { template, setter: Some(setter) })
+    }
+
+    fn parse_syntax_rules(parts: &[Expr]) -> Result<Macro, LispError> {
+        if parts.len() < 2 {
+            return Err("A macro must be defined with (syntax-rules (literal ...) rule ...)".into());
+        }
+
+        let literals = match &parts[1] {
+            Expr::List(literals) => literals
+                .iter()
+                .map(|literal| match literal {
+                    Expr::Symbol(literal) => Ok(*literal),
+                    _ => Err(LispError::from(format!("Invalid syntax-rules literal: {}", literal))),
+                })
+                .collect::<Result<Vec<SymbolId>, LispError>>()?,
+            _ => return Err("'syntax-rules' requires a list of literals".into()),
+        };
+
+        let mut rules = Vec::new();
+        for rule in &parts[2..] {
+            match rule {
+                Expr::List(rule) if rule.len() == 2 && matches!(rule[0], Expr::List(_)) => {
+                    rules.push((rule[0].clone(), rule[1].clone()));
+                }
+                _ => return Err(format!("Each syntax-rules rule must be (pattern template): {}", rule).into()),
+            }
+        }
+
+        Ok(Macro::Rules { literals, rules })
+    }
+
+    // What a pattern variable matched. Variables under an ellipsis match once per repetition
+    #[derive(Clone)]
+    enum MacroBinding {
+        One(Expr),
+        Many(Vec<MacroBinding>),
+    }
+
+    fn is_ellipsis(expr: &Expr) -> bool {
+        matches!(expr, Expr::Symbol(s) if *s == "...")
+    }
+
+    fn pattern_vars(pattern: &Expr, literals: &[SymbolId], vars: &mut Vec<SymbolId>) {
+        match pattern {
+            Expr::Symbol(s) if *s == "_" || *s == "..." || *s == "." || literals.contains(s) => {}
+            Expr::Symbol(s) => vars.push(*s),
+            Expr::List(patterns) => {
+                for pattern in patterns {
+                    pattern_vars(pattern, literals, vars);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn match_pattern(pattern: &Expr, form: &Expr, literals: &[SymbolId], bindings: &mut HashMap<SymbolId, MacroBinding>) -> bool {
+        match pattern {
+            Expr::Symbol(s) if *s == "_" => true,
+            Expr::Symbol(s) if literals.contains(s) => form == pattern,
+            Expr::Symbol(s) => {
+                bindings.insert(*s, MacroBinding::One(form.clone()));
+                true
+            }
+            Expr::List(patterns) => match form {
+                Expr::List(forms) => match_list_pattern(patterns, forms, literals, bindings),
+                _ => false,
+            },
+            _ => pattern == form,
+        }
+    }
+
+    // Handles `p ...` anywhere in the list, and a dotted `. rest` tail that matches the remaining forms
+    fn match_list_pattern(
+        patterns: &[Expr],
+        forms: &[Expr],
+        literals: &[SymbolId],
+        bindings: &mut HashMap<SymbolId, MacroBinding>,
+    ) -> bool {
+        let (patterns, tail) = match patterns {
+            [init @ .., Expr::Symbol(dot), tail] if *dot == "." => (init, Some(tail)),
+            _ => (patterns, None),
+        };
+
+        let (before, repeated, after) = match patterns.iter().position(is_ellipsis) {
+            Some(i) if i > 0 => (&patterns[..i - 1], Some(&patterns[i - 1]), &patterns[i + 1..]),
+            _ => (patterns, None, &patterns[..0]),
+        };
+
+        let fixed = before.len() + after.len();
+        let repeats = match (repeated, tail) {
+            (Some(_), _) if forms.len() >= fixed => forms.len() - fixed,
+            (None, Some(_)) if forms.len() >= fixed => 0,
+            (None, None) if forms.len() == fixed => 0,
+            _ => return false,
+        };
+        // Without an ellipsis a dotted tail takes whatever follows the fixed patterns
+        let rest_start = if repeated.is_some() { forms.len() } else { before.len() };
+
+        for (pattern, form) in before.iter().zip(forms) {
+            if !match_pattern(pattern, form, literals, bindings) {
+                return false;
+            }
+        }
+
+        if let Some(repeated) = repeated {
+            let mut matches = Vec::with_capacity(repeats);
+            for form in &forms[before.len()..before.len() + repeats] {
+                let mut iteration = HashMap::new();
+                if !match_pattern(repeated, form, literals, &mut iteration) {
+                    return false;
+                }
+                matches.push(iteration);
+            }
+
+            let mut vars = Vec::new();
+            pattern_vars(repeated, literals, &mut vars);
+            for var in vars {
+                let values = matches.iter_mut().filter_map(|iteration| iteration.remove(&var)).collect();
+                bindings.insert(var, MacroBinding::Many(values));
+            }
+
+            for (pattern, form) in after.iter().zip(&forms[before.len() + repeats..]) {
+                if !match_pattern(pattern, form, literals, bindings) {
+                    return false;
+                }
+            }
+        }
+
+        match tail {
+            Some(tail) => match_pattern(tail, &Expr::List(forms[rest_start..].to_vec()), literals, bindings),
+            None => true,
+        }
+    }
+
+    // Symbols the template itself binds with lambda, let and friends. They are renamed on each
+    // expansion so they cannot capture the caller's variables
+    fn template_binders(template: &Expr, bindings: &HashMap<SymbolId, MacroBinding>, binders: &mut HashSet<SymbolId>) {
+        let items = match template {
+            Expr::List(items) => items,
+            _ => return,
+        };
+
+        let mut add = |expr: &Expr| {
+            let formals = match expr {
+                Expr::List(formals) => formals.clone(),
+                other => vec![other.clone()],
+            };
+            for formal in formals {
+                if let Expr::Symbol(s) = formal {
+                    if !bindings.contains_key(&s) && s != "." && s != "..." {
+                        binders.insert(s);
+                    }
+                }
+            }
+        };
+        let binding_names = |expr: &Expr| match expr {
+            Expr::List(specs) => specs
+                .iter()
+                .filter_map(|spec| match spec {
+                    Expr::List(spec) => spec.first().cloned(),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        match items.first() {
+            Some(Expr::Symbol(head)) if *head == "lambda" || *head == "receive" => {
+                if let Some(formals) = items.get(1) {
+                    add(formals);
+                }
+            }
+            Some(Expr::Symbol(head)) if ["let", "let*", "letrec", "letrec*", "let-values", "do"].contains(&head.as_str()) => {
+                let specs = match items.get(1) {
+                    // Named let
+                    Some(name @ Expr::Symbol(_)) => {
+                        add(name);
+                        items.get(2)
+                    }
+                    specs => specs,
+                };
+                if let Some(specs) = specs {
+                    for name in binding_names(specs) {
+                        add(&name);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for item in items {
+            template_binders(item, bindings, binders);
+        }
+    }
+
+    static MACRO_RENAMES: AtomicUsize = AtomicUsize::new(0);
+
+    fn expand_template(
+        template: &Expr,
+        bindings: &HashMap<SymbolId, MacroBinding>,
+        renames: &HashMap<SymbolId, SymbolId>,
+    ) -> Result<Expr, LispError> {
+        match template {
+            Expr::Symbol(s) => match (bindings.get(s), renames.get(s)) {
+                (Some(MacroBinding::One(value)), _) => Ok(value.clone()),
+                (Some(MacroBinding::Many(_)), _) => {
+                    Err(format!("Pattern variable {} must be followed by ... in the template", s).into())
+                }
+                (None, Some(renamed)) => Ok(Expr::Symbol(*renamed)),
+                (None, None) => Ok(template.clone()),
+            },
+            Expr::List(items) => {
+                let mut expanded = Vec::new();
+                let mut i = 0;
+                while i < items.len() {
+                    let mut depth = 0;
+                    while items.get(i + 1 + depth).is_some_and(is_ellipsis) {
+                        depth += 1;
+                    }
+
+                    if depth == 0 {
+                        expanded.push(expand_template(&items[i], bindings, renames)?);
+                    } else {
+                        expand_repeated(&items[i], depth, bindings, renames, &mut expanded)?;
+                    }
+                    i += 1 + depth;
+                }
+                Ok(Expr::List(expanded))
+            }
+            _ => Ok(template.clone()),
+        }
+    }
+
+    // Expands `template` once per repetition of the ellipsis variables it uses, `depth` levels deep
+    fn expand_repeated(
+        template: &Expr,
+        depth: usize,
+        bindings: &HashMap<SymbolId, MacroBinding>,
+        renames: &HashMap<SymbolId, SymbolId>,
+        out: &mut Vec<Expr>,
+    ) -> Result<(), LispError> {
+        let mut vars = Vec::new();
+        pattern_vars(template, &[], &mut vars);
+        let repeated: Vec<(SymbolId, &Vec<MacroBinding>)> = vars
+            .into_iter()
+            .filter_map(|var| match bindings.get(&var) {
+                Some(MacroBinding::Many(values)) => Some((var, values)),
+                _ => None,
+            })
+            .collect();
+
+        let count = match repeated.first() {
+            Some((_, values)) => values.len(),
+            None => return Err(format!("No pattern variable under ... in template {}", template).into()),
+        };
+        if repeated.iter().any(|(_, values)| values.len() != count) {
+            return Err(format!("Pattern variables under ... repeat a different number of times in {}", template).into());
+        }
+
+        for i in 0..count {
+            let mut iteration = bindings.clone();
+            for (var, values) in &repeated {
+                iteration.insert(*var, values[i].clone());
+            }
+            if depth > 1 {
+                expand_repeated(template, depth - 1, &iteration, renames, out)?;
+            } else {
+                out.push(expand_template(template, &iteration, renames)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_macro(literals: &[SymbolId], rules: &[(Expr, Expr)], form: &[Expr]) -> Result<Expr, LispError> {
+        // The keyword position is ignored, so the pattern's head can be the macro name or _
+        let args = Expr::List(form[1..].to_vec());
+        for (pattern, template) in rules {
+            let pattern = match pattern {
+                Expr::List(pattern) => Expr::List(pattern[1..].to_vec()),
+                _ => continue,
+            };
+
+            let mut bindings = HashMap::new();
+            if !match_pattern(&pattern, &args, literals, &mut bindings) {
+                continue;
+            }
+
+            // Renamed names contain a space, which keeps them apart from anything the reader produces
+            let mut binders = HashSet::new();
+            template_binders(template, &bindings, &mut binders);
+            let renames = binders
+                .into_iter()
+                .map(|name| {
+                    let n = MACRO_RENAMES.fetch_add(1, Ordering::Relaxed);
+                    (name, SymbolId::intern(&format!("{} {}", name, n)))
+                })
+                .collect();
+            return expand_template(template, &bindings, &renames);
+        }
+
+        Err(format!("No syntax-rules pattern matches {}", Expr::List(form.to_vec())).into())
+    }
+
+    fn eval_define_syntax(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Symbol(name), spec] => {
+                let transformer = parse_transformer(spec, env)?;
+                env.define(*name, Expr::Macro(Arc::new(transformer)));
+                Ok(Expr::Symbol(*name))
+            }
+            _ => Err("'define-syntax' requires a name and a transformer".into()),
+        }
+    }
+
+    // Macros are looked up when a form is expanded, so the transformers of both forms can refer to
+    // each other and let-syntax behaves like letrec-syntax
+    fn eval_let_syntax(args: &[Expr], env: &mut Environment, form: &str) -> Result<Expr, LispError> {
+        let (specs, body) = match args.split_first() {
+            Some((specs, body)) => (binding_pairs(specs, form)?, body),
+            None => return Err(format!("'{}' requires a list of bindings", form).into()),
+        };
+
+        let mut bindings = Vec::new();
+        for (name, spec) in specs {
+            bindings.push((name, Expr::Macro(Arc::new(parse_transformer(spec, env)?))));
+        }
+
+        let scope = env.scope.clone();
+        eval_with_bindings(&scope, bindings, body, env)
+    }
+
+    // A macro named on its own; syntax-rules macros evaluate to themselves
+    fn expand_identifier(transformer: &Arc<Macro>, form: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        match &**transformer {
+            Macro::Rules { .. } => Ok(Expr::Macro(transformer.clone())),
+            Macro::Identifier { template, .. } => eval(template, env),
+            Macro::Variable(procedure) => {
+                let expansion = apply_procedure(procedure, std::slice::from_ref(form), env)?;
+                eval(&expansion, env)
+            }
+        }
+    }
+
+    fn eval_macro_set(transformer: &Arc<Macro>, form: &Expr, value: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        let expansion = match &**transformer {
+            Macro::Identifier { setter: Some((var, template)), .. } => {
+                let bindings = HashMap::from([(*var, MacroBinding::One(value.clone()))]);
+                expand_template(template, &bindings, &HashMap::new())?
+            }
+            Macro::Variable(procedure) => apply_procedure(procedure, std::slice::from_ref(form), env)?,
+            _ => return Err(format!("Cannot set! a macro without a set! clause: {}", form).into()),
+        };
+
+        eval(&expansion, env)
+    }
+
+    // The narrowest level of the numeric tower a value belongs to. Every number is an f64, so
+    // there is no separate exact level: exact-integer? is integer?, and 3.0 counts as one
+    #[derive(PartialEq, PartialOrd)]
+    enum NumericClass {
+        Integer,
+        Rational,
+        Real,
+        NotANumber,
+    }
+
+    fn numeric_class(expr: &Expr) -> NumericClass {
+        match expr {
+            Expr::Number(n) if n.fract() == 0.0 => NumericClass::Integer,
+            Expr::Number(n) if n.is_finite() => NumericClass::Rational,
+            Expr::Number(_) => NumericClass::Real,
+            _ => NumericClass::NotANumber,
+        }
+    }
+
+    fn numeric_class_at_most(args: &[Expr], class: NumericClass, name: &str) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(numeric_class(arg) <= class)),
+            _ => Err(format!("'{}' requires exactly 1 argument", name).into()),
+        }
+    }
+
+    fn is_exact_integer(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Integer, "exact-integer?")
+    }
+
+    fn is_integer(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Integer, "integer?")
+    }
+
+    fn is_rational(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Rational, "rational?")
+    }
+
+    fn is_real(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Real, "real?")
+    }
+
+    // There are no complex numbers, so complex? and number? accept exactly the reals
+    fn is_complex(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Real, "complex?")
+    }
+
+    fn is_number(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        numeric_class_at_most(args, NumericClass::Real, "number?")
+    }
+
+    fn square(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n)] => Ok(Expr::Number(n * n)),
+            [_] => Err("Invalid argument type for square".into()),
+            _ => Err("'square' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn abs(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n)] => Ok(Expr::Number(n.abs())),
+            [_] => Err("Invalid argument type for abs".into()),
+            _ => Err("'abs' requires exactly 1 argument".into()),
+        }
+    }
+
+    // There are no complex numbers, so negative numbers have no square root
+    fn sqrt(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n)] if *n < 0.0 => Err(format!("Cannot take the square root of {}", n).into()),
+            [Expr::Number(n)] => Ok(Expr::Number(n.sqrt())),
+            [_] => Err("Invalid argument type for sqrt".into()),
+            _ => Err("'sqrt' requires exactly 1 argument".into()),
+        }
+    }
+
+    // Newton's method on integers, starting above the root so the estimates decrease to it
+    fn integer_sqrt(n: u64) -> u64 {
+        if n < 2 {
+            return n;
+        }
+
+        let mut x = n;
+        let mut y = x.div_ceil(2);
+        while y < x {
+            x = y;
+            y = (x + n / x) / 2;
+        }
+        x
+    }
+
+    fn exact_integer_sqrt(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let n = match args {
+            // Above 2^53 a float no longer holds every integer
+            [Expr::Number(n)] if *n >= 0.0 && n.fract() == 0.0 && *n <= 9007199254740992.0 => *n as u64,
+            _ => return Err("'exact-integer-sqrt' requires a non-negative integer".into()),
+        };
+
+        let root = integer_sqrt(n);
+        Ok(Expr::Values(vec![Expr::Number(root as f64), Expr::Number((n - root * root) as f64)]))
+    }
+
+    fn separator_argument(arg: Option<&Expr>, default: &str, name: &str) -> Result<String, LispError> {
+        match arg {
+            None => Ok(default.to_string()),
+            Some(Expr::Char(c)) => Ok(c.to_string()),
+            Some(Expr::Str(s)) => Ok(s.clone()),
+            Some(_) => Err(format!("Invalid separator for {}", name).into()),
+        }
+    }
+
+    // Groups the integer digits in threes: (number->string/pretty n [separator [decimal-point]])
+    fn number_to_string_pretty(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let n = match args.first() {
+            Some(Expr::Number(n)) if args.len() <= 3 => *n,
+            _ => return Err("'number->string/pretty' requires a number and optional separator and decimal point".into()),
+        };
+        let separator = separator_argument(args.get(1), ",", "number->string/pretty")?;
+        let point = separator_argument(args.get(2), ".", "number->string/pretty")?;
+
+        if !n.is_finite() {
+            return Ok(Expr::Str(n.to_string()));
+        }
+
+        let plain = n.abs().to_string();
+        let (digits, fraction) = match plain.split_once('.') {
+            Some((digits, fraction)) => (digits, Some(fraction)),
+            None => (plain.as_str(), None),
+        };
+
+        let mut result = String::new();
+        if n < 0.0 {
+            result.push('-');
+        }
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                result.push_str(&separator);
+            }
+            result.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            result.push_str(&point);
+            result.push_str(fraction);
+        }
+        Ok(Expr::Str(result))
+    }
+
+    fn number_to_string_scientific(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n)] => Ok(Expr::Str(format!("{:e}", n))),
+            _ => Err("'number->string/scientific' requires exactly 1 number".into()),
+        }
+    }
+
+    fn number_to_string_fixed(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n), places] => Ok(Expr::Str(format!("{:.*}", index_value(places, "number->string/fixed")?, n))),
+            _ => Err("'number->string/fixed' requires a number and a count of decimal places".into()),
+        }
+    }
+
+    // Digits above 9 are written in upper case, as in `FF`
+    fn integer_to_radix_string(n: f64, radix: u32, name: &str) -> Result<Expr, LispError> {
+        if n.fract() != 0.0 || n.abs() > u64::MAX as f64 {
+            return Err(format!("'{}' requires an integer", name).into());
+        }
+
+        let mut magnitude = n.abs() as u64;
+        let mut digits = Vec::new();
+        loop {
+            digits.push(char::from_digit((magnitude % radix as u64) as u32, radix).unwrap().to_ascii_uppercase());
+            magnitude /= radix as u64;
+            if magnitude == 0 {
+                break;
+            }
+        }
+        if n < 0.0 {
+            digits.push('-');
+        }
+        Ok(Expr::Str(digits.into_iter().rev().collect()))
+    }
+
+    // (number->string n [radix])
+    fn number_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [n @ Expr::Number(_)] | [n @ Expr::Number(_), Expr::Number(10.0)] => Ok(Expr::Str(n.to_string())),
+            [Expr::Number(n), Expr::Number(radix)] if [2.0, 8.0, 16.0].contains(radix) => {
+                integer_to_radix_string(*n, *radix as u32, "number->string")
+            }
+            [Expr::Number(_), radix] => Err(format!("Invalid radix for number->string: {}", radix).into()),
+            _ => Err("'number->string' requires a number and an optional radix".into()),
+        }
+    }
+
+    fn radix_number_to_string(args: &[Expr], radix: u32, name: &str) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n)] => integer_to_radix_string(*n, radix, name),
+            _ => Err(format!("'{}' requires exactly 1 number", name).into()),
+        }
+    }
+
+    fn number_to_string_hex(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_number_to_string(args, 16, "number->string/hex")
+    }
+
+    fn number_to_string_bin(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_number_to_string(args, 2, "number->string/bin")
+    }
+
+    fn number_to_string_oct(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_number_to_string(args, 8, "number->string/oct")
+    }
+
+    // The digits alone, without a #x-style prefix; #f when they are not valid in the radix
+    fn radix_string_to_number(args: &[Expr], prefix: &str, name: &str) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(digits)] if !digits.starts_with('#') => {
+                Ok(parse_number(&format!("{}{}", prefix, digits.trim())).map_or(Expr::Bool(false), Expr::Number))
+            }
+            [Expr::Str(_)] => Ok(Expr::Bool(false)),
+            _ => Err(format!("'{}' requires exactly 1 string", name).into()),
+        }
+    }
+
+    fn hex_to_number(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_string_to_number(args, "#x", "hex->number")
+    }
+
+    fn bin_to_number(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_string_to_number(args, "#b", "bin->number")
+    }
+
+    fn oct_to_number(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        radix_string_to_number(args, "#o", "oct->number")
+    }
+
+    type CharPredicate = fn(char) -> bool;
+
+    static CHAR_SET_FULL: LazyLock<Arc<CharSet>> =
+        LazyLock::new(|| Arc::new(CharSet::from_ranges(vec![(0, 0xD7FF), (0xE000, char::MAX as u32)])));
+
+    // The predefined SRFI-14 sets, computed once on first use
+    static STANDARD_CHAR_SETS: LazyLock<Vec<(&'static str, Arc<CharSet>)>> = LazyLock::new(|| {
+        let sets: [(&str, CharPredicate); 9] = [
+            ("char-set:alphabetic", char::is_alphabetic),
+            ("char-set:numeric", char::is_numeric),
+            ("char-set:whitespace", char::is_whitespace),
+            ("char-set:upper-case", char::is_uppercase),
+            ("char-set:lower-case", char::is_lowercase),
+            ("char-set:letter+digit", char::is_alphanumeric),
+            ("char-set:punctuation", |c| c.is_ascii_punctuation()),
+            ("char-set:ascii", |c| c.is_ascii()),
+            ("char-set:empty", |_| false),
+        ];
+
+        let mut sets: Vec<(&str, Arc<CharSet>)> =
+            sets.iter().map(|(name, predicate)| (*name, Arc::new(CharSet::from_predicate(*predicate)))).collect();
+        sets.push(("char-set:full", CHAR_SET_FULL.clone()));
+        sets
+    });
+
+    fn char_set_argument<'a>(expr: &'a Expr, name: &str) -> Result<&'a Arc<CharSet>, LispError> {
+        match expr {
+            Expr::CharSet(set) => Ok(set),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn char_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut ranges = Vec::new();
+        for arg in args {
+            match arg {
+                Expr::Char(c) => ranges.push((*c as u32, *c as u32)),
+                _ => return Err("'char-set' requires characters".into()),
+            }
+        }
+
+        Ok(Expr::CharSet(Arc::new(CharSet::from_ranges(ranges))))
+    }
+
+    fn string_to_char_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(Expr::CharSet(Arc::new(CharSet::from_ranges(
+                s.chars().map(|c| (c as u32, c as u32)).collect(),
+            )))),
+            _ => Err("'string->char-set' requires exactly 1 string".into()),
+        }
+    }
+
+    fn is_char_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [arg] => Ok(Expr::Bool(matches!(arg, Expr::CharSet(_)))),
+            _ => Err("'char-set?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn char_set_member(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [set, Expr::Char(c)] => Ok(Expr::Bool(char_set_argument(set, "char-set-member?")?.contains(*c))),
+            _ => Err("'char-set-member?' requires a char-set and a character".into()),
+        }
+    }
+
+    fn char_set_size(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [set] => Ok(Expr::Number(char_set_argument(set, "char-set-size")?.size() as f64)),
+            _ => Err("'char-set-size' requires exactly 1 char-set".into()),
+        }
+    }
+
+    // Folds `op` over the sets, left to right
+    fn fold_char_sets(args: &[Expr], name: &str, op: fn(&CharSet, &CharSet) -> CharSet) -> Result<Expr, LispError> {
+        let (first, rest) = match args.split_first() {
+            Some((first, rest)) => (char_set_argument(first, name)?, rest),
+            None => return Err(format!("'{}' requires at least one char-set", name).into()),
+        };
+
+        let mut result = CharSet::new(first.ranges().to_vec());
+        for set in rest {
+            result = op(&result, char_set_argument(set, name)?);
+        }
+        Ok(Expr::CharSet(Arc::new(result)))
+    }
+
+    fn char_set_union(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        fold_char_sets(args, "char-set-union", |a, b| CharSet::from_ranges([a.ranges(), b.ranges()].concat()))
+    }
+
+    fn char_set_intersection(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        fold_char_sets(args, "char-set-intersection", CharSet::intersection)
+    }
+
+    fn char_set_difference(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        fold_char_sets(args, "char-set-difference", CharSet::difference)
+    }
+
+    fn char_set_complement(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [set] => Ok(Expr::CharSet(Arc::new(CHAR_SET_FULL.difference(char_set_argument(set, "char-set-complement")?)))),
+            _ => Err("'char-set-complement' requires exactly 1 char-set".into()),
+        }
+    }
+
+    // Keeps the characters of `s` matching a char-set, a character or a predicate
+    fn string_filter(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (criterion, s) = match args {
+            [criterion, Expr::Str(s)] => (criterion, s),
+            _ => return Err("'string-filter' requires a char-set, character or predicate and a string".into()),
+        };
+
+        let mut result = String::new();
+        for c in s.chars() {
+            if char_matches(criterion, c, "string-filter", env)? {
+                result.push(c);
+            }
+        }
+        Ok(Expr::Str(result))
+    }
+
+    // A char-set, a character to compare against, or a predicate to call
+    fn char_matches(criterion: &Expr, c: char, name: &str, env: &mut Environment) -> Result<bool, LispError> {
+        match criterion {
+            Expr::CharSet(set) => Ok(set.contains(c)),
+            Expr::Char(wanted) => Ok(c == *wanted),
+            predicate if is_procedure(predicate) => Ok(is_truthy(&apply_procedure(predicate, &[Expr::Char(c)], env)?)),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn string_ref(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, index) = match args {
+            [Expr::Str(s), index] => (s, index_value(index, "string-ref")?),
+            _ => return Err("'string-ref' requires a string and an index".into()),
+        };
+
+        match s.chars().nth(index) {
+            Some(c) => Ok(Expr::Char(c)),
+            None => Err(format!("Index {} is out of range for a string of length {}", index, s.chars().count()).into()),
+        }
+    }
+
+    fn string_index_of(args: &[Expr], env: &mut Environment, name: &str, from_right: bool) -> Result<Expr, LispError> {
+        let (s, criterion) = match args {
+            [Expr::Str(s), criterion] => (s, criterion),
+            _ => return Err(format!("'{}' requires a string and a char-set, character or predicate", name).into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let order: Vec<usize> = if from_right { (0..chars.len()).rev().collect() } else { (0..chars.len()).collect() };
+        for i in order {
+            if char_matches(criterion, chars[i], name, env)? {
+                return Ok(Expr::Number(i as f64));
+            }
+        }
+        Ok(Expr::Bool(false))
+    }
+
+    fn string_index(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        string_index_of(args, env, "string-index", false)
+    }
+
+    fn string_index_right(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        string_index_of(args, env, "string-index-right", true)
+    }
+
+    fn string_count(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, criterion) = match args {
+            [Expr::Str(s), criterion] => (s, criterion),
+            _ => return Err("'string-count' requires a string and a char-set, character or predicate".into()),
+        };
+
+        let mut count = 0;
+        for c in s.chars() {
+            if char_matches(criterion, c, "string-count", env)? {
+                count += 1;
+            }
+        }
+        Ok(Expr::Number(count as f64))
+    }
+
+    // Stops at the first character whose match differs from `wanted`; string-every is the
+    // search for a mismatch, string-any the search for a match
+    fn string_search(args: &[Expr], env: &mut Environment, name: &str, wanted: bool) -> Result<Expr, LispError> {
+        let (criterion, s) = match args {
+            [criterion, Expr::Str(s)] => (criterion, s),
+            _ => return Err(format!("'{}' requires a char-set, character or predicate and a string", name).into()),
+        };
+
+        for c in s.chars() {
+            if char_matches(criterion, c, name, env)? == wanted {
+                return Ok(Expr::Bool(wanted));
+            }
+        }
+        Ok(Expr::Bool(!wanted))
+    }
+
+    fn string_every(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        string_search(args, env, "string-every", false)
+    }
+
+    fn string_any(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        string_search(args, env, "string-any", true)
+    }
+
+    fn lambda_argument(args: &[Expr], name: &str, env: &Environment) -> Result<Arc<Lambda>, LispError> {
+        let procedure = match args {
+            [Expr::Symbol(symbol)] => env.lookup(*symbol),
+            [procedure] => Some(procedure.clone()),
+            _ => return Err(format!("'{}' requires exactly 1 argument", name).into()),
+        };
+
+        match procedure {
+            Some(Expr::Lambda(lambda)) => Ok(lambda),
+            _ => Err(format!("'{}' requires a procedure defined in Lisp", name).into()),
+        }
+    }
+
+    fn procedure_source(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let lambda = lambda_argument(args, "procedure-source", env)?;
+        Ok(Expr::List(
+            [symbol("lambda"), lambda.params.clone()].into_iter().chain(lambda.body.iter().cloned()).collect(),
+        ))
+    }
+
+    // (file line column) of the top-level form that created the procedure, or #f if it was not
+    // loaded from a file
+    fn source_location(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match &lambda_argument(args, "source-location", env)?.source {
+            Some(location) => Ok(Expr::List(vec![
+                Expr::Str(location.file.clone()),
+                Expr::Number(location.line as f64),
+                Expr::Number(location.column as f64),
+            ])),
+            None => Ok(Expr::Bool(false)),
+        }
+    }
+
+    fn environment_argument(arg: &Expr, name: &str) -> Result<Arc<EnvironmentValue>, LispError> {
+        match arg {
+            Expr::Environment(environment) => Ok(environment.clone()),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    // (eval expr [environment]); without an environment the expression sees the caller's bindings
+    fn eval_procedure(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (expr, environment) = match args {
+            [expr] => return eval(expr, env),
+            [expr, environment] => (expr, environment_argument(environment, "eval")?),
+            _ => return Err("'eval' requires an expression and an optional environment".into()),
+        };
+
+        let mut target = env.with_scope(environment.scope.clone(), environment.natives);
+        target.handlers = env.handlers.clone();
+        eval(expr, &mut target)
+    }
+
+    fn interaction_environment(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'interaction-environment' takes no arguments".into());
+        }
+
+        let mut scope = env.scope.clone();
+        loop {
+            let parent = scope.lock().unwrap().parent.clone();
+            match parent {
+                Some(parent) => scope = parent,
+                None => break,
+            }
+        }
+        Ok(Expr::Environment(Arc::new(EnvironmentValue { scope, natives: Natives::All })))
+    }
+
+    fn report_environment(args: &[Expr], name: &str, natives: Natives) -> Result<Expr, LispError> {
+        match args {
+            [] | [Expr::Number(_)] => Ok(Expr::Environment(Arc::new(EnvironmentValue {
+                scope: Arc::new(Mutex::new(Scope::default())),
+                natives,
+            }))),
+            _ => Err(format!("'{}' takes an optional report version", name).into()),
+        }
+    }
+
+    // A fresh environment with only the standard procedures, so user definitions are not visible
+    fn scheme_report_environment(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        report_environment(args, "scheme-report-environment", Natives::Standard)
+    }
+
+    // Only the special forms, which are built into eval
+    fn null_environment(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        report_environment(args, "null-environment", Natives::None)
+    }
+
+    // Counts the bytes each thread has allocated and not yet freed, which is what sandbox-eval's
+    // memory limit measures. Memory freed by another thread than the one that allocated it is
+    // credited to the freeing thread, which only makes the count err on the high side.
+    //
+    // The library does not install it, so embedding programs keep their own allocator. A program
+    // that wants memory limits declares it:
+    //
+    //     #[global_allocator]
+    //     static ALLOCATOR: CountingAllocator = CountingAllocator;
+    pub struct CountingAllocator;
+
+    thread_local! {
+        static THREAD_ALLOCATED: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+    }
+
+    static COUNTING_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            if !COUNTING_INSTALLED.load(Ordering::Relaxed) {
+                COUNTING_INSTALLED.store(true, Ordering::Relaxed);
+            }
+            let _ = THREAD_ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + layout.size() as isize));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            let _ = THREAD_ALLOCATED.try_with(|allocated| allocated.set(allocated.get() - layout.size() as isize));
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    fn thread_allocated() -> isize {
+        THREAD_ALLOCATED.with(|allocated| allocated.get())
+    }
+
+    // The key and value of an association list entry, written either (key . value) or (key value)
+    fn pair_parts(entry: &Expr) -> Option<(&Expr, &Expr)> {
+        match entry {
+            Expr::List(parts) if parts.len() == 3 && parts[1] == symbol(".") => Some((&parts[0], &parts[2])),
+            Expr::List(parts) if parts.len() == 2 => Some((&parts[0], &parts[1])),
+            _ => None,
+        }
+    }
+
+    const SANDBOX_STACK_SIZE: usize = 256 * 1024 * 1024;
+    const SANDBOX_NESTING_LIMIT: usize = 10_000;
+
+    // (sandbox-eval expr env [options]) runs `expr` on its own thread, in `env`, with the limits in
+    // the options alist: time-limit-ms and memory-limit-bytes
+    fn sandbox_eval(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (expr, environment, options) = match args {
+            [expr, environment] => (expr, environment_argument(environment, "sandbox-eval")?, &[][..]),
+            [expr, environment, Expr::List(options)] => (expr, environment_argument(environment, "sandbox-eval")?, &options[..]),
+            _ => return Err("'sandbox-eval' requires an expression, an environment and an optional options list".into()),
+        };
+
+        let mut time_limit = None;
+        let mut memory_limit = None;
+        for option in options {
+            match pair_parts(option) {
+                Some((Expr::Symbol(key), Expr::Number(n))) if *key == "time-limit-ms" && *n >= 0.0 => time_limit = Some(*n as u64),
+                Some((Expr::Symbol(key), Expr::Number(n))) if *key == "memory-limit-bytes" && *n >= 0.0 => memory_limit = Some(*n as usize),
+                _ => return Err(format!("Invalid sandbox-eval option: {}", option).into()),
+            }
+        }
+
+        if memory_limit.is_some() && !COUNTING_INSTALLED.load(Ordering::Relaxed) {
+            return Err("memory-limit-bytes needs CountingAllocator installed as the global allocator".into());
+        }
+
+        let mut target = env.with_scope(environment.scope.clone(), environment.natives);
+        let timed_out = Arc::new(AtomicBool::new(false));
+        if let Some(ms) = time_limit {
+            target.timed_out = Some((timed_out.clone(), ms));
+        }
+
+        // Deep recursion fails with an error instead of overflowing the thread's stack
+        target.depth_limit = Some(SANDBOX_NESTING_LIMIT);
+
+        let expr = expr.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .stack_size(SANDBOX_STACK_SIZE)
+            .spawn(move || {
+                // The limit counts what the evaluation allocates beyond the thread's own startup
+                target.memory_limit = memory_limit.map(|limit| (thread_allocated(), limit));
+                let _ = sender.send(eval(&expr, &mut target));
+            })
+            .map_err(|e| format!("Cannot start a sandbox thread: {}", e))?;
+
+        match time_limit {
+            Some(ms) => match receiver.recv_timeout(std::time::Duration::from_millis(ms)) {
+                Ok(result) => result,
+                Err(_) => {
+                    // The thread is detached, not killed: it stops at its next evaluation step, and
+                    // cannot catch that. A native call that blocks, like thread-sleep! or
+                    // mutex-lock!, holds it up until the call returns, and a mutex it locked with
+                    // mutex-lock! stays locked
+                    timed_out.store(true, Ordering::Relaxed);
+                    Err(LispError::SandboxTimeout(ms))
+                }
+            },
+            None => receiver.recv().map_err(|_| LispError::from("Sandboxed evaluation panicked"))?,
+        }
+    }
+
+    // The number a string holds, with any prefix the reader accepts, or #f
+    fn read_number_string(args: &[Expr], name: &str) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(parse_number(s.trim()).map_or(Expr::Bool(false), Expr::Number)),
+            _ => Err(format!("'{}' requires exactly 1 string", name).into()),
+        }
+    }
+
+    fn string_number_radix(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        read_number_string(args, "string-number-radix")
+    }
+
+    fn string_parse_number(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        read_number_string(args, "string-parse-number")
+    }
+
+    // Interior mutability in keys is fine: procedures and ports hash by identity, and like any
+    // equal?-keyed table, a vector key mutated after insertion is simply not found again
+    #[allow(clippy::mutable_key_type)]
+    fn make_hash_table_value(entries: HashMap<Expr, Expr>) -> Expr {
+        Expr::HashTable(Arc::new(Mutex::new(entries)))
+    }
+
+    fn hash_table_argument<'a>(expr: &'a Expr, name: &str) -> Result<&'a Arc<Mutex<HashMap<Expr, Expr>>>, LispError> {
+        match expr {
+            Expr::HashTable(table) => Ok(table),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    // Keys compare with equal?; SRFI-69's optional equality and hash procedures are accepted and ignored
+    fn make_hash_table(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() > 2 {
+            return Err("'make-hash-table' takes an optional equality and hash procedure".into());
+        }
+        Ok(make_hash_table_value(HashMap::new()))
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn make_weak_hash_table(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() > 2 {
+            return Err("'make-weak-hash-table' takes an optional equality and hash procedure".into());
+        }
+        let table = Arc::new(Mutex::new(HashMap::new()));
+        env.weak_tables.lock().unwrap().push(Arc::downgrade(&table));
+        Ok(Expr::HashTable(table))
+    }
+
+    fn is_hash_table(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] => Ok(Expr::Bool(matches!(obj, Expr::HashTable(_)))),
+            _ => Err("'hash-table?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn hash_table_set(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key, value] => {
+                hash_table_argument(table, "hash-table-set!")?.lock().unwrap().insert(key.clone(), value.clone());
+                Ok(Expr::Void)
+            }
+            _ => Err("'hash-table-set!' requires a hash table, a key and a value".into()),
+        }
+    }
+
+    fn hash_table_ref(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (table, key, missing) = match args {
+            [table, key] => (table, key, None),
+            [table, key, missing] => (table, key, Some(missing)),
+            _ => return Err("'hash-table-ref' requires a hash table, a key and an optional thunk".into()),
+        };
+
+        let found = hash_table_argument(table, "hash-table-ref")?.lock().unwrap().get(key).cloned();
+        match (found, missing) {
+            (Some(value), _) => Ok(value),
+            (None, Some(thunk)) => apply_procedure(thunk, &[], env),
+            (None, None) => Err(format!("Key not found in hash table: {}", key).into()),
+        }
+    }
+
+    fn hash_table_ref_default(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key, default] => Ok(hash_table_argument(table, "hash-table-ref/default")?
+                .lock()
+                .unwrap()
+                .get(key)
+                .cloned()
+                .unwrap_or_else(|| default.clone())),
+            _ => Err("'hash-table-ref/default' requires a hash table, a key and a default".into()),
+        }
+    }
+
+    fn hash_table_delete(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key] => {
+                hash_table_argument(table, "hash-table-delete!")?.lock().unwrap().remove(key);
+                Ok(Expr::Void)
+            }
+            _ => Err("'hash-table-delete!' requires a hash table and a key".into()),
+        }
+    }
+
+    fn hash_table_contains(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key] => Ok(Expr::Bool(hash_table_argument(table, "hash-table-contains?")?.lock().unwrap().contains_key(key))),
+            _ => Err("'hash-table-contains?' requires a hash table and a key".into()),
+        }
+    }
+
+    fn hash_table_size(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table] => Ok(Expr::Number(hash_table_argument(table, "hash-table-size")?.lock().unwrap().len() as f64)),
+            _ => Err("'hash-table-size' requires a hash table".into()),
+        }
+    }
+
+    // The entries as they are now, so procedures called on them are free to modify the table
+    fn hash_table_entries(args: &[Expr], name: &str) -> Result<Vec<(Expr, Expr)>, LispError> {
+        match args {
+            [table] => Ok(hash_table_argument(table, name)?
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()),
+            _ => Err(format!("'{}' requires a hash table", name).into()),
+        }
+    }
+
+    fn hash_table_keys(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let entries = hash_table_entries(args, "hash-table-keys")?;
+        Ok(Expr::List(entries.into_iter().map(|(key, _)| key).collect()))
+    }
+
+    fn hash_table_values(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let entries = hash_table_entries(args, "hash-table-values")?;
+        Ok(Expr::List(entries.into_iter().map(|(_, value)| value).collect()))
+    }
+
+    fn hash_table_to_alist(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let entries = hash_table_entries(args, "hash-table->alist")?;
+        Ok(Expr::List(entries.into_iter().map(|(key, value)| make_pair(key, value)).collect()))
+    }
+
+    fn hash_table_walk(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (table, procedure) = match args {
+            [table, procedure] if is_procedure(procedure) => (table, procedure),
+            _ => return Err("'hash-table-walk' requires a hash table and a procedure".into()),
+        };
+
+        for (key, value) in hash_table_entries(std::slice::from_ref(table), "hash-table-walk")? {
+            apply_procedure(procedure, &[key, value], env)?;
+        }
+        Ok(Expr::Void)
+    }
+
+    // Entries of the second table overwrite those of the first
+    fn hash_table_merge(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (target, source) = match args {
+            [target, source] => (target, source),
+            _ => return Err("'hash-table-merge!' requires two hash tables".into()),
+        };
+
+        let entries = hash_table_entries(std::slice::from_ref(source), "hash-table-merge!")?;
+        hash_table_argument(target, "hash-table-merge!")?.lock().unwrap().extend(entries);
+        Ok(target.clone())
+    }
+
+    fn hash_table_copy(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table] | [table, _] => Ok(make_hash_table_value(hash_table_argument(table, "hash-table-copy")?.lock().unwrap().clone())),
+            _ => Err("'hash-table-copy' requires a hash table and an optional mutability flag".into()),
+        }
+    }
+
+    // Entries earlier in the list win, as they would for assoc
+    #[allow(clippy::mutable_key_type)]
+    fn alist_to_hash_table(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let entries = match args {
+            [Expr::List(entries)] | [Expr::List(entries), ..] => entries,
+            _ => return Err("'alist->hash-table' requires an association list".into()),
+        };
+
+        let mut table = HashMap::new();
+        for entry in entries {
+            let (key, value) = pair_parts(entry).ok_or_else(|| format!("Invalid association list entry: {}", entry))?;
+            table.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        Ok(make_hash_table_value(table))
+    }
+
+    // The table is unlocked while `procedure` runs, so it may use the table itself
+    fn update_hash_table(
+        table: &Expr,
+        key: &Expr,
+        procedure: &Expr,
+        missing: impl FnOnce(&mut Environment) -> Result<Expr, LispError>,
+        name: &str,
+        env: &mut Environment,
+    ) -> Result<Expr, LispError> {
+        let table = hash_table_argument(table, name)?;
+        let current = table.lock().unwrap().get(key).cloned();
+        let current = match current {
+            Some(value) => value,
+            None => missing(env)?,
+        };
+
+        let updated = apply_procedure(procedure, &[current], env)?;
+        table.lock().unwrap().insert(key.clone(), updated);
+        Ok(Expr::Void)
+    }
+
+    fn hash_table_update(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key, procedure] => update_hash_table(
+                table,
+                key,
+                procedure,
+                |_| Err(format!("Key not found in hash table: {}", key).into()),
+                "hash-table-update!",
+                env,
+            ),
+            [table, key, procedure, thunk] => {
+                update_hash_table(table, key, procedure, |env| apply_procedure(thunk, &[], env), "hash-table-update!", env)
+            }
+            _ => Err("'hash-table-update!' requires a hash table, a key, a procedure and an optional thunk".into()),
+        }
+    }
+
+    fn hash_table_update_default(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [table, key, procedure, default] => {
+                update_hash_table(table, key, procedure, |_| Ok(default.clone()), "hash-table-update!/default", env)
+            }
+            _ => Err("'hash-table-update!/default' requires a hash table, a key, a procedure and a default".into()),
+        }
+    }
+
+    // 64-bit FNV-1a, which unlike the standard library's hasher is not randomly seeded
+    struct FnvHasher(u64);
+
+    impl std::hash::Hasher for FnvHasher {
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    // Built on the same Hash implementation as hash table keys, so values that are equal? hash
+    // alike. Kept below 2^53 so the result is an exact integer
+    fn object_hash(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        use std::hash::{Hash, Hasher};
+
+        let (obj, bound) = match args {
+            [obj] => (obj, None),
+            [obj, bound] => (obj, Some(index_value(bound, "object-hash")?).filter(|bound| *bound > 0)),
+            _ => return Err("'object-hash' requires an object and an optional bound".into()),
+        };
+        if args.len() == 2 && bound.is_none() {
+            return Err("The bound for object-hash must be positive".into());
+        }
+
+        let mut hasher = FnvHasher(0xcbf29ce484222325);
+        obj.hash(&mut hasher);
+        let hash = hasher.finish() & ((1 << 53) - 1);
+        Ok(Expr::Number(bound.map_or(hash, |bound| hash % bound as u64) as f64))
+    }
+
+    fn circular_list(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.is_empty() {
+            return Err("'circular-list' requires at least 1 element".into());
+        }
+        Ok(Expr::CircularList(Arc::new(args.to_vec()), 0))
+    }
+
+    fn is_circular_list(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] => Ok(Expr::Bool(matches!(obj, Expr::CircularList(..)))),
+            _ => Err("'circular-list?' requires exactly 1 argument".into()),
+        }
+    }
+
+    // (string-replace s1 s2 start end): s1 with the characters from start to end swapped for s2
+    fn string_replace(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, replacement, range) = match args {
+            [Expr::Str(s), Expr::Str(replacement), range @ ..] if range.len() == 2 => (s, replacement, range),
+            _ => return Err("'string-replace' requires two strings, a start and an end".into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let (start, end) = range_arguments(range, chars.len(), "string-replace")?;
+        let mut result: String = chars[..start].iter().collect();
+        result.push_str(replacement);
+        result.extend(&chars[end..]);
+        Ok(Expr::Str(result))
+    }
+
+    fn substitution_arguments<'a>(args: &'a [Expr], name: &str) -> Result<(&'a str, &'a str, &'a str), LispError> {
+        match args {
+            [Expr::Str(_), Expr::Str(from), Expr::Str(_)] if from.is_empty() => {
+                Err(format!("'{}' cannot replace the empty string", name).into())
+            }
+            [Expr::Str(s), Expr::Str(from), Expr::Str(to)] => Ok((s, from, to)),
+            _ => Err(format!("'{}' requires a string, the text to replace and its replacement", name).into()),
+        }
+    }
+
+    fn string_substitute(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, from, to) = substitution_arguments(args, "string-substitute")?;
+        Ok(Expr::Str(s.replace(from, to)))
+    }
+
+    fn string_replace_first(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, from, to) = substitution_arguments(args, "string-replace-first")?;
+        Ok(Expr::Str(s.replacen(from, to, 1)))
+    }
+
+    // The whole file is read up front, so the port behaves like a string input port
+    fn open_input_file(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let path = path_argument(args, "open-input-file")?;
+        let text = std::fs::read_to_string(path).map_err(|e| file_error("read", path, e))?;
+        Ok(console_port(Port::StringInput { text: text.chars().collect(), position: 0 }))
+    }
+
+    // Reads every remaining datum from `port`, or current-input-port, leaving it at EOF
+    fn read_all(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let port = match args {
+            [] => &env.ports.input,
+            [port] => port,
+            _ => return Err("'read-all' takes an optional port".into()),
+        };
+
+        let text = match port {
+            Expr::Port(port) => match &mut *port.lock().unwrap() {
+                Port::StringInput { text, position } => {
+                    let rest: String = text[*position..].iter().collect();
+                    *position = text.len();
+                    rest
+                }
+                Port::Stdin => {
+                    let mut rest = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut rest)
+                        .map_err(|e| format!("Cannot read from stdin: {}", e))?;
+                    rest
+                }
+                Port::StringOutput(_) | Port::Stdout | Port::Stderr => {
+                    return Err("Cannot read-all from an output port".into())
+                }
+                Port::Closed => return Err("Cannot read-all from a closed port".into()),
+            },
+            _ => return Err("Invalid port argument for read-all".into()),
+        };
+
+        Ok(Expr::List(parse_all(&text)?))
+    }
+
+    fn read_all_from_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(Expr::List(parse_all(s)?)),
+            _ => Err("'read-all-from-string' requires exactly 1 string argument".into()),
+        }
+    }
+
+    // A dotted pair unless `rest` is a list, in which case `first` is put in front of it
+    fn cons(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [first, Expr::List(rest)] => {
+                let mut list = Vec::with_capacity(rest.len() + 1);
+                list.push(first.clone());
+                list.extend(rest.iter().cloned());
+                Ok(Expr::List(list))
+            }
+            [first, second] => Ok(make_pair(first.clone(), second.clone())),
+            _ => Err("'cons' requires exactly 2 arguments".into()),
+        }
+    }
+
+    const STDLIB: &str = include_str!("stdlib.lisp");
+
+    // Procedures written in Lisp document themselves with a docstring instead
+    const BUILTIN_DOCS: &[(&str, &str)] = &[
+        ("+", "(+ num ...) → num: Adds zero or more numbers."),
+        ("-", "(- num num ...) → num: Subtracts the rest from the first number, or negates a single one."),
+        ("*", "(* num ...) → num: Multiplies zero or more numbers."),
+        ("/", "(/ num num ...) → num: Divides the first number by the rest, or gives the reciprocal of a single one."),
+        ("=", "(= num num ...) → bool: Whether all the numbers are equal."),
+        ("<", "(< num num ...) → bool: Whether the numbers are strictly increasing."),
+        (">", "(> num num ...) → bool: Whether the numbers are strictly decreasing."),
+        ("<=", "(<= num num ...) → bool: Whether the numbers never decrease."),
+        (">=", "(>= num num ...) → bool: Whether the numbers never increase."),
+        ("not", "(not obj) → bool: #t when obj is #f, otherwise #f."),
+        ("car", "(car list) → obj: The first element of a list."),
+        ("cdr", "(cdr list) → list: Everything after the first element of a list."),
+        ("cons", "(cons obj list) → list: A list with obj in front, or a dotted pair when the second argument is not a list."),
+        ("list", "(list obj ...) → list: A list of the arguments."),
+        ("apply", "(apply proc obj ... list) → obj: Calls proc with the objects followed by the elements of list."),
+        ("list-length", "(list-length list) → num: The number of elements in a list."),
+        ("equal?", "(equal? a b) → bool: Whether two values have the same structure and contents."),
+        ("odd?", "(odd? int) → bool: Whether an integer is odd."),
+        ("even?", "(even? int) → bool: Whether an integer is even."),
+        ("square", "(square num) → num: The number multiplied by itself."),
+        ("values", "(values obj ...) → values: Returns several values at once."),
+        ("call-with-values", "(call-with-values producer consumer) → obj: Calls consumer with the values producer returns."),
+        ("reduce", "(reduce f initial list) → obj: Combines the elements left to right with (f element acc); initial if the list is empty."),
+        ("take", "(take list k) → list: The first k elements."),
+        ("drop", "(drop list k) → list: All but the first k elements."),
+        ("zip", "(zip list ...) → list: Lists of the elements at each position."),
+        ("count", "(count pred list ...) → num: How many elements satisfy pred."),
+        ("list-tabulate", "(list-tabulate n proc) → list: (proc 0) through (proc n-1)."),
+        ("list-index", "(list-index pred list ...) → num: The index of the first elements satisfying pred, or #f."),
+        ("delete", "(delete x list [=]) → list: The list without the elements equal to x."),
+        ("delete-duplicates", "(delete-duplicates list [=]) → list: The list with only the first occurrence of each element."),
+        ("lset-union", "(lset-union = list ...) → list: The elements found in any of the lists."),
+        ("lset-intersection", "(lset-intersection = list ...) → list: The elements of the first list found in all the others."),
+        ("lset-difference", "(lset-difference = list ...) → list: The elements of the first list found in none of the others."),
+        ("every", "(every pred list ...) → obj: Whether pred holds for every element."),
+        ("any", "(any pred list ...) → obj: Whether pred holds for some element."),
+        ("flatten", "(flatten [depth] list) → list: The elements of nested lists, spliced in up to depth levels."),
+        ("display", "(display obj [port]) → void: Writes obj for people to read, without quotes on strings."),
+        ("write", "(write obj [port]) → void: Writes obj so that read could read it back."),
+        ("newline", "(newline [port]) → void: Writes a line break."),
+        ("print", "(print obj) → obj: Writes obj and a line break, returning obj."),
+        ("pretty-print", "(pretty-print obj [width]) → void: Writes obj indented to fit the width."),
+        ("pp", "(pp obj) → void: Pretty-prints obj at the terminal width."),
+        ("write-to-string", "(write-to-string obj) → str: What write would output for obj."),
+        ("read-from-string", "(read-from-string str) → obj: The datum written in str."),
+        ("read-line", "(read-line [port]) → str: The next line of input, or the eof object."),
+        ("read-char", "(read-char [port]) → char: The next character of input, or the eof object."),
+        ("read-all", "(read-all [port]) → list: Every datum left in the input."),
+        ("open-input-string", "(open-input-string str) → port: An input port reading from str."),
+        ("open-output-string", "(open-output-string) → port: An output port collecting text in memory."),
+        ("get-output-string", "(get-output-string port) → str: The text written to a string output port."),
+        ("with-output-redirection", "(with-output-redirection port thunk) → obj: Calls thunk with current-output-port set to port."),
+        ("with-input-redirection", "(with-input-redirection port thunk) → obj: Calls thunk with current-input-port set to port."),
+        ("string-contains", "(string-contains str sub) → num: The index of the first occurrence of sub, or #f."),
+        ("string-ref", "(string-ref str k) → char: The character at index k."),
+        ("string-length", "(string-length str) → num: The number of characters in str."),
+        ("string-parse-number", "(string-parse-number str) → num: The number str is written as, or #f."),
+        ("string-append", "(string-append str ...) → str: The strings joined end to end."),
+        ("string-index", "(string-index str pred) → num: The index of the first character matching pred, or #f."),
+        ("string-count", "(string-count str pred) → num: The number of characters matching pred."),
+        ("string-every", "(string-every pred str) → bool: Whether every character of str matches pred."),
+        ("string-any", "(string-any pred str) → bool: Whether some character of str matches pred."),
+        ("string-split", "(string-split str [separator]) → list: The parts of str between separators."),
+        ("string-join", "(string-join list [separator]) → str: The strings joined with separator between them."),
+        ("string-replace", "(string-replace s1 s2 start end) → str: s1 with the characters from start to end replaced by s2."),
+        ("string-substitute", "(string-substitute str from to) → str: str with every occurrence of from replaced by to."),
+        ("string-copy", "(string-copy str [start [end]]) → str: A copy of str or of part of it."),
+        ("number->string", "(number->string num [radix]) → str: The digits of num in radix 2, 8, 10 or 16."),
+        ("char-upcase", "(char-upcase char) → char: The upper-case form of a character."),
+        ("char-downcase", "(char-downcase char) → char: The lower-case form of a character."),
+        ("vector", "(vector obj ...) → vector: A vector of the arguments."),
+        ("make-vector", "(make-vector k [fill]) → vector: A vector of k elements, all fill."),
+        ("vector-ref", "(vector-ref vector k) → obj: The element at index k."),
+        ("vector-set!", "(vector-set! vector k obj) → void: Replaces the element at index k."),
+        ("vector-length", "(vector-length vector) → num: The number of elements in a vector."),
+        ("vector-map", "(vector-map f vector ...) → vector: The results of f on the elements at each index."),
+        ("list->vector", "(list->vector list) → vector: A vector of a list's elements."),
+        ("vector->list", "(vector->list vector [start [end]]) → list: A list of a vector's elements."),
+        ("make-hash-table", "(make-hash-table) → table: An empty hash table keyed by equal?."),
+        ("object-hash", "(object-hash obj [bound]) → num: A hash of obj that agrees with equal?, below bound if given."),
+        ("hash-table-set!", "(hash-table-set! table key value) → void: Associates key with value."),
+        ("hash-table-ref", "(hash-table-ref table key [thunk]) → obj: The value for key, or the result of thunk when it is missing."),
+        ("hash-table-ref/default", "(hash-table-ref/default table key default) → obj: The value for key, or default."),
+        ("hash-table-delete!", "(hash-table-delete! table key) → void: Removes key from the table."),
+        ("hash-table-update!", "(hash-table-update! table key f [thunk]) → void: Replaces the value for key with (f value)."),
+        ("hash-table-walk", "(hash-table-walk table proc) → void: Calls (proc key value) for every entry."),
+        ("hash-table->alist", "(hash-table->alist table) → list: The entries as (key . value) pairs."),
+        ("alist->hash-table", "(alist->hash-table alist) → table: A hash table of an association list's entries."),
+        ("error", "(error message obj ...) → never: Raises an error object with the message and irritants."),
+        ("raise", "(raise obj) → never: Raises obj as an exception."),
+        ("with-exception-handler", "(with-exception-handler handler thunk) → obj: Calls thunk, handing anything it raises to handler."),
+        ("make-parameter", "(make-parameter value [converter]) → parameter: A parameter object for parameterize."),
+        ("eval", "(eval expr [environment]) → obj: Evaluates expr, in environment when one is given."),
+        ("sandbox-eval", "(sandbox-eval expr environment [options]) → obj: Evaluates expr on its own thread within time and memory limits."),
+        ("make-regexp", "(make-regexp pattern [flags]) → regexp: A compiled regular expression."),
+        ("regexp-match", "(regexp-match pattern str) → list: The match and its groups, or #f."),
+        ("random", "(random limit [source]) → num: A random number below limit, an integer when limit is one."),
+        ("memoize", "(memoize proc) → proc: proc with its results cached by argument."),
+        ("with-timing", "(with-timing label thunk) → obj: Calls thunk and reports how long it took."),
+        ("apropos", "(apropos pattern) → list: The names in scope that contain pattern."),
+        ("describe", "(describe 'name) → void: Shows the signature and documentation of a procedure."),
+        ("procedure-documentation", "(procedure-documentation proc) → str: The documentation of a procedure, or #f."),
+        ("procedure-arity", "(procedure-arity proc) → pair: The least and most arguments proc takes, with #f for no maximum."),
+        ("procedure-name", "(procedure-name proc) → str: The name proc was defined under, or #f for an anonymous one."),
+        ("set-procedure-name!", "(set-procedure-name! proc name) → void: Names a lambda for describe and error messages."),
+        ("procedure-arity-valid?", "(procedure-arity-valid? proc n) → bool: Whether proc can be called with n arguments."),
+        ("with-mutex", "(with-mutex mutex thunk) → obj: Calls thunk holding mutex, releasing it even if thunk raises."),
+        ("make-recursive-mutex", "(make-recursive-mutex) → mutex: A mutex the thread holding it can lock again."),
+        ("mutex-locked?", "(mutex-locked? mutex) → bool: Whether some thread holds mutex."),
+        ("symbol-gensym", "(symbol-gensym prefix) → sym: A fresh symbol that no other expression can name."),
+        ("reset-gensym-counter!", "(reset-gensym-counter!) → void: Numbers the next gensyms from 0 again."),
+        ("gensym?", "(gensym? obj) → bool: Whether obj is a symbol made by symbol-gensym."),
+        ("exit", "(exit [code]) → never: Runs the exit handlers and ends the program."),
+    ];
+
+    // (apply f a b ... lst) calls f with a, b, ... followed by the elements of lst
+    fn apply(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (procedure, rest) = match args {
+            [procedure, rest @ .., Expr::List(last)] => {
+                (procedure, rest.iter().chain(last.iter()).cloned().collect::<Vec<Expr>>())
+            }
+            _ => return Err("'apply' requires a procedure and a final list of arguments".into()),
+        };
+
+        apply_procedure(procedure, &rest, env)
+    }
+
+    fn char_predicate(args: &[Expr], name: &str, test: fn(char) -> bool) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Char(c)] => Ok(Expr::Bool(test(*c))),
+            _ => Err(format!("'{}' requires exactly 1 character", name).into()),
+        }
+    }
+
+    fn is_char_alphabetic(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        char_predicate(args, "char-alphabetic?", char::is_alphabetic)
+    }
+
+    fn is_char_numeric(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        char_predicate(args, "char-numeric?", char::is_numeric)
+    }
+
+    fn is_char_whitespace(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        char_predicate(args, "char-whitespace?", char::is_whitespace)
+    }
+
+    fn is_char_upper_case(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        char_predicate(args, "char-upper-case?", char::is_uppercase)
+    }
+
+    fn is_char_lower_case(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        char_predicate(args, "char-lower-case?", char::is_lowercase)
+    }
+
+    // A character whose case mapping is more than one character, like ß, is left as it is
+    fn convert_case(args: &[Expr], name: &str, convert: fn(char) -> Vec<char>) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Char(c)] => match convert(*c)[..] {
+                [converted] => Ok(Expr::Char(converted)),
+                _ => Ok(Expr::Char(*c)),
+            },
+            _ => Err(format!("'{}' requires exactly 1 character", name).into()),
+        }
+    }
+
+    fn char_upcase(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        convert_case(args, "char-upcase", |c| c.to_uppercase().collect())
+    }
+
+    fn char_downcase(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        convert_case(args, "char-downcase", |c| c.to_lowercase().collect())
+    }
+
+    // Every name visible in `env` that matches `pattern`: a substring, or a regexp object
+    fn apropos_matches(args: &[Expr], env: &Environment, name: &str, only_procedures: bool) -> Result<Expr, LispError> {
+        let matches: Box<dyn Fn(&str) -> bool> = match args {
+            [Expr::Str(pattern)] => {
+                let pattern = pattern.clone();
+                Box::new(move |candidate| candidate.contains(pattern.as_str()))
+            }
+            [Expr::Regexp(regex)] => {
+                let regex = regex.clone();
+                Box::new(move |candidate| regex.is_match(candidate))
+            }
+            _ => return Err(format!("'{}' requires a pattern string or regexp", name).into()),
+        };
+
+        let mut names: Vec<String> = env
+            .defined_symbols()
+            .into_iter()
+            .filter(|symbol| !only_procedures || env.lookup(SymbolId::intern(symbol)).is_some_and(|value| is_procedure(&value)))
+            .chain(env.functions.keys().cloned())
+            .chain(env.closures.keys().cloned())
+            .filter(|candidate| matches(candidate))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        Ok(Expr::List(names.iter().map(|name| Expr::Symbol(SymbolId::intern(name))).collect()))
+    }
+
+    fn apropos(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        apropos_matches(args, env, "apropos", false)
+    }
+
+    fn apropos_functions(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        apropos_matches(args, env, "apropos-functions", true)
+    }
+
+    // A Lisp procedure documents itself with a string before the rest of its body
+    fn lambda_docstring(lambda: &Lambda) -> Option<&str> {
+        match &lambda.body[..] {
+            [Expr::Str(doc), _, ..] => Some(doc),
+            _ => None,
+        }
+    }
+
+    fn lambda_signature(name: &str, params: &Expr) -> String {
+        match params {
+            Expr::List(params) if params.is_empty() => format!("({})", name),
+            Expr::List(params) => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                format!("({} {})", name, params.join(" "))
+            }
+            rest => format!("({} . {})", name, rest),
+        }
+    }
+
+    // A symbol is looked up the way a call would find it, with bindings in scope ahead of natives
+    fn described_procedure(args: &[Expr], env: &Environment, name: &str) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Symbol(symbol)] => match env.lookup(*symbol) {
+                Some(value) => Ok(value),
+                None if env.has_native(symbol.as_str()) => Ok(Expr::Builtin(symbol.to_string())),
+                None => Err(format!("Undefined symbol: {}", symbol).into()),
+            },
+            [value] => Ok(value.clone()),
+            _ => Err(format!("'{}' requires exactly 1 argument", name).into()),
+        }
+    }
+
+    fn procedure_documentation(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let doc = match described_procedure(args, env, "procedure-documentation")? {
+            Expr::Builtin(name) => env.docs.get(&name).map(|doc| doc.to_string()),
+            Expr::Lambda(lambda) => lambda_docstring(&lambda).map(str::to_string),
+            other if is_procedure(&other) => None,
+            other => return Err(format!("Not a procedure: {}", other).into()),
+        };
+
+        Ok(doc.map_or(Expr::Bool(false), Expr::Str))
+    }
+
+    fn describe(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let description = match described_procedure(args, env, "describe")? {
+            Expr::Builtin(name) => match env.docs.get(&name) {
+                Some(doc) => doc.to_string(),
+                None => format!("({} ...): A built-in procedure without documentation.", name),
+            },
+            Expr::Lambda(lambda) => {
+                let name = lambda.name().map_or("lambda", |name| name.as_str());
+                let signature = lambda_signature(name, &lambda.params);
+                match lambda_docstring(&lambda) {
+                    Some(doc) => format!("{}: {}", signature, doc),
+                    None => signature,
+                }
+            }
+            other => match &args[0] {
+                Expr::Symbol(symbol) => format!("{} is bound to {}", symbol, other),
+                _ => format!("{} is not a procedure", other),
+            },
+        };
+
+        write_to_port(&format!("{}\n", description), None, "describe", env)
+    }
+
+    // How many arguments a procedure accepts; without a maximum it takes any number from `min` up
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct ArityInfo {
+        min: usize,
+        max: Option<usize>,
+    }
+
+    impl ArityInfo {
+        const ANY: ArityInfo = ArityInfo { min: 0, max: None };
+
+        fn of_formals(formals: &Expr) -> ArityInfo {
+            match formals {
+                Expr::List(names) => match names.iter().position(|name| *name == Expr::Symbol(".".into())) {
+                    Some(dot) => ArityInfo { min: dot, max: None },
+                    None => ArityInfo { min: names.len(), max: Some(names.len()) },
+                },
+                _ => ArityInfo::ANY,
+            }
+        }
+
+        // Read off a documented signature such as "(name a b [c [d]] e ...)", where bracketed
+        // parameters are optional and the one before "..." repeats zero or more times
+        fn of_signature(doc: &str) -> ArityInfo {
+            let signature = doc.split(" → ").next().unwrap_or(doc).trim_start_matches('(').trim_end_matches(')');
+            let mut arity = ArityInfo { min: 0, max: Some(0) };
+            let mut depth = 0;
+            for param in signature.split_whitespace().skip(1) {
+                if param == "..." {
+                    arity.max = None;
+                    if depth == 0 {
+                        arity.min = arity.min.saturating_sub(1);
+                    }
+                    continue;
+                }
+                if depth == 0 && !param.starts_with('[') {
+                    arity.min += 1;
+                }
+                arity.max = arity.max.map(|max| max + 1);
+                depth += param.matches('[').count();
+                depth -= param.matches(']').count().min(depth);
+            }
+            arity
+        }
+
+        fn accepts(&self, count: usize) -> bool {
+            count >= self.min && self.max.is_none_or(|max| count <= max)
+        }
+    }
+
+    impl fmt::Display for ArityInfo {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.max {
+                Some(max) if max == self.min => write!(f, "{}", max),
+                Some(max) => write!(f, "{} to {}", self.min, max),
+                None => write!(f, "at least {}", self.min),
+            }
+        }
+    }
+
+    // Natives check their own arguments, so only those with a documented signature have a known
+    // arity; the rest report that they take any number
+    fn arity_of(procedure: &Expr, env: &Environment, name: &str) -> Result<ArityInfo, LispError> {
+        match procedure {
+            Expr::Builtin(builtin) => Ok(env.docs.get(builtin).map_or(ArityInfo::ANY, |doc| ArityInfo::of_signature(doc))),
+            Expr::Lambda(lambda) => Ok(ArityInfo::of_formals(&lambda.params)),
+            Expr::Memoized(memoized) => arity_of(&memoized.procedure, env, name),
+            Expr::Parameter(_) => Ok(ArityInfo { min: 0, max: Some(1) }),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn procedure_arity(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [procedure] => {
+                let arity = arity_of(procedure, env, "procedure-arity")?;
+                let max = arity.max.map_or(Expr::Bool(false), |max| Expr::Number(max as f64));
+                Ok(make_pair(Expr::Number(arity.min as f64), max))
+            }
+            _ => Err("'procedure-arity' requires exactly 1 procedure".into()),
+        }
+    }
+
+    fn procedure_arity_valid(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [procedure, count] => {
+                let count = index_value(count, "procedure-arity-valid?")?;
+                Ok(Expr::Bool(arity_of(procedure, env, "procedure-arity-valid?")?.accepts(count)))
+            }
+            _ => Err("'procedure-arity-valid?' requires a procedure and an argument count".into()),
+        }
+    }
+
+    fn name_of(procedure: &Expr) -> Option<String> {
+        match procedure {
+            Expr::Lambda(lambda) => lambda.name().map(|name| name.to_string()),
+            Expr::Builtin(name) => Some(name.clone()),
+            Expr::Memoized(memoized) => name_of(&memoized.procedure),
+            _ => None,
+        }
+    }
+
+    fn procedure_name(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [procedure] if is_procedure(procedure) => Ok(name_of(procedure).map_or(Expr::Bool(false), Expr::Str)),
+            [_] => Err("Invalid argument type for procedure-name".into()),
+            _ => Err("'procedure-name' requires exactly 1 procedure".into()),
+        }
+    }
+
+    fn set_procedure_name(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let name = match args {
+            [_, Expr::Str(name)] => SymbolId::intern(name),
+            [_, Expr::Symbol(name)] => *name,
+            _ => return Err("'set-procedure-name!' requires a procedure and a name".into()),
+        };
+        match &args[0] {
+            Expr::Lambda(lambda) => {
+                *lambda.name.lock().unwrap() = Some(name);
+                Ok(Expr::Void)
+            }
+            _ => Err("Only a lambda can be renamed with set-procedure-name!".into()),
+        }
+    }
+
+    static GENSYMS: AtomicUsize = AtomicUsize::new(0);
+
+    // A gensym's name starts with a NUL, which the reader never produces, so printing one and
+    // reading it back gives a different symbol
+    fn symbol_gensym(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let prefix = match args {
+            [Expr::Str(prefix)] => prefix.as_str(),
+            [Expr::Symbol(prefix)] => prefix.as_str(),
+            _ => return Err("'symbol-gensym' requires a prefix string".into()),
+        };
+        let n = GENSYMS.fetch_add(1, Ordering::Relaxed);
+        Ok(Expr::Symbol(SymbolId::intern(&format!("\0{}#{}", prefix, n))))
+    }
+
+    fn reset_gensym_counter(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'reset-gensym-counter!' takes no arguments".into());
+        }
+        GENSYMS.store(0, Ordering::Relaxed);
+        Ok(Expr::Void)
+    }
+
+    fn is_gensym(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Symbol(s)] => Ok(Expr::Bool(s.as_str().starts_with('\0'))),
+            [_] => Ok(Expr::Bool(false)),
+            _ => Err("'gensym?' requires exactly 1 argument".into()),
+        }
+    }
+
+    fn char_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Char(c)] => Ok(Expr::Str(c.to_string())),
+            _ => Err("'char->string' requires exactly 1 character".into()),
+        }
+    }
+
+    fn chars_to_string(items: &[Expr], name: &str) -> Result<Expr, LispError> {
+        let mut s = String::with_capacity(items.len());
+        for item in items {
+            match item {
+                Expr::Char(c) => s.push(*c),
+                _ => return Err(format!("Invalid element for {}: {}", name, item).into()),
+            }
+        }
+        Ok(Expr::Str(s))
+    }
+
+    fn string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        chars_to_string(args, "string")
+    }
+
+    fn list_to_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::List(items)] => chars_to_string(items, "list->string"),
+            _ => Err("'list->string' requires a list of characters".into()),
+        }
+    }
+
+    fn string_to_list(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (s, range) = match args.split_first() {
+            Some((Expr::Str(s), range)) => (s, range),
+            _ => return Err("'string->list' requires a string and an optional range".into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let (start, end) = range_arguments(range, chars.len(), "string->list")?;
+        Ok(Expr::List(chars[start..end].iter().map(|c| Expr::Char(*c)).collect()))
+    }
+
+    // Without a fill character the string is made of spaces
+    fn make_string(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (len, fill) = match args {
+            [len] => (index_value(len, "make-string")?, ' '),
+            [len, Expr::Char(fill)] => (index_value(len, "make-string")?, *fill),
+            _ => return Err("'make-string' requires a length and an optional fill character".into()),
+        };
+        Ok(Expr::Str(std::iter::repeat_n(fill, len).collect()))
+    }
+
+    fn weak_cons(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [car, cdr] => Ok(Expr::WeakPair(Arc::new(WeakPair { car: WeakValue::new(car), cdr: cdr.clone() }))),
+            _ => Err("'weak-cons' requires exactly 2 arguments".into()),
+        }
+    }
+
+    fn weak_pair_argument<'a>(args: &'a [Expr], name: &str) -> Result<&'a WeakPair, LispError> {
+        match args {
+            [Expr::WeakPair(pair)] => Ok(pair),
+            [_] => Err(format!("Invalid argument type for {}", name).into()),
+            _ => Err(format!("'{}' requires exactly 1 weak pair", name).into()),
+        }
+    }
+
+    // The car of a weak pair whose object has been collected reads as #f
+    fn weak_car(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(weak_pair_argument(args, "weak-car")?.car.upgrade().unwrap_or(Expr::Bool(false)))
+    }
+
+    fn weak_cdr(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(weak_pair_argument(args, "weak-cdr")?.cdr.clone())
+    }
+
+    fn weak_pair_car_p(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::Bool(weak_pair_argument(args, "weak-pair/car?")?.car.upgrade().is_some()))
+    }
+
+    fn weak_pair_p(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [obj] => Ok(Expr::Bool(matches!(obj, Expr::WeakPair(_)))),
+            _ => Err("'weak-pair?' requires exactly 1 argument".into()),
+        }
+    }
+
+    impl Environment {
+        pub fn new() -> Self {
+            let mut env = Environment::default();
+            env.docs.extend(BUILTIN_DOCS.iter().map(|(name, doc)| (name.to_string(), *doc)));
+            env.functions.insert("+".to_string(), add);
+            env.functions.insert("-".to_string(), subtract);
+            env.functions.insert("*".to_string(), multiply);
+            env.functions.insert("/".to_string(), divide);
+            env.functions.insert("=".to_string(), equal);
+            env.functions.insert("<".to_string(), less_than);
+            env.functions.insert(">".to_string(), greater_than);
+            env.functions.insert("<=".to_string(), less_or_equal);
+            env.functions.insert(">=".to_string(), greater_or_equal);
+            env.functions.insert("not".to_string(), not);
+            env.functions.insert("car".to_string(), car);
+            env.functions.insert("cdr".to_string(), cdr);
+            env.functions.insert("list-length".to_string(), list_length);
+            env.functions.insert("list-sum".to_string(), list_sum);
+            env.functions.insert("define".to_string(), define);
+            env.functions.insert("print".to_string(), print);
+            env.functions.insert("features".to_string(), features);
+            env.functions.insert("values".to_string(), values);
+            env.functions.insert("call-with-values".to_string(), call_with_values);
+            env.functions.insert("list".to_string(), list);
+            env.functions.insert("with-values".to_string(), with_values);
+            env.functions.insert("floor/".to_string(), floor_division);
+            env.functions.insert("reduce".to_string(), reduce);
+            env.functions.insert("reduce-right".to_string(), reduce_right);
+            env.functions.insert("every".to_string(), every);
+            env.functions.insert("any".to_string(), any);
+            env.functions.insert("partition".to_string(), partition);
+            env.functions.insert("span".to_string(), span);
+            env.functions.insert("break".to_string(), break_list);
+            env.functions.insert("take".to_string(), take);
+            env.functions.insert("drop".to_string(), drop);
+            env.functions.insert("take-while".to_string(), take_while);
+            env.functions.insert("drop-while".to_string(), drop_while);
+            env.functions.insert("zip".to_string(), zip);
+            env.functions.insert("unzip1".to_string(), unzip1);
+            env.functions.insert("unzip2".to_string(), unzip2);
+            env.functions.insert("unzip3".to_string(), unzip3);
+            env.functions.insert("unzip4".to_string(), unzip4);
+            env.functions.insert("unzip5".to_string(), unzip5);
+            env.functions.insert("count".to_string(), count);
+            env.functions.insert("list-tabulate".to_string(), list_tabulate);
+            env.functions.insert("list-index".to_string(), list_index);
+            env.functions.insert("delete".to_string(), delete);
+            env.functions.insert("delete-duplicates".to_string(), delete_duplicates);
+            env.functions.insert("lset-union".to_string(), lset_union);
+            env.functions.insert("lset-intersection".to_string(), lset_intersection);
+            env.functions.insert("lset-difference".to_string(), lset_difference);
+            env.functions.insert("flatten".to_string(), flatten);
+            env.functions.insert("deep-map".to_string(), deep_map);
+            env.functions.insert("string-contains".to_string(), string_contains);
+            env.functions.insert("string-contains-ci".to_string(), string_contains_ci);
+            env.functions.insert("string-prefix?".to_string(), string_prefix);
+            env.functions.insert("string-prefix-ci?".to_string(), string_prefix_ci);
+            env.functions.insert("string-suffix?".to_string(), string_suffix);
+            env.functions.insert("string-suffix-ci?".to_string(), string_suffix_ci);
+            env.functions.insert("string-split".to_string(), string_split);
+            env.functions.insert("string-join".to_string(), string_join);
+            env.functions.insert("string-trim".to_string(), string_trim);
+            env.functions.insert("string-trim-right".to_string(), string_trim_right);
+            env.functions.insert("string-trim-both".to_string(), string_trim_both);
+            env.functions.insert("string-pad".to_string(), string_pad);
+            env.functions.insert("string-pad-right".to_string(), string_pad_right);
+            env.functions.insert("string-length".to_string(), string_length);
+            env.functions.insert("string-append".to_string(), string_append);
+            env.functions.insert("write".to_string(), write);
+            env.functions.insert("display".to_string(), display);
+            env.functions.insert("newline".to_string(), newline);
+            env.functions.insert("open-output-string".to_string(), open_output_string);
+            env.functions.insert("get-output-string".to_string(), get_output_string);
+            env.functions.insert("close-port".to_string(), close_port);
+            env.functions.insert("call-with-string-output-port".to_string(), call_with_string_output_port);
+            env.functions.insert("with-string-output-port".to_string(), call_with_string_output_port);
+            env.functions.insert("pretty-print".to_string(), pretty_print_builtin);
+            env.functions.insert("write-to-string".to_string(), write_to_string);
+            env.functions.insert("read-from-string".to_string(), read_from_string);
+            env.functions.insert("object->string".to_string(), write_to_string);
+            env.functions.insert("repr".to_string(), write_to_string);
+            env.functions.insert("string->object".to_string(), read_from_string);
+            env.functions.insert("odd?".to_string(), is_odd);
+            env.functions.insert("even?".to_string(), is_even);
+            env.functions.insert("json->lisp".to_string(), json_to_lisp);
+            env.functions.insert("lisp->json".to_string(), lisp_to_json);
+            env.functions.insert("http-get".to_string(), http_get);
+            env.functions.insert("http-post".to_string(), http_post);
+            env.functions.insert("file-exists?".to_string(), file_exists);
+            env.functions.insert("delete-file".to_string(), delete_file);
+            env.functions.insert("rename-file".to_string(), rename_file);
+            env.functions.insert("directory-files".to_string(), directory_files);
+            env.functions.insert("make-directory".to_string(), make_directory);
+            env.functions.insert("file-size".to_string(), file_size);
+            env.functions.insert("file-modification-time".to_string(), file_modification_time);
+            env.functions.insert("command-line".to_string(), command_line);
+            env.functions.insert("get-environment-variable".to_string(), get_environment_variable);
+            env.functions.insert("get-environment-variables".to_string(), get_environment_variables);
+            env.functions.insert("exit".to_string(), exit);
+            env.functions.insert("emergency-exit".to_string(), emergency_exit);
+            env.functions.insert("at-exit".to_string(), at_exit);
+            env.functions.insert("current-time".to_string(), current_time);
+            env.functions.insert("time-difference".to_string(), time_difference);
+            env.functions.insert("time->seconds".to_string(), time_to_seconds);
+            env.functions.insert("date->time".to_string(), date_to_time);
+            env.functions.insert("time->date".to_string(), time_to_date);
+            env.functions.insert("random".to_string(), random);
+            env.functions.insert("random-real".to_string(), random_real);
+            env.functions.insert("make-random-source".to_string(), make_random_source);
+            env.functions.insert("random-source-randomize!".to_string(), random_source_randomize);
+            env.functions.insert("random-source-make-integers".to_string(), random_source_make_integers);
+            env.functions.insert("make-regexp".to_string(), make_regexp);
+            env.functions.insert("regexp?".to_string(), is_regexp);
+            env.functions.insert("string-contains-regexp".to_string(), string_contains_regexp);
+            env.functions.insert("regexp-match".to_string(), regexp_match);
+            env.functions.insert("regexp-replace".to_string(), regexp_replace);
+            env.functions.insert("regexp-replace-all".to_string(), regexp_replace_all);
+            env.functions.insert("gc".to_string(), gc);
+            env.functions.insert("gc-stats".to_string(), gc_stats);
+            env.functions.insert("make-thread".to_string(), make_thread);
+            env.functions.insert("thread-start!".to_string(), thread_start);
+            env.functions.insert("thread-join!".to_string(), thread_join);
+            env.functions.insert("thread-sleep!".to_string(), thread_sleep);
+            env.functions.insert("thread?".to_string(), is_thread);
+            env.functions.insert("make-mutex".to_string(), make_mutex);
+            env.functions.insert("mutex-lock!".to_string(), mutex_lock);
+            env.functions.insert("mutex-unlock!".to_string(), mutex_unlock);
+            env.functions.insert("make-recursive-mutex".to_string(), make_recursive_mutex);
+            env.functions.insert("mutex-locked?".to_string(), mutex_locked_p);
+            env.functions.insert("with-mutex".to_string(), with_mutex);
+            env.functions.insert("mutex?".to_string(), is_mutex);
+            env.functions.insert("make-coroutine".to_string(), make_coroutine);
+            env.functions.insert("resume".to_string(), resume);
+            env.functions.insert("yield".to_string(), yield_value);
+            env.functions.insert("coroutine?".to_string(), is_coroutine);
+            env.functions.insert("macroexpand-1".to_string(), macroexpand_1);
+            env.functions.insert("macroexpand".to_string(), macroexpand);
+            env.functions.insert("assert-equal?".to_string(), assert_equal);
+            env.functions.insert("assert-raises?".to_string(), assert_raises);
+            env.functions.insert("equal?".to_string(), is_equal);
+            env.functions.insert("test-begin".to_string(), test_begin);
+            env.functions.insert("test-end".to_string(), test_end);
+            env.functions.insert("test-runner-current".to_string(), test_runner_current);
+            env.functions.insert("profile".to_string(), profile);
+            env.functions.insert("profile-reset!".to_string(), profile_reset);
+            env.functions.insert("memoize".to_string(), memoize);
+            env.functions.insert("memoized?".to_string(), is_memoized);
+            env.functions.insert("memoize-clear!".to_string(), memoize_clear);
+            env.functions.insert("void".to_string(), void);
+            env.functions.insert("void?".to_string(), is_void);
+            env.functions.insert("make-parameter".to_string(), make_parameter);
+            env.functions.insert("vector".to_string(), vector);
+            env.functions.insert("make-vector".to_string(), make_vector);
+            env.functions.insert("vector?".to_string(), is_vector);
+            env.functions.insert("vector-length".to_string(), vector_length);
+            env.functions.insert("vector-ref".to_string(), vector_ref);
+            env.functions.insert("vector-set!".to_string(), vector_set);
+            env.functions.insert("vector-map".to_string(), vector_map);
+            env.functions.insert("vector-for-each".to_string(), vector_for_each);
+            env.functions.insert("vector-for-each-indexed".to_string(), vector_for_each_indexed);
+            env.functions.insert("string-map".to_string(), string_map);
+            env.functions.insert("string-for-each".to_string(), string_for_each);
+            env.functions.insert("string-copy".to_string(), string_copy);
+            env.functions.insert("vector-copy!".to_string(), vector_copy_into);
+            env.functions.insert("list->vector".to_string(), list_to_vector);
+            env.functions.insert("vector->list".to_string(), vector_to_list);
+            env.functions.insert("vector-copy".to_string(), vector_copy);
+            env.functions.insert("string->vector".to_string(), string_to_vector);
+            env.functions.insert("vector->string".to_string(), vector_to_string);
+            env.functions.insert("bytevector".to_string(), bytevector);
+            env.functions.insert("make-bytevector".to_string(), make_bytevector);
+            env.functions.insert("bytevector?".to_string(), is_bytevector);
+            env.functions.insert("bytevector-length".to_string(), bytevector_length);
+            env.functions.insert("bytevector-u8-ref".to_string(), bytevector_u8_ref);
+            env.functions.insert("bytevector-u8-set!".to_string(), bytevector_u8_set);
+            env.functions.insert("bytevector-s8-ref".to_string(), bytevector_s8_ref);
+            env.functions.insert("bytevector-u16-ref".to_string(), bytevector_u16_ref);
+            env.functions.insert("bytevector-u16-set!".to_string(), bytevector_u16_set);
+            env.functions.insert("bytevector-u32-ref".to_string(), bytevector_u32_ref);
+            env.functions.insert("bytevector-u32-set!".to_string(), bytevector_u32_set);
+            env.functions.insert("bytevector-u64-ref".to_string(), bytevector_u64_ref);
+            env.functions.insert("bytevector-u64-set!".to_string(), bytevector_u64_set);
+            env.functions.insert("bytevector-copy".to_string(), bytevector_copy);
+            env.functions.insert("bytevector-copy!".to_string(), bytevector_copy_into);
+            env.functions.insert("bytevector-append".to_string(), bytevector_append);
+            env.functions.insert("utf8->string".to_string(), utf8_to_string);
+            env.functions.insert("string->utf8".to_string(), string_to_utf8);
+            env.functions.insert("with-exception-handler".to_string(), with_exception_handler);
+            env.functions.insert("raise".to_string(), raise);
+            env.functions.insert("raise-continuable".to_string(), raise_continuable);
+            env.functions.insert("error".to_string(), error);
+            env.functions.insert("error-object?".to_string(), is_error_object);
+            env.functions.insert("error-object-message".to_string(), error_object_message);
+            env.functions.insert("error-object-irritants".to_string(), error_object_irritants);
+            env.functions.insert("read-error?".to_string(), is_read_error);
+            env.functions.insert("file-error?".to_string(), is_file_error);
+            env.functions.insert("condition/report-string".to_string(), condition_report_string);
+            env.functions.insert("exact-integer?".to_string(), is_exact_integer);
+            env.functions.insert("integer?".to_string(), is_integer);
+            env.functions.insert("rational?".to_string(), is_rational);
+            env.functions.insert("real?".to_string(), is_real);
+            env.functions.insert("complex?".to_string(), is_complex);
+            env.functions.insert("number?".to_string(), is_number);
+            env.functions.insert("square".to_string(), square);
+            env.functions.insert("exact-integer-sqrt".to_string(), exact_integer_sqrt);
+            env.functions.insert("number->string/pretty".to_string(), number_to_string_pretty);
+            env.functions.insert("number->string/scientific".to_string(), number_to_string_scientific);
+            env.functions.insert("number->string/fixed".to_string(), number_to_string_fixed);
+            env.functions.insert("char-set".to_string(), char_set);
+            env.functions.insert("string->char-set".to_string(), string_to_char_set);
+            env.functions.insert("char-set?".to_string(), is_char_set);
+            env.functions.insert("char-set-member?".to_string(), char_set_member);
+            env.functions.insert("char-set-contains?".to_string(), char_set_member);
+            env.functions.insert("char-set-size".to_string(), char_set_size);
+            env.functions.insert("char-set-union".to_string(), char_set_union);
+            env.functions.insert("char-set-intersection".to_string(), char_set_intersection);
+            env.functions.insert("char-set-difference".to_string(), char_set_difference);
+            env.functions.insert("char-set-complement".to_string(), char_set_complement);
+            env.functions.insert("string-filter".to_string(), string_filter);
+            env.functions.insert("procedure-source".to_string(), procedure_source);
+            env.functions.insert("source-location".to_string(), source_location);
+            env.functions.insert("eval".to_string(), eval_procedure);
+            env.functions.insert("interaction-environment".to_string(), interaction_environment);
+            env.functions.insert("scheme-report-environment".to_string(), scheme_report_environment);
+            env.functions.insert("null-environment".to_string(), null_environment);
+            env.functions.insert("sandbox-eval".to_string(), sandbox_eval);
+            env.functions.insert("open-input-string".to_string(), open_input_string);
+            env.functions.insert("read-line".to_string(), read_line);
+            env.functions.insert("read-char".to_string(), read_char);
+            env.functions.insert("peek-char".to_string(), peek_char);
+            env.functions.insert("eof-object".to_string(), eof_object);
+            env.functions.insert("eof-object?".to_string(), is_eof_object);
+            env.functions.insert("current-input-port".to_string(), current_input_port);
+            env.functions.insert("current-output-port".to_string(), current_output_port);
+            env.functions.insert("current-error-port".to_string(), current_error_port);
+            env.functions.insert("with-input-redirection".to_string(), with_input_redirection);
+            env.functions.insert("with-output-redirection".to_string(), with_output_redirection);
+            env.functions.insert("with-error-redirection".to_string(), with_error_redirection);
+            env.functions.insert("string-number-radix".to_string(), string_number_radix);
+            env.functions.insert("string-parse-number".to_string(), string_parse_number);
+            env.functions.insert("string-ref".to_string(), string_ref);
+            env.functions.insert("string-contains?".to_string(), string_contains_p);
+            env.functions.insert("string-index".to_string(), string_index);
+            env.functions.insert("string-index-right".to_string(), string_index_right);
+            env.functions.insert("string-count".to_string(), string_count);
+            env.functions.insert("string-every".to_string(), string_every);
+            env.functions.insert("string-any".to_string(), string_any);
+            env.functions.insert("make-hash-table".to_string(), make_hash_table);
+            env.functions.insert("hash-table?".to_string(), is_hash_table);
+            env.functions.insert("hash-table-set!".to_string(), hash_table_set);
+            env.functions.insert("hash-table-ref".to_string(), hash_table_ref);
+            env.functions.insert("hash-table-ref/default".to_string(), hash_table_ref_default);
+            env.functions.insert("hash-table-delete!".to_string(), hash_table_delete);
+            env.functions.insert("hash-table-contains?".to_string(), hash_table_contains);
+            env.functions.insert("hash-table-exists?".to_string(), hash_table_contains);
+            env.functions.insert("hash-table-size".to_string(), hash_table_size);
+            env.functions.insert("hash-table-keys".to_string(), hash_table_keys);
+            env.functions.insert("hash-table-values".to_string(), hash_table_values);
+            env.functions.insert("hash-table->alist".to_string(), hash_table_to_alist);
+            env.functions.insert("hash-table-walk".to_string(), hash_table_walk);
+            env.functions.insert("hash-table-merge!".to_string(), hash_table_merge);
+            env.functions.insert("hash-table-copy".to_string(), hash_table_copy);
+            env.functions.insert("alist->hash-table".to_string(), alist_to_hash_table);
+            env.functions.insert("hash-table-update!".to_string(), hash_table_update);
+            env.functions.insert("hash-table-update!/default".to_string(), hash_table_update_default);
+            env.functions.insert("circular-list".to_string(), circular_list);
+            env.functions.insert("circular-list?".to_string(), is_circular_list);
+            env.functions.insert("string-replace".to_string(), string_replace);
+            env.functions.insert("string-substitute".to_string(), string_substitute);
+            env.functions.insert("string-replace-first".to_string(), string_replace_first);
+            env.functions.insert("open-input-file".to_string(), open_input_file);
+            env.functions.insert("read-all".to_string(), read_all);
+            env.functions.insert("read-all-from-string".to_string(), read_all_from_string);
+            env.functions.insert("cons".to_string(), cons);
+            env.functions.insert("apply".to_string(), apply);
+            env.functions.insert("char-alphabetic?".to_string(), is_char_alphabetic);
+            env.functions.insert("char-numeric?".to_string(), is_char_numeric);
+            env.functions.insert("char-whitespace?".to_string(), is_char_whitespace);
+            env.functions.insert("char-upper-case?".to_string(), is_char_upper_case);
+            env.functions.insert("char-lower-case?".to_string(), is_char_lower_case);
+            env.functions.insert("char-upcase".to_string(), char_upcase);
+            env.functions.insert("char-downcase".to_string(), char_downcase);
+            env.functions.insert("with-timing".to_string(), with_timing);
+            env.functions.insert("timing-report".to_string(), timing_report);
+            env.functions.insert("reset-timings!".to_string(), reset_timings);
+            env.functions.insert("pretty-print-to-string".to_string(), pretty_print_to_string);
+            env.functions.insert("pp->string".to_string(), pretty_print_to_string);
+            env.functions.insert("pp".to_string(), pp);
+            env.functions.insert("number->string".to_string(), number_to_string);
+            env.functions.insert("number->string/hex".to_string(), number_to_string_hex);
+            env.functions.insert("number->string/bin".to_string(), number_to_string_bin);
+            env.functions.insert("number->string/oct".to_string(), number_to_string_oct);
+            env.functions.insert("hex->number".to_string(), hex_to_number);
+            env.functions.insert("bin->number".to_string(), bin_to_number);
+            env.functions.insert("oct->number".to_string(), oct_to_number);
+            env.functions.insert("list->string/join".to_string(), list_to_string_join);
+            env.functions.insert("string->words".to_string(), string_to_words);
+            env.functions.insert("string->lines".to_string(), string_to_lines);
+            env.functions.insert("apropos".to_string(), apropos);
+            env.functions.insert("apropos-functions".to_string(), apropos_functions);
+            env.functions.insert("describe".to_string(), describe);
+            env.functions.insert("procedure-documentation".to_string(), procedure_documentation);
+            env.functions.insert("abs".to_string(), abs);
+            env.functions.insert("sqrt".to_string(), sqrt);
+            env.functions.insert("char->string".to_string(), char_to_string);
+            env.functions.insert("string".to_string(), string);
+            env.functions.insert("list->string".to_string(), list_to_string);
+            env.functions.insert("string->list".to_string(), string_to_list);
+            env.functions.insert("make-string".to_string(), make_string);
+            env.functions.insert("vector-append".to_string(), vector_append);
+            env.functions.insert("subvector".to_string(), subvector);
+            env.functions.insert("vector-reverse".to_string(), vector_reverse);
+            env.functions.insert("vector-reverse!".to_string(), vector_reverse_in_place);
+            env.functions.insert("make-weak-hash-table".to_string(), make_weak_hash_table);
+            env.functions.insert("weak-cons".to_string(), weak_cons);
+            env.functions.insert("weak-car".to_string(), weak_car);
+            env.functions.insert("weak-cdr".to_string(), weak_cdr);
+            env.functions.insert("weak-pair/car?".to_string(), weak_pair_car_p);
+            env.functions.insert("weak-pair?".to_string(), weak_pair_p);
+            env.functions.insert("object-hash".to_string(), object_hash);
+            env.functions.insert("procedure-arity".to_string(), procedure_arity);
+            env.functions.insert("procedure-arity-valid?".to_string(), procedure_arity_valid);
+            env.functions.insert("procedure-name".to_string(), procedure_name);
+            env.functions.insert("set-procedure-name!".to_string(), set_procedure_name);
+            env.functions.insert("symbol-gensym".to_string(), symbol_gensym);
+            env.functions.insert("reset-gensym-counter!".to_string(), reset_gensym_counter);
+            env.functions.insert("gensym?".to_string(), is_gensym);
+            for (name, set) in STANDARD_CHAR_SETS.iter() {
+                env.define(SymbolId::intern(name), Expr::CharSet(set.clone()));
+            }
+            env.add_feature("lisp-interpreter");
+            env.command_line = std::env::args().collect();
+            *env.random_source.lock().unwrap() = RandomState::randomized();
+            eval_all_str(STDLIB, &mut env).expect("the standard library failed to load");
+            env
+        }
+
+        pub fn define_native(&mut self, name: &str, f: Function) {
+            self.closures.remove(name);
+            self.functions.insert(name.to_string(), f);
+        }
+
+        pub fn define_native_closure(&mut self, name: &str, f: Box<NativeClosure>) {
+            self.functions.remove(name);
+            self.closures.insert(name.to_string(), Arc::from(f));
+        }
+
+        // A child environment: its definitions stay local while `set!` reaches the parent bindings
+        pub fn fork(&self) -> Environment {
+            self.with_scope(Arc::new(Mutex::new(Scope::child(&self.scope))), Natives::All)
+        }
+
+        // A fresh evaluation context over `scope` that shares this one's process-wide state
+        fn with_scope(&self, scope: Arc<Mutex<Scope>>, natives: Natives) -> Environment {
+            let (functions, closures) = match natives {
+                Natives::All => (self.functions.clone(), self.closures.clone()),
+                Natives::Standard => (
+                    self.functions
+                        .iter()
+                        .filter(|(name, _)| STANDARD_PROCEDURES.contains(&name.as_str()))
+                        .map(|(name, f)| (name.clone(), *f))
+                        .collect(),
+                    HashMap::new(),
+                ),
+                Natives::None => (HashMap::new(), HashMap::new()),
+            };
+
+            Environment {
+                scope,
+                heap: Heap::default(),
+                weak_tables: self.weak_tables.clone(),
+                functions,
+                closures,
+                features: self.features.clone(),
+                command_line: self.command_line.clone(),
+                exit_handlers: self.exit_handlers.clone(),
+                random_source: self.random_source.clone(),
+                coroutine: None,
+                stepping: None,
+                debugger: None,
+                interrupt: self.interrupt.clone(),
+                profiling: false,
+                profile_stats: HashMap::new(),
+                handlers: Vec::new(),
+                location: None,
+                backtrace: Vec::new(),
+                memory_limit: None,
+                depth: 0,
+                depth_limit: None,
+                timed_out: self.timed_out.clone(),
+                ports: self.ports.clone(),
+                docs: self.docs.clone(),
+            }
+        }
+
+        pub fn set_debugger(&mut self, debugger: Box<Debugger>) {
+            self.debugger = Some(debugger);
+        }
+
+        // Setting the flag makes the running evaluation fail with LispError::Interrupted
+        pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+            self.interrupt.clone()
+        }
+
+        // Where the top-level form about to be evaluated comes from; lambdas it creates record it
+        pub fn set_source_location(&mut self, location: Option<SourceLocation>) {
+            self.location = location;
+        }
+
+        // The frames the last uncaught error unwound through, innermost first
+        pub fn take_backtrace(&mut self) -> Vec<Frame> {
+            std::mem::take(&mut self.backtrace)
+        }
+
+        pub fn get_symbol(&self, name: &str) -> Option<Expr> {
+            self.scope.lock().unwrap().lookup(SymbolId::intern(name))
+        }
+
+        pub fn set_symbol(&mut self, name: &str, value: Expr) {
+            self.define(SymbolId::intern(name), value);
+        }
+
+        pub fn remove_symbol(&mut self, name: &str) -> Option<Expr> {
+            self.scope.lock().unwrap().symbols.remove(&SymbolId::intern(name))
+        }
+
+        pub fn defined_symbols(&self) -> Vec<String> {
+            let mut names = HashSet::new();
+            self.scope.lock().unwrap().collect_names(&mut names);
+
+            let mut names: Vec<String> = names.into_iter().collect();
+            names.sort();
+            names
+        }
+
+        fn lookup(&self, name: SymbolId) -> Option<Expr> {
+            self.scope.lock().unwrap().lookup(name)
+        }
+
+        fn define(&mut self, name: SymbolId, value: Expr) {
+            self.scope.lock().unwrap().symbols.insert(name, value);
+        }
+
+        fn assign(&mut self, name: SymbolId, value: Expr) -> Result<(), LispError> {
+            if self.scope.lock().unwrap().assign(name, value) {
+                Ok(())
+            } else {
+                Err(format!("Cannot set! undefined symbol: {}", name).into())
+            }
+        }
+
+        pub fn defined_functions(&self) -> Vec<&str> {
+            let mut names: Vec<&str> = self
+                .functions
+                .keys()
+                .chain(self.closures.keys())
+                .map(|name| name.as_str())
+                .collect();
+            names.sort();
+            names
+        }
+
+        fn has_native(&self, name: &str) -> bool {
+            self.functions.contains_key(name) || self.closures.contains_key(name)
+        }
+
+        pub fn set_command_line(&mut self, args: Vec<String>) {
+            self.command_line = args;
+        }
+
+        // Runs the thunks registered with at-exit, most recent first
+        pub fn run_exit_handlers(&mut self) {
+            while let Some(thunk) = self.exit_handlers.pop() {
+                if let Err(e) = apply_procedure(&thunk, &[], self) {
+                    eprintln!("Error in exit handler: {}", e);
+                }
+            }
+        }
+
+        pub fn add_feature(&mut self, feature: &str) {
+            self.features.insert(feature.to_string());
+        }
+
+        pub fn has_feature(&self, feature: &str) -> bool {
+            self.features.contains(feature)
+        }
+    }
+
+    fn features(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if !args.is_empty() {
+            return Err("'features' takes no arguments".into());
+        }
+
+        let mut names: Vec<&String> = env.features.iter().collect();
+        names.sort();
+
+        Ok(Expr::List(names.into_iter().map(|name| Expr::Symbol(SymbolId::intern(name))).collect()))
+    }
+
+    fn eval_body(body: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let mut result = Expr::Void;
+        for expr in body {
+            result = eval(expr, env)?;
+        }
+        Ok(result)
+    }
+
+    fn eval_body_tail(body: &[Expr], env: &mut Environment) -> Result<Step, LispError> {
+        match body.split_last() {
+            Some((last, init)) => {
+                for expr in init {
+                    eval(expr, env)?;
+                }
+                eval_tail(last, env)
+            }
+            None => Ok(Step::Value(Expr::Void)),
+        }
+    }
+
+    fn feature_requirement_met(requirement: &Expr, env: &Environment) -> Result<bool, LispError> {
+        match requirement {
+            Expr::Symbol(name) => Ok(*name == "else" || env.has_feature(name.as_str())),
+            Expr::List(parts) => match parts.split_first() {
+                Some((Expr::Symbol(op), operands)) => match op.as_str() {
+                    "and" => {
+                        for operand in operands {
+                            if !feature_requirement_met(operand, env)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    "or" => {
+                        for operand in operands {
+                            if feature_requirement_met(operand, env)? {
+                                return Ok(true);
+                            }
+                        }
+                        Ok(false)
+                    }
+                    "not" => {
+                        if operands.len() != 1 {
+                            return Err("'not' requirement takes exactly 1 argument".into());
+                        }
+                        Ok(!feature_requirement_met(&operands[0], env)?)
+                    }
+                    _ => Err(format!("Invalid feature requirement: {}", requirement).into()),
+                },
+                _ => Err(format!("Invalid feature requirement: {}", requirement).into()),
+            },
+            _ => Err(format!("Invalid feature requirement: {}", requirement).into()),
+        }
+    }
+
+    fn eval_cond_expand(clauses: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        for clause in clauses {
+            let (requirement, body) = match clause {
+                Expr::List(parts) if !parts.is_empty() => (&parts[0], &parts[1..]),
+                _ => return Err("Invalid clause in 'cond-expand'".into()),
+            };
+
+            // Only the forms of the first matching clause are ever evaluated
+            if feature_requirement_met(requirement, env)? {
+                return eval_body(body, env);
+            }
+        }
+
+        Err("No matching clause in 'cond-expand'".into())
+    }
+
+    // A lambda built from Rust, whose body sees `captured` as local bindings
+    fn make_closure(params: &[&str], body: Expr, captured: Vec<(&str, Expr)>) -> Expr {
+        let mut scope = Scope::default();
+        scope.symbols.extend(captured.into_iter().map(|(name, value)| (SymbolId::intern(name), value)));
+
+        Expr::Lambda(Arc::new(Lambda {
+            params: Expr::List(params.iter().map(|&p| Expr::Symbol(p.into())).collect()),
+            body: vec![body],
+            scope: Arc::new(Mutex::new(scope)),
+            source: None,
+            name: Mutex::new(None),
+        }))
+    }
+
+    fn is_procedure(expr: &Expr) -> bool {
+        matches!(expr, Expr::Builtin(_) | Expr::Lambda(_) | Expr::Memoized(_) | Expr::Parameter(_))
+    }
+
+    fn apply_procedure(procedure: &Expr, args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match procedure {
+            Expr::Builtin(name) => {
+                if let Some(func) = env.functions.get(name) {
+                    let func = *func;
+                    func(args, env)
+                } else if let Some(closure) = env.closures.get(name) {
+                    let closure = closure.clone();
+                    closure(args, env)
+                } else {
+                    Err(format!("Undefined function: {}", name).into())
+                }
+            }
+            Expr::Lambda(lambda) => {
+                let mut lambda = lambda.clone();
+                let mut args = args.to_vec();
+                // A call in tail position comes back here instead of growing the Rust stack
+                loop {
+                    match apply_lambda(&lambda, args, env) {
+                        Ok(Step::Value(value)) => return Ok(value),
+                        Ok(Step::TailCall(next, next_args)) => {
+                            lambda = next;
+                            args = next_args;
+                        }
+                        Err(error) => {
+                            env.backtrace.push(Frame {
+                                name: lambda.name().map(|name| name.to_string()),
+                                location: lambda.source.clone(),
+                            });
+                            return Err(error);
+                        }
+                    }
+                }
+            }
+            Expr::Memoized(memoized) => {
+                if let Some(value) = memoized.cache.lock().unwrap().get(args) {
+                    return Ok(value.clone());
+                }
+
+                // The cache is not locked while computing, since the procedure may call itself
+                let value = apply_procedure(&memoized.procedure, args, env)?;
+                memoized.cache.lock().unwrap().insert(args.to_vec(), value.clone());
+                Ok(value)
+            }
+            Expr::Parameter(parameter) => match args {
+                [] => Ok(parameter.value.lock().unwrap().clone()),
+                _ => Err("A parameter takes no arguments".into()),
+            },
+            _ => Err(format!("Not a procedure: {}", procedure).into()),
+        }
+    }
+
+    // Runs the body of `lambda`, leaving a call in tail position of the body to the caller
+    fn apply_lambda(lambda: &Lambda, args: Vec<Expr>, env: &mut Environment) -> Result<Step, LispError> {
+        if let Some(name) = lambda.name() {
+            let arity = ArityInfo::of_formals(&lambda.params);
+            if !arity.accepts(args.len()) {
+                return Err(format!("Wrong number of arguments to {}: expected {}, got {}", name, arity, args.len()).into());
+            }
+        }
+        let bindings = bind_formals(&lambda.params, args)?;
+
+        let mut scope = Scope::child(&lambda.scope);
+        scope.symbols.extend(bindings);
+
+        let scope = env.heap.alloc_scope(scope);
+        let caller_scope = std::mem::replace(&mut env.scope, scope);
+        let result = eval_body_tail(&lambda.body, env);
+        env.scope = caller_scope;
+
+        result
+    }
+
+    // Follows SRFI-1: the accumulator is the second argument, `(f elem acc)`
+    fn reduce(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 3 {
+            return Err("'reduce' requires exactly 3 arguments".into());
+        }
+
+        let list = match &args[2] {
+            Expr::List(l) => l,
+            _ => return Err("Invalid argument type for reduce".into()),
+        };
+
+        let (first, rest) = match list.split_first() {
+            Some(split) => split,
+            None => return Ok(args[1].clone()),
+        };
+
+        let mut acc = first.clone();
+        for item in rest {
+            acc = apply_procedure(&args[0], &[item.clone(), acc], env)?;
+        }
+
+        Ok(acc)
+    }
+
+    fn reduce_right(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 3 {
+            return Err("'reduce-right' requires exactly 3 arguments".into());
+        }
+
+        let list = match &args[2] {
+            Expr::List(l) => l,
+            _ => return Err("Invalid argument type for reduce-right".into()),
+        };
+
+        let (last, rest) = match list.split_last() {
+            Some(split) => split,
+            None => return Ok(args[1].clone()),
+        };
+
+        let mut acc = last.clone();
+        for item in rest.iter().rev() {
+            acc = apply_procedure(&args[0], &[item.clone(), acc], env)?;
+        }
+
+        Ok(acc)
+    }
+
+    fn is_truthy(expr: &Expr) -> bool {
+        !matches!(expr, Expr::Bool(false))
+    }
+
+    // Collects the lists passed to a multi-list procedure such as `every`, truncated to the shortest
+    fn list_arguments<'a>(args: &'a [Expr], name: &str) -> Result<(Vec<&'a Vec<Expr>>, usize), LispError> {
+        if args.is_empty() {
+            return Err(format!("'{}' requires at least one list", name).into());
+        }
+
+        let mut lists = Vec::new();
+        for arg in args {
+            match arg {
+                Expr::List(l) => lists.push(l),
+                _ => return Err(format!("Invalid argument type for {}", name).into()),
+            }
+        }
+
+        let len = lists.iter().map(|l| l.len()).min().unwrap();
+        Ok((lists, len))
+    }
+
+    fn every(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, lists) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'every' requires a predicate and at least one list".into()),
+        };
+        let (lists, len) = list_arguments(lists, "every")?;
+
+        let mut result = Expr::Bool(true);
+        for i in 0..len {
+            let call_args: Vec<Expr> = lists.iter().map(|l| l[i].clone()).collect();
+            result = apply_procedure(pred, &call_args, env)?;
+            if !is_truthy(&result) {
+                return Ok(result);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn any(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, lists) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'any' requires a predicate and at least one list".into()),
+        };
+        let (lists, len) = list_arguments(lists, "any")?;
+
+        for i in 0..len {
+            let call_args: Vec<Expr> = lists.iter().map(|l| l[i].clone()).collect();
+            let result = apply_procedure(pred, &call_args, env)?;
+            if is_truthy(&result) {
+                return Ok(result);
+            }
+        }
+
+        Ok(Expr::Bool(false))
+    }
+
+    fn predicate_and_list<'a>(args: &'a [Expr], name: &str) -> Result<(&'a Expr, &'a [Expr]), LispError> {
+        if args.len() != 2 {
+            return Err(format!("'{}' requires exactly 2 arguments", name).into());
+        }
+
+        match &args[1] {
+            Expr::List(l) => Ok((&args[0], l)),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn partition(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, list) = predicate_and_list(args, "partition")?;
+
+        let mut matching = Vec::new();
+        let mut rest = Vec::new();
+        for item in list {
+            if is_truthy(&apply_procedure(pred, std::slice::from_ref(item), env)?) {
+                matching.push(item.clone());
+            } else {
+                rest.push(item.clone());
+            }
+        }
+
+        Ok(Expr::Values(vec![Expr::List(matching), Expr::List(rest)]))
+    }
+
+    // Index of the first element for which `pred` returns `stop_when`, or the list length
+    fn split_index(
+        pred: &Expr,
+        list: &[Expr],
+        stop_when: bool,
+        env: &mut Environment,
+    ) -> Result<usize, LispError> {
+        for (i, item) in list.iter().enumerate() {
+            if is_truthy(&apply_procedure(pred, std::slice::from_ref(item), env)?) == stop_when {
+                return Ok(i);
+            }
+        }
+
+        Ok(list.len())
+    }
+
+    fn split_list_at(list: &[Expr], split: usize) -> Expr {
+        Expr::Values(vec![
+            Expr::List(list[..split].to_vec()),
+            Expr::List(list[split..].to_vec()),
+        ])
+    }
+
+    fn span(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, list) = predicate_and_list(args, "span")?;
+        let split = split_index(pred, list, false, env)?;
+        Ok(split_list_at(list, split))
+    }
+
+    fn break_list(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, list) = predicate_and_list(args, "break")?;
+        let split = split_index(pred, list, true, env)?;
+        Ok(split_list_at(list, split))
+    }
+
+    fn list_and_count<'a>(args: &'a [Expr], name: &str) -> Result<(&'a [Expr], usize), LispError> {
+        if args.len() != 2 {
+            return Err(format!("'{}' requires exactly 2 arguments", name).into());
+        }
+
+        match (&args[0], &args[1]) {
+            (Expr::List(l), Expr::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+                let count = *n as usize;
+                if count > l.len() {
+                    return Err(format!("List too short for {}: {} > {}", name, count, l.len()).into());
+                }
+                Ok((l, count))
+            }
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn take(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (list, count) = list_and_count(args, "take")?;
+        Ok(Expr::List(list[..count].to_vec()))
+    }
+
+    fn drop(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (list, count) = list_and_count(args, "drop")?;
+        Ok(Expr::List(list[count..].to_vec()))
+    }
+
+    fn take_while(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, list) = predicate_and_list(args, "take-while")?;
+        let split = split_index(pred, list, false, env)?;
+        Ok(Expr::List(list[..split].to_vec()))
+    }
+
+    fn drop_while(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, list) = predicate_and_list(args, "drop-while")?;
+        let split = split_index(pred, list, false, env)?;
+        Ok(Expr::List(list[split..].to_vec()))
+    }
+
+    fn zip(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (lists, len) = list_arguments(args, "zip")?;
+
+        let tuples = (0..len)
+            .map(|i| Expr::List(lists.iter().map(|l| l[i].clone()).collect()))
+            .collect();
+
+        Ok(Expr::List(tuples))
+    }
+
+    fn unzip(args: &[Expr], width: usize, name: &str) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err(format!("'{}' requires exactly 1 argument", name).into());
+        }
+
+        let tuples = match &args[0] {
+            Expr::List(l) => l,
+            _ => return Err(format!("Invalid argument type for {}", name).into()),
+        };
+
+        let mut columns = vec![Vec::new(); width];
+        for tuple in tuples {
+            match tuple {
+                Expr::List(items) if items.len() >= width => {
+                    for (column, item) in columns.iter_mut().zip(items) {
+                        column.push(item.clone());
+                    }
+                }
+                _ => return Err(format!("Each element of {} must be a list of at least {} items", name, width).into()),
+            }
+        }
+
+        let mut columns: Vec<Expr> = columns.into_iter().map(Expr::List).collect();
+        if width == 1 {
+            return Ok(columns.remove(0));
+        }
+
+        Ok(Expr::Values(columns))
+    }
+
+    fn unzip1(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        unzip(args, 1, "unzip1")
+    }
+
+    fn unzip2(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        unzip(args, 2, "unzip2")
+    }
+
+    fn unzip3(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        unzip(args, 3, "unzip3")
+    }
+
+    fn unzip4(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        unzip(args, 4, "unzip4")
+    }
+
+    fn unzip5(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        unzip(args, 5, "unzip5")
+    }
+
+    fn count(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, lists) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'count' requires a predicate and at least one list".into()),
+        };
+        let (lists, len) = list_arguments(lists, "count")?;
+
+        let mut total = 0;
+        for i in 0..len {
+            let call_args: Vec<Expr> = lists.iter().map(|l| l[i].clone()).collect();
+            if is_truthy(&apply_procedure(pred, &call_args, env)?) {
+                total += 1;
+            }
+        }
+
+        Ok(Expr::Number(total as f64))
+    }
+
+    fn list_tabulate(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (len, proc) = match args {
+            [len, proc] => (index_value(len, "list-tabulate")?, proc),
+            _ => return Err("'list-tabulate' requires a length and a procedure".into()),
+        };
+
+        let mut items = Vec::with_capacity(len);
+        for i in 0..len {
+            items.push(apply_procedure(proc, &[Expr::Number(i as f64)], env)?);
+        }
+
+        Ok(Expr::List(items))
+    }
+
+    fn list_index(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (pred, lists) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("'list-index' requires a predicate and at least one list".into()),
+        };
+        let (lists, len) = list_arguments(lists, "list-index")?;
+
+        for i in 0..len {
+            let call_args: Vec<Expr> = lists.iter().map(|l| l[i].clone()).collect();
+            if is_truthy(&apply_procedure(pred, &call_args, env)?) {
+                return Ok(Expr::Number(i as f64));
+            }
+        }
+
+        Ok(Expr::Bool(false))
+    }
+
+    // Compares with the given equality procedure, or with equal? when there is none
+    fn same_element(same: Option<&Expr>, a: &Expr, b: &Expr, env: &mut Environment) -> Result<bool, LispError> {
+        match same {
+            Some(same) => Ok(is_truthy(&apply_procedure(same, &[a.clone(), b.clone()], env)?)),
+            None => Ok(a == b),
+        }
+    }
+
+    fn contains_element(same: Option<&Expr>, list: &[Expr], x: &Expr, env: &mut Environment) -> Result<bool, LispError> {
+        for item in list {
+            if same_element(same, x, item, env)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn delete(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (x, list, same) = match args {
+            [x, Expr::List(list)] => (x, list, None),
+            [x, Expr::List(list), same] => (x, list, Some(same)),
+            [_, _] | [_, _, _] => return Err("Invalid argument type for delete".into()),
+            _ => return Err("'delete' requires an element, a list and an optional equality procedure".into()),
+        };
+
+        let mut kept = Vec::new();
+        for item in list {
+            if !same_element(same, x, item, env)? {
+                kept.push(item.clone());
+            }
+        }
+        Ok(Expr::List(kept))
+    }
+
+    // Keeps the first occurrence of each element, in order
+    fn delete_duplicates(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (list, same) = match args {
+            [Expr::List(list)] => (list, None),
+            [Expr::List(list), same] => (list, Some(same)),
+            [_] | [_, _] => return Err("Invalid argument type for delete-duplicates".into()),
+            _ => return Err("'delete-duplicates' requires a list and an optional equality procedure".into()),
+        };
+
+        let mut kept = Vec::new();
+        for item in list {
+            if !contains_element(same, &kept, item, env)? {
+                kept.push(item.clone());
+            }
+        }
+        Ok(Expr::List(kept))
+    }
+
+    // The equality procedure and the lists of an lset operation
+    fn lset_arguments<'a>(args: &'a [Expr], name: &str) -> Result<(&'a Expr, Vec<&'a Vec<Expr>>), LispError> {
+        match args.split_first() {
+            Some((same, [])) => Ok((same, Vec::new())),
+            Some((same, lists)) => Ok((same, list_arguments(lists, name)?.0)),
+            None => Err(format!("'{}' requires an equality procedure and lists", name).into()),
+        }
+    }
+
+    fn lset_union(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (same, lists) = lset_arguments(args, "lset-union")?;
+
+        let mut union: Vec<Expr> = Vec::new();
+        for (i, list) in lists.into_iter().enumerate() {
+            if i == 0 {
+                union.extend(list.iter().cloned());
+                continue;
+            }
+            for item in list {
+                if !contains_element(Some(same), &union, item, env)? {
+                    union.push(item.clone());
+                }
+            }
+        }
+        Ok(Expr::List(union))
+    }
+
+    fn lset_intersection(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (same, lists) = lset_arguments(args, "lset-intersection")?;
+        let Some((first, others)) = lists.split_first() else {
+            return Err("'lset-intersection' requires at least one list".into());
+        };
+
+        let mut kept = Vec::new();
+        'items: for item in first.iter() {
+            for other in others {
+                if !contains_element(Some(same), other, item, env)? {
+                    continue 'items;
+                }
+            }
+            kept.push(item.clone());
+        }
+        Ok(Expr::List(kept))
+    }
+
+    fn lset_difference(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (same, lists) = lset_arguments(args, "lset-difference")?;
+        let Some((first, others)) = lists.split_first() else {
+            return Err("'lset-difference' requires at least one list".into());
+        };
+
+        let mut kept = Vec::new();
+        'items: for item in first.iter() {
+            for other in others {
+                if contains_element(Some(same), other, item, env)? {
+                    continue 'items;
+                }
+            }
+            kept.push(item.clone());
+        }
+        Ok(Expr::List(kept))
+    }
+
+    fn flatten_into(items: &[Expr], depth: Option<usize>, out: &mut Vec<Expr>) {
+        for item in items {
+            match (item, depth) {
+                (Expr::List(inner), None) => flatten_into(inner, None, out),
+                (Expr::List(inner), Some(levels)) if levels > 0 => {
+                    flatten_into(inner, Some(levels - 1), out)
+                }
+                _ => out.push(item.clone()),
+            }
+        }
+    }
+
+    // The depth may come before or after the list
+    fn flatten(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (list, depth) = match args {
+            [list] => (list, None),
+            [depth @ Expr::Number(_), list @ Expr::List(_)] | [list, depth] => match depth {
+                Expr::Number(n) if *n >= 0.0 && n.fract() == 0.0 => (list, Some(*n as usize)),
+                _ => return Err("Depth for flatten must be a non-negative integer".into()),
+            },
+            _ => return Err("'flatten' requires 1 or 2 arguments".into()),
+        };
+
+        let items = match list {
+            Expr::List(l) => l,
+            _ => return Err("Invalid argument type for flatten".into()),
+        };
+
+        let mut flat = Vec::new();
+        flatten_into(items, depth, &mut flat);
+        Ok(Expr::List(flat))
+    }
+
+    fn deep_map_expr(func: &Expr, expr: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        match expr {
+            Expr::List(items) => {
+                let mapped: Result<Vec<Expr>, LispError> =
+                    items.iter().map(|item| deep_map_expr(func, item, env)).collect();
+                mapped.map(Expr::List)
+            }
+            _ => apply_procedure(func, std::slice::from_ref(expr), env),
+        }
+    }
+
+    fn deep_map(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 2 {
+            return Err("'deep-map' requires exactly 2 arguments".into());
+        }
+
+        deep_map_expr(&args[0], &args[1], env)
+    }
+
+    fn string_pair<'a>(args: &'a [Expr], name: &str) -> Result<(&'a str, &'a str), LispError> {
+        if args.len() != 2 {
+            return Err(format!("'{}' requires exactly 2 arguments", name).into());
+        }
+
+        match (&args[0], &args[1]) {
+            (Expr::Str(a), Expr::Str(b)) => Ok((a, b)),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
+
+    fn find_substring(haystack: &str, needle: &str) -> Expr {
+        match haystack.find(needle) {
+            Some(byte_index) => Expr::Number(haystack[..byte_index].chars().count() as f64),
+            None => Expr::Bool(false),
+        }
+    }
+
+    fn string_contains(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (haystack, needle) = string_pair(args, "string-contains")?;
+        Ok(find_substring(haystack, needle))
+    }
+
+    fn string_contains_p(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (haystack, needle) = string_pair(args, "string-contains?")?;
+        Ok(Expr::Bool(haystack.contains(needle)))
+    }
+
+    fn string_contains_ci(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (haystack, needle) = string_pair(args, "string-contains-ci")?;
+        Ok(find_substring(&haystack.to_lowercase(), &needle.to_lowercase()))
+    }
+
+    fn string_prefix(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (prefix, s) = string_pair(args, "string-prefix?")?;
+        Ok(Expr::Bool(s.starts_with(prefix)))
+    }
+
+    fn string_prefix_ci(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (prefix, s) = string_pair(args, "string-prefix-ci?")?;
+        Ok(Expr::Bool(s.to_lowercase().starts_with(&prefix.to_lowercase())))
+    }
+
+    fn string_suffix(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (suffix, s) = string_pair(args, "string-suffix?")?;
+        Ok(Expr::Bool(s.ends_with(suffix)))
+    }
+
+    fn string_suffix_ci(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (suffix, s) = string_pair(args, "string-suffix-ci?")?;
+        Ok(Expr::Bool(s.to_lowercase().ends_with(&suffix.to_lowercase())))
+    }
+
+    fn string_split(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let s = match args.first() {
+            Some(Expr::Str(s)) => s,
+            _ => return Err("'string-split' requires a string argument".into()),
+        };
+
+        let parts: Vec<&str> = match args.get(1) {
+            None => s.split_whitespace().collect(),
+            Some(Expr::Char(sep)) if args.len() == 2 => s.split(*sep).collect(),
+            Some(Expr::Str(sep)) if args.len() == 2 => s.split(sep.as_str()).collect(),
+            _ => return Err("Separator for string-split must be a character or string".into()),
+        };
+
+        Ok(Expr::List(parts.into_iter().map(|part| Expr::Str(part.to_string())).collect()))
+    }
+
+    fn string_join(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let items = match args.first() {
+            Some(Expr::List(items)) => items,
+            _ => return Err("'string-join' requires a list of strings".into()),
+        };
+
+        let separator = match args.get(1) {
+            None => String::new(),
+            Some(Expr::Str(sep)) if args.len() == 2 => sep.clone(),
+            Some(Expr::Char(sep)) if args.len() == 2 => sep.to_string(),
+            _ => return Err("Separator for string-join must be a string".into()),
+        };
+
+        let mut parts = Vec::new();
+        for item in items {
+            match item {
+                Expr::Str(s) => parts.push(s.as_str()),
+                _ => return Err("Invalid element type for string-join".into()),
+            }
+        }
+
+        Ok(Expr::Str(parts.join(&separator)))
+    }
+
+    // Like string-join, but characters may stand in for one-character strings, as in list->string
+    fn list_to_string_join(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let (items, separator) = match args {
+            [Expr::List(items), Expr::Str(separator)] => (items, separator),
+            _ => return Err("'list->string/join' requires a list of strings and a separator string".into()),
+        };
+
+        let mut parts = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Expr::Str(s) => parts.push(s.clone()),
+                Expr::Char(c) => parts.push(c.to_string()),
+                _ => return Err(format!("Invalid element for list->string/join: {}", item).into()),
+            }
+        }
+
+        Ok(Expr::Str(parts.join(separator)))
+    }
+
+    fn string_to_words(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(Expr::List(s.split_whitespace().map(|word| Expr::Str(word.to_string())).collect())),
+            _ => Err("'string->words' requires exactly 1 string".into()),
+        }
+    }
+
+    // Splits on \n or \r\n; a final line ending does not start another, empty line
+    fn string_to_lines(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(Expr::List(s.lines().map(|line| Expr::Str(line.to_string())).collect())),
+            _ => Err("'string->lines' requires exactly 1 string".into()),
+        }
+    }
+
+    // The optional criterion of the trim functions is a character or a predicate procedure
+    fn trim_matches(criterion: Option<&Expr>, c: char, env: &mut Environment) -> Result<bool, LispError> {
+        match criterion {
+            None => Ok(c.is_whitespace()),
+            Some(Expr::Char(target)) => Ok(c == *target),
+            Some(pred) => Ok(is_truthy(&apply_procedure(pred, &[Expr::Char(c)], env)?)),
+        }
+    }
+
+    fn trim_string(
+        args: &[Expr],
+        env: &mut Environment,
+        name: &str,
+        left: bool,
+        right: bool,
+    ) -> Result<Expr, LispError> {
+        let s = match args {
+            [Expr::Str(s)] | [Expr::Str(s), _] => s,
+            _ => return Err(format!("'{}' requires a string and an optional criterion", name).into()),
+        };
+        let criterion = args.get(1);
+
+        let chars: Vec<char> = s.chars().collect();
+        let mut start = 0;
+        let mut end = chars.len();
+
+        if left {
+            while start < end && trim_matches(criterion, chars[start], env)? {
+                start += 1;
+            }
+        }
+        if right {
+            while end > start && trim_matches(criterion, chars[end - 1], env)? {
+                end -= 1;
+            }
+        }
+
+        Ok(Expr::Str(chars[start..end].iter().collect()))
     }
 
-    type Function = fn(&[Expr], &mut Environment) -> Result<Expr, String>;
+    fn string_trim(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        trim_string(args, env, "string-trim", true, false)
+    }
 
-    #[derive(Default)]
-    pub struct Environment {
-        symbols: HashMap<String, Expr>,
-        functions: HashMap<String, Function>,
+    fn string_trim_right(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        trim_string(args, env, "string-trim-right", false, true)
     }
 
-    impl fmt::Debug for Environment {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            f.debug_struct("Environment")
-                .field("symbols", &self.symbols)
-                .field("functions", &"Function HashMap") // Use a string description for the functions field
-                .finish()
-        }
+    fn string_trim_both(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        trim_string(args, env, "string-trim-both", true, true)
     }
 
-    impl fmt::Display for Expr {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            match self {
-                Expr::Symbol(s) => write!(f, "{}", s),
-                Expr::Number(n) => write!(f, "{}", n),
-                Expr::List(l) => {
-                    let inner: Vec<String> = l.iter().map(|e| e.to_string()).collect();
-                    write!(f, "({})", inner.join(" "))
-                }
+    // Pads or truncates to exactly `n` characters, keeping the end of the string when `left`
+    fn pad_string(args: &[Expr], name: &str, left: bool) -> Result<Expr, LispError> {
+        let (s, n) = match args {
+            [Expr::Str(s), Expr::Number(n)] | [Expr::Str(s), Expr::Number(n), _]
+                if *n >= 0.0 && n.fract() == 0.0 =>
+            {
+                (s, *n as usize)
+            }
+            _ => return Err(format!("'{}' requires a string, a length and an optional character", name).into()),
+        };
+
+        let fill = match args.get(2) {
+            None => ' ',
+            Some(Expr::Char(c)) => *c,
+            Some(_) => return Err(format!("Fill argument of {} must be a character", name).into()),
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let len = chars.len();
+        let padded: String = if len >= n {
+            if left {
+                chars[len - n..].iter().collect()
+            } else {
+                chars[..n].iter().collect()
             }
+        } else {
+            let padding: String = std::iter::repeat_n(fill, n - len).collect();
+            if left {
+                padding + s
+            } else {
+                s.clone() + &padding
+            }
+        };
+
+        Ok(Expr::Str(padded))
+    }
+
+    fn string_pad(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        pad_string(args, "string-pad", true)
+    }
+
+    fn string_pad_right(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        pad_string(args, "string-pad-right", false)
+    }
+
+    // Counted in characters, like string-ref indexes
+    fn string_length(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Str(s)] => Ok(Expr::Number(s.chars().count() as f64)),
+            [_] => Err("Invalid argument type for string-length".into()),
+            _ => Err("'string-length' requires exactly 1 argument".into()),
         }
     }
 
-    pub fn tokenize(input: &str) -> Vec<String> {
-        // Replace parentheses with spaces and add split tokens
-        input.replace("(", " ( ")
-            .replace(")", " ) ")
-            .split_whitespace()
-            .map(|token| token.to_string())
-            .collect()
+    fn string_append(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let mut joined = String::new();
+        for arg in args {
+            match arg {
+                Expr::Str(s) => joined.push_str(s),
+                _ => return Err("Invalid argument type for string-append".into()),
+            }
+        }
+        Ok(Expr::Str(joined))
     }
 
-    pub fn parse(tokens: &[String]) -> Result<(Expr, &[String]), String> {
-        if tokens.is_empty() {
-            return Err("Unexpected EOF".to_string());
+    fn integer_argument(args: &[Expr], name: &str) -> Result<f64, LispError> {
+        if args.len() != 1 {
+            return Err(format!("'{}' requires exactly 1 argument", name).into());
         }
 
-        let (token, rest) = tokens.split_first().unwrap();
+        match args[0] {
+            Expr::Number(n) if n.fract() == 0.0 => Ok(n),
+            _ => Err(format!("Invalid argument type for {}", name).into()),
+        }
+    }
 
-        let expr = match &token[..] {
-            "(" => {
-                let mut list = Vec::new();
-                let mut remaining_tokens = rest;
+    fn is_odd(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let n = integer_argument(args, "odd?")?;
+        Ok(Expr::Bool(n % 2.0 != 0.0))
+    }
 
-                while let Some(token) = remaining_tokens.first() {
-                    if token == ")" {
-                        break;
-                    }
+    fn is_even(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        let n = integer_argument(args, "even?")?;
+        Ok(Expr::Bool(n % 2.0 == 0.0))
+    }
 
-                    let (parsed_expr, new_remaining_tokens) = parse(remaining_tokens)?;
-                    list.push(parsed_expr);
-                    remaining_tokens = new_remaining_tokens;
-                }
+    fn values(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() == 1 {
+            return Ok(args[0].clone());
+        }
 
-                if remaining_tokens.is_empty() {
-                    return Err("Unexpected EOF".to_string());
-                }
+        Ok(Expr::Values(args.to_vec()))
+    }
 
-                let (_, new_remaining_tokens) = remaining_tokens.split_first().unwrap();
-                (Expr::List(list), new_remaining_tokens)
-            }
-            ")" => {
-                return Err("Unexpected )".to_string());
+    fn with_values(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [produced, receiver] if is_procedure(receiver) => {
+                apply_procedure(receiver, &into_values(produced.clone()), env)
             }
-            _ => {
-                let atom = if let Ok(number) = token.parse::<f64>() {
-                    Expr::Number(number)
-                } else {
-                    Expr::Symbol(token.clone())
-                };
+            _ => Err("'with-values' requires a value and a receiver procedure".into()),
+        }
+    }
 
-                (atom, rest)
+    fn floor_division(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        match args {
+            [Expr::Number(n), Expr::Number(d)] if *d != 0.0 => {
+                let quotient = (n / d).floor();
+                Ok(Expr::Values(vec![Expr::Number(quotient), Expr::Number(n - d * quotient)]))
             }
+            [Expr::Number(_), Expr::Number(_)] => Err("Division by zero in 'floor/'".into()),
+            _ => Err("'floor/' requires 2 numbers".into()),
+        }
+    }
+
+    fn list(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+        Ok(Expr::List(args.to_vec()))
+    }
+
+    fn call_with_values(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (producer, consumer) = match args {
+            [producer, consumer] if is_procedure(producer) && is_procedure(consumer) => (producer, consumer),
+            _ => return Err("'call-with-values' requires a producer and a consumer procedure".into()),
         };
 
-        Ok(expr)
+        let produced = into_values(apply_procedure(producer, &[], env)?);
+        apply_procedure(consumer, &produced, env)
     }
 
+    fn into_values(expr: Expr) -> Vec<Expr> {
+        match expr {
+            Expr::Values(values) => values,
+            other => vec![other],
+        }
+    }
 
-    fn add(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        let mut sum = 0.0;
+    // Pairs each formal with a value; `(a b . rest)` and a bare `rest` collect the remainder
+    fn bind_formals(formals: &Expr, values: Vec<Expr>) -> Result<Vec<(SymbolId, Expr)>, LispError> {
+        let names = match formals {
+            Expr::Symbol(rest) => return Ok(vec![(*rest, Expr::List(values))]),
+            Expr::List(names) => names,
+            _ => return Err(format!("Invalid formals: {}", formals).into()),
+        };
 
-        for arg in args {
-            match arg {
-                Expr::Number(n) => sum += n,
-                _ => return Err("Invalid argument type for addition".to_string()),
+        let (required, rest) = match names.iter().position(|name| *name == Expr::Symbol(".".into())) {
+            Some(dot) if dot + 2 == names.len() => (&names[..dot], Some(&names[dot + 1])),
+            Some(_) => return Err(format!("Invalid formals: {}", formals).into()),
+            None => (&names[..], None),
+        };
+
+        if values.len() < required.len() || (rest.is_none() && values.len() > required.len()) {
+            return Err(format!(
+                "Expected {} value(s) for {}, got {}",
+                required.len(),
+                formals,
+                values.len()
+            ).into());
+        }
+
+        let mut bindings = Vec::new();
+        let mut values_iter = values.into_iter();
+        for name in required {
+            match name {
+                Expr::Symbol(s) => bindings.push((*s, values_iter.next().unwrap())),
+                _ => return Err(format!("Invalid formals: {}", formals).into()),
             }
         }
 
-        Ok(Expr::Number(sum))
+        if let Some(rest) = rest {
+            match rest {
+                Expr::Symbol(s) => bindings.push((*s, Expr::List(values_iter.collect()))),
+                _ => return Err(format!("Invalid formals: {}", formals).into()),
+            }
+        }
+
+        Ok(bindings)
     }
 
-    fn subtract(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.is_empty() {
-            return Err("At least one argument is required for subtraction".to_string());
-        }
+    // Evaluates `body` in a new scope below `parent` holding `bindings`
+    fn eval_with_bindings(
+        parent: &Arc<Mutex<Scope>>,
+        bindings: Vec<(SymbolId, Expr)>,
+        body: &[Expr],
+        env: &mut Environment,
+    ) -> Result<Expr, LispError> {
+        let mut scope = Scope::child(parent);
+        scope.symbols.extend(bindings);
 
-        let mut args_iter = args.iter();
-        let first_arg = args_iter.next().unwrap();
+        let scope = env.heap.alloc_scope(scope);
+        let caller_scope = std::mem::replace(&mut env.scope, scope);
+        let result = eval_body(body, env);
+        env.scope = caller_scope;
 
-        let mut difference = match first_arg {
-            Expr::Number(n) => *n,
-            _ => return Err("Invalid argument type for subtraction".to_string()),
+        result
+    }
+
+    // Evaluates `arg_exprs` and applies `procedure` to the results
+    fn call_with_arguments(procedure: &Expr, arg_exprs: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let args: Vec<Expr> = arg_exprs.iter().map(|expr| eval(expr, env)).collect::<Result<_, _>>()?;
+        apply_procedure(procedure, &args, env)
+    }
+
+    fn eval_let_values(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        let (binding_specs, body) = match args.split_first() {
+            Some((Expr::List(specs), body)) => (specs, body),
+            _ => return Err("'let-values' requires a list of bindings".into()),
         };
 
-        for arg in args_iter {
-            match arg {
-                Expr::Number(n) => difference -= n,
-                _ => return Err("Invalid argument type for subtraction".to_string()),
+        // All initializers are evaluated before any of the bindings are visible
+        let mut bindings = Vec::new();
+        for spec in binding_specs {
+            match spec {
+                Expr::List(parts) if parts.len() == 2 => {
+                    let values = into_values(eval(&parts[1], env)?);
+                    bindings.extend(bind_formals(&parts[0], values)?);
+                }
+                _ => return Err("Each 'let-values' binding must be (formals expr)".into()),
             }
         }
 
-        Ok(Expr::Number(difference))
+        let scope = env.scope.clone();
+        eval_with_bindings(&scope, bindings, body, env)
     }
 
-    fn equal(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 2 {
-            return Err("Equality function requires exactly 2 arguments".to_string());
+    fn eval_receive(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() < 3 {
+            return Err("'receive' requires formals, an expression and a body".into());
         }
-    
-        let a = match args[0] {
-            Expr::Number(n) => n,
-            _ => return Err("Invalid argument type for equality function".to_string()),
-        };
-        let b = match args[1] {
-            Expr::Number(n) => n,
-            _ => return Err("Invalid argument type for equality function".to_string()),
-        };
-    
-        Ok(Expr::Symbol(if a == b { "true" } else { "false" }.to_string()))
+
+        let values = into_values(eval(&args[1], env)?);
+        let bindings = bind_formals(&args[0], values)?;
+
+        let scope = env.scope.clone();
+        eval_with_bindings(&scope, bindings, &args[2..], env)
     }
-    
 
-    fn car(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 1 {
-            return Err("Expected exactly one argument for car".to_string());
+    fn eval_define_values(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 2 {
+            return Err("Exactly 2 arguments are required for 'define-values'".into());
         }
 
-        match &args[0] {
-            Expr::List(list) if !list.is_empty() => Ok(list[0].clone()),
-            _ => Err("Invalid argument type for car".to_string()),
+        let values = into_values(eval(&args[1], env)?);
+        let bindings = bind_formals(&args[0], values)?;
+
+        let mut names = Vec::new();
+        for (name, value) in bindings {
+            env.define(name, value);
+            names.push(Expr::Symbol(name));
         }
+
+        Ok(Expr::List(names))
     }
 
-    fn cdr(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 1 {
-            return Err("Expected exactly one argument for cdr".to_string());
+    pub fn eval_str(input: &str, env: &mut Environment) -> Result<Expr, LispError> {
+        let tokens = tokenize(input);
+        let (expr, rest) = parse(&tokens)?;
+        if !rest.is_empty() {
+            return Err(LispError::Read(format!("Unexpected input after expression: {}", rest.join(" "))));
         }
 
-        match &args[0] {
-            Expr::List(list) => Ok(Expr::List(list[1..].to_vec())),
-            _ => Err("Invalid argument type for cdr".to_string()),
+        eval(&expr, env)
+    }
+
+    pub fn eval_all_str(input: &str, env: &mut Environment) -> Result<Expr, LispError> {
+        let tokens = tokenize(input);
+        let mut remaining_tokens = &tokens[..];
+        let mut result = Expr::Void;
+
+        while !remaining_tokens.is_empty() {
+            let (expr, rest) = parse(remaining_tokens)?;
+            result = eval(&expr, env)?;
+            remaining_tokens = rest;
         }
+
+        Ok(result)
     }
 
-    fn list_length(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 1 {
-            return Err("List length function requires exactly 1 argument".to_string());
+    fn read_step_command(expr: &Expr) -> StepCommand {
+        use std::io::Write;
+
+        loop {
+            print!("[step] > {} ", expr);
+            std::io::stdout().flush().ok();
+
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                return StepCommand::Continue;
+            }
+
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            match (command, argument.trim()) {
+                ("" | "n", _) => return StepCommand::Next,
+                ("c", _) => return StepCommand::Continue,
+                ("u", _) => return StepCommand::Up,
+                ("e", source) if !source.is_empty() => return StepCommand::Eval(source.to_string()),
+                ("p", name) if !name.is_empty() => return StepCommand::Print(name.to_string()),
+                _ => println!("Commands: n (next), c (continue), u (up), e <expr> (evaluate), p <symbol> (print)"),
+            }
         }
-    
-        let list = match &args[0] {
-            Expr::List(l) => l,
-            _ => return Err("Invalid argument type for list length function".to_string()),
-        };
-    
-        Ok(Expr::Number(list.len() as f64))
     }
-    
-    fn list_sum(args: &[Expr], _env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 1 {
-            return Err("List sum function requires exactly 1 argument".to_string());
+
+    fn debugger_prompt(expr: &Expr, depth: usize, env: &mut Environment) {
+        loop {
+            let command = match env.debugger.take() {
+                Some(mut debugger) => {
+                    let command = debugger(expr, depth);
+                    env.debugger = Some(debugger);
+                    command
+                }
+                None => read_step_command(expr),
+            };
+
+            match command {
+                StepCommand::Next => {
+                    if let Some(state) = &mut env.stepping {
+                        state.stop_depth = usize::MAX;
+                    }
+                    return;
+                }
+                StepCommand::Continue => {
+                    env.stepping = None;
+                    return;
+                }
+                StepCommand::Up => {
+                    if let Some(state) = &mut env.stepping {
+                        state.stop_depth = depth.saturating_sub(1);
+                    }
+                    return;
+                }
+                StepCommand::Eval(source) => {
+                    let stepping = env.stepping.take();
+                    match eval_all_str(&source, env) {
+                        Ok(value) => println!("{}", value),
+                        Err(err) => println!("Error: {}", err),
+                    }
+                    env.stepping = stepping;
+                }
+                StepCommand::Print(name) => match env.get_symbol(&name) {
+                    Some(value) => println!("{} = {}", name, value),
+                    None => println!("Undefined symbol: {}", name),
+                },
+            }
         }
-    
-        let list = match &args[0] {
-            Expr::List(l) => l,
-            _ => return Err("Invalid argument type for list sum function".to_string()),
-        };
-    
-        let mut sum = 0.0;
-        for item in list {
-            match item {
-                Expr::Number(n) => sum += n,
-                _ => return Err("Invalid element type for list sum function".to_string()),
+    }
+
+    fn eval_stepped(expr: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        let depth = match &env.stepping {
+            Some(state) if state.depth <= state.stop_depth => {
+                let depth = state.depth;
+                debugger_prompt(expr, depth, env);
+                depth
             }
+            Some(state) => state.depth,
+            None => return eval_expr(expr, env),
+        };
+
+        if let Some(state) = &mut env.stepping {
+            state.depth = depth + 1;
         }
-    
-        Ok(Expr::Number(sum))
+        let result = eval_expr(expr, env);
+        if let Some(state) = &mut env.stepping {
+            state.depth = depth;
+        }
+        result
     }
 
-    fn define(args: &[Expr], env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 2 {
-            return Err("Exactly 2 arguments are required for 'define'".to_string());
+    fn eval_step(args: &[Expr], env: &mut Environment) -> Result<Expr, LispError> {
+        if args.len() != 1 {
+            return Err("Exactly 1 argument is required for 'step'".into());
         }
-    
-        let symbol = match &args[0] {
-            Expr::Symbol(s) => s,
-            _ => return Err("First argument of 'define' must be a symbol".to_string()),
+
+        let outer = env.stepping.replace(StepState { depth: 0, stop_depth: usize::MAX });
+        let result = eval(&args[0], env);
+        env.stepping = outer;
+        result
+    }
+
+    // What is left of an expression evaluated in tail position: either its value, or a call to a
+    // lambda that the enclosing apply_procedure makes in place of the current one
+    enum Step {
+        Value(Expr),
+        TailCall(Arc<Lambda>, Vec<Expr>),
+    }
+
+    pub fn eval(expr: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        check_can_evaluate(env)?;
+
+        env.depth += 1;
+        // Only compound expressions are worth stopping at
+        let result = if env.stepping.is_some() && matches!(expr, Expr::List(_)) {
+            eval_stepped(expr, env)
+        } else {
+            eval_expr(expr, env)
         };
-    
-        let value = eval(&args[1], env)?;
-    
-        env.symbols.insert(symbol.clone(), value.clone());
-    
-        Ok(value)
+        env.depth -= 1;
+        // A single builtin call can allocate a lot before the next step
+        check_memory_limit(env)?;
+        result
     }
 
-    fn print(args: &[Expr], env: &mut Environment) -> Result<Expr, String> {
-        if args.len() != 1 {
-            return Err("Exactly 1 argument is required for 'print'".to_string());
+    // Like eval, but a call to a lambda is returned to the caller rather than made
+    fn eval_tail(expr: &Expr, env: &mut Environment) -> Result<Step, LispError> {
+        // The stepper reports each call at its own depth, so it gets no tail calls
+        if env.stepping.is_some() {
+            return eval(expr, env).map(Step::Value);
         }
-    
-        let value = eval(&args[0], env)?;
-        println!("{}", value);
-    
-        Ok(value)
+        check_can_evaluate(env)?;
+
+        env.depth += 1;
+        let result = eval_form(expr, env, true);
+        env.depth -= 1;
+        check_memory_limit(env)?;
+        result
     }
 
-    impl Environment {
-        pub fn new() -> Self {
-            let mut env = Environment::default();
-            env.functions.insert("+".to_string(), add);
-            env.functions.insert("-".to_string(), subtract);
-            env.functions.insert("=".to_string(), equal);
-            env.functions.insert("car".to_string(), car);
-            env.functions.insert("cdr".to_string(), cdr);
-            env.functions.insert("list-length".to_string(), list_length);
-            env.functions.insert("list-sum".to_string(), list_sum);
-            env.functions.insert("define".to_string(), define);
-            env.functions.insert("print".to_string(), print);
-            env
+    fn check_can_evaluate(env: &Environment) -> Result<(), LispError> {
+        if env.interrupt.load(Ordering::Relaxed) && env.interrupt.swap(false, Ordering::Relaxed) {
+            return Err(LispError::Interrupted);
+        }
+        if let Some((timed_out, ms)) = &env.timed_out {
+            if timed_out.load(Ordering::Relaxed) {
+                return Err(LispError::SandboxTimeout(*ms));
+            }
+        }
+        check_memory_limit(env)?;
+
+        if env.depth_limit.is_some_and(|limit| env.depth >= limit) {
+            return Err(format!("Sandboxed evaluation exceeded its nesting limit of {}", env.depth).into());
+        }
+        Ok(())
+    }
+
+    fn check_memory_limit(env: &Environment) -> Result<(), LispError> {
+        match env.memory_limit {
+            Some((baseline, limit)) if thread_allocated() - baseline > limit as isize => Err(LispError::SandboxMemoryExceeded(limit)),
+            _ => Ok(()),
         }
     }
 
+    fn eval_expr(expr: &Expr, env: &mut Environment) -> Result<Expr, LispError> {
+        match eval_form(expr, env, false)? {
+            Step::Value(value) => Ok(value),
+            Step::TailCall(lambda, args) => apply_procedure(&Expr::Lambda(lambda), &args, env),
+        }
+    }
 
-    pub fn eval(expr: &Expr, env: &mut Environment) -> Result<Expr, String> {
-        match expr {
+    fn eval_form(expr: &Expr, env: &mut Environment, tail: bool) -> Result<Step, LispError> {
+        let value = match expr {
             Expr::Symbol(symbol) => {
-                env.symbols
-                    .get(symbol)
-                    .cloned()
-                    .ok_or_else(|| format!("Undefined symbol: {}", symbol))
+                if let Some(value) = env.lookup(*symbol) {
+                    match value {
+                        Expr::Macro(transformer) => expand_identifier(&transformer, expr, env),
+                        value => Ok(value),
+                    }
+                } else if env.has_native(symbol.as_str()) {
+                    Ok(Expr::Builtin(symbol.to_string()))
+                } else {
+                    Err(format!("Undefined symbol: {}", symbol).into())
+                }
             }
-            Expr::Number(_) => Ok(expr.clone()),
+            Expr::Number(_)
+            | Expr::Bool(_)
+            | Expr::Str(_)
+            | Expr::Char(_)
+            | Expr::Values(_)
+            | Expr::Builtin(_)
+            | Expr::Lambda(_)
+            | Expr::Port(_)
+            | Expr::TimePoint(_)
+            | Expr::Duration(_)
+            | Expr::RandomSource(_)
+            | Expr::Regexp(_)
+            | Expr::Thread(_)
+            | Expr::Mutex(_)
+            | Expr::Coroutine(_)
+            | Expr::Memoized(_)
+            | Expr::Void
+            | Expr::Eof
+            | Expr::Parameter(_)
+            | Expr::Vector(_)
+            | Expr::Bytevector(_)
+            | Expr::ErrorObject(_)
+            | Expr::Macro(_)
+            | Expr::CharSet(_)
+            | Expr::Environment(_)
+            | Expr::HashTable(_)
+            | Expr::CircularList(..)
+            | Expr::WeakPair(_) => Ok(expr.clone()),
             Expr::List(list) => {
                 if list.is_empty() {
-                    return Err("Cannot evaluate an empty list".to_string());
+                    return Err("Cannot evaluate an empty list".into());
                 }
     
+                if let Some(expansion) = expand_once(expr, env)? {
+                    return match tail {
+                        true => eval_tail(&expansion, env),
+                        false => eval(&expansion, env).map(Step::Value),
+                    };
+                }
+
                 let first_expr = &list[0];
                 match first_expr {
-                    Expr::Symbol(symbol) => match &symbol[..] {
+                    Expr::Symbol(symbol) => match symbol.as_str() {
+                        "quote" => {
+                            if list.len() != 2 {
+                                return Err("Invalid number of arguments for 'quote'".into());
+                            }
+                            Ok(list[1].clone())
+                        }
                         "define" => {
                             if list.len() != 3 {
-                                return Err("Invalid number of arguments for 'define'".to_string());
+                                return Err("Invalid number of arguments for 'define'".into());
+                            }
+                            let var_name = match &list[1] {
+                                Expr::Symbol(name) => name,
+                                _ => return Err("Expected a symbol for the variable name".into()),
+                            };
+                            let value = eval(&list[2], env)?;
+                            if let Expr::Lambda(lambda) = &value {
+                                lambda.name.lock().unwrap().get_or_insert(*var_name);
+                            }
+                            env.define(*var_name, value);
+                            Ok(Expr::Symbol(*var_name))
+                        }
+                        "set!" => {
+                            if list.len() != 3 {
+                                return Err("Invalid number of arguments for 'set!'".into());
                             }
                             let var_name = match &list[1] {
                                 Expr::Symbol(name) => name,
-                                _ => return Err("Expected a symbol for the variable name".to_string()),
+                                _ => return Err("Expected a symbol for the variable name".into()),
                             };
+                            if let Some(Expr::Macro(transformer)) = env.lookup(*var_name) {
+                                return eval_macro_set(&transformer, expr, &list[2], env).map(Step::Value);
+                            }
                             let value = eval(&list[2], env)?;
-                            env.symbols.insert(var_name.clone(), value);
-                            Ok(Expr::Symbol(var_name.clone()))
+                            env.assign(*var_name, value)?;
+                            Ok(Expr::Void)
                         }
                         "print" => {
                             if list.len() != 2 {
-                                return Err("Invalid number of arguments for 'print'".to_string());
+                                return Err("Invalid number of arguments for 'print'".into());
                             }
                             let value = eval(&list[1], env)?;
-                            println!("{}", value);
+                            write_to_port(&format!("{}\n", value), None, "print", env)?;
                             Ok(value)
                         }
+                        "lambda" => {
+                            if list.len() < 3 {
+                                return Err("Invalid number of arguments for 'lambda'".into());
+                            }
+                            Ok(Expr::Lambda(Arc::new(Lambda {
+                                params: list[1].clone(),
+                                body: list[2..].to_vec(),
+                                scope: env.scope.clone(),
+                                source: env.location.clone(),
+                                name: Mutex::new(None),
+                            })))
+                        }
+                        "if" => {
+                            if list.len() != 3 && list.len() != 4 {
+                                return Err("Invalid number of arguments for 'if'".into());
+                            }
+                            let branch = match is_truthy(&eval(&list[1], env)?) {
+                                true => &list[2],
+                                false if list.len() == 4 => &list[3],
+                                false => return Ok(Step::Value(Expr::Void)),
+                            };
+                            match tail {
+                                true => return eval_tail(branch, env),
+                                false => eval(branch, env),
+                            }
+                        }
+                        "begin" if tail => return eval_body_tail(&list[1..], env),
+                        "begin" => eval_body(&list[1..], env),
+                        "cond-expand" => eval_cond_expand(&list[1..], env),
+                        "let-values" => eval_let_values(&list[1..], env),
+                        "define-values" => eval_define_values(&list[1..], env),
+                        "receive" => eval_receive(&list[1..], env),
+                        "step" => eval_step(&list[1..], env),
+                        "assert" => eval_assert(&list[1..], env),
+                        "parameterize" => eval_parameterize(&list[1..], env),
+                        "fluid-let" => eval_fluid_let(&list[1..], env),
+                        "the" => eval_the(&list[1..], env),
+                        "define-syntax" => eval_define_syntax(&list[1..], env),
+                        "let-syntax" => eval_let_syntax(&list[1..], env, "let-syntax"),
+                        "letrec-syntax" => eval_let_syntax(&list[1..], env, "letrec-syntax"),
+                        "test-equal" => eval_test_equal(&list[1..], env),
+                        "test-assert" => eval_test_assert(&list[1..], env),
+                        "test-error" => eval_test_error(&list[1..], env),
                         _ => {
-                            if env.functions.contains_key(symbol) {
-                                let func = env.functions[symbol];
-                                let args: Result<Vec<Expr>, String> =
-                                    list[1..].iter().map(|expr| eval(expr, env)).collect();
-                                match args {
-                                    Ok(evaluated_args) => func(&evaluated_args, env),
-                                    Err(e) => Err(e),
+                            // Lexical bindings shadow natives, as they do when the symbol is evaluated
+                            let procedure = match env.lookup(*symbol) {
+                                Some(procedure) if is_procedure(&procedure) => procedure,
+                                Some(value) => return Err(format!("Not a procedure: {}", value).into()),
+                                None if env.has_native(symbol.as_str()) => Expr::Builtin(symbol.to_string()),
+                                None => return Err(format!("Undefined function: {}", symbol).into()),
+                            };
+
+                            if let (true, false, Expr::Lambda(lambda)) = (tail, env.profiling, &procedure) {
+                                let args = list[1..].iter().map(|expr| eval(expr, env)).collect::<Result<_, _>>()?;
+                                return Ok(Step::TailCall(lambda.clone(), args));
+                            }
+
+                            if env.profiling {
+                                let entry = env.profile_stats.entry(*symbol).or_default();
+                                entry.calls += 1;
+                                entry.active += 1;
+
+                                let start = std::time::Instant::now();
+                                let result = call_with_arguments(&procedure, &list[1..], env);
+                                let entry = env.profile_stats.entry(*symbol).or_default();
+                                entry.active -= 1;
+                                if entry.active == 0 {
+                                    entry.time += start.elapsed();
                                 }
+                                result
                             } else {
-                                Err(format!("Undefined function: {}", symbol))
+                                call_with_arguments(&procedure, &list[1..], env)
                             }
                         }
                     },
                     _ => {
-                        let evaluated_list: Result<Vec<Expr>, String> =
-                            list.iter().map(|expr| eval(expr, env)).collect();
-                        evaluated_list.map(|elems| Expr::List(elems))
+                        let mut evaluated_list: Vec<Expr> =
+                            list.iter().map(|expr| eval(expr, env)).collect::<Result<_, _>>()?;
+                        if let (true, Expr::Lambda(lambda)) = (tail, &evaluated_list[0]) {
+                            let lambda = lambda.clone();
+                            return Ok(Step::TailCall(lambda, evaluated_list.split_off(1)));
+                        }
+                        if is_procedure(&evaluated_list[0]) {
+                            apply_procedure(&evaluated_list[0], &evaluated_list[1..], env)
+                        } else {
+                            Ok(Expr::List(evaluated_list))
+                        }
                     }
                 }
             }
-        }
+        };
+        value.map(Step::Value)
     }
     
     
@@ -1,26 +1,158 @@
 use std::io::{self, Write};
-use lisp_interpreter::interpreter::{Environment, tokenize, parse, eval};
+use lisp_interpreter::interpreter::{
+    CountingAllocator, Environment, Expr, LispError, SourceLocation, eval, eval_all_str, parse, pretty_print,
+    terminal_width, tokenize_with_positions,
+};
 
-fn interpret(input: &str, env: &mut Environment) -> Result<String, String> {
-    let tokens = tokenize(input);
-    let (parsed_expr, _) = parse(&tokens)?;
+// Lets sandbox-eval enforce memory-limit-bytes
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
-    let result = eval(&parsed_expr, env)?;
-    Ok(format!("{}", result))
+// Void results are not printed, like the REPLs of Racket and Guile. Lists too long for one line
+// are pretty-printed
+fn interpret(input: &str, env: &mut Environment) -> Result<Option<String>, LispError> {
+    // Frames left behind by errors that were caught earlier
+    env.take_backtrace();
+    match eval_all_str(input, env)? {
+        Expr::Void => Ok(None),
+        result @ Expr::List(_) => Ok(Some(pretty_print(&result, terminal_width()))),
+        result => Ok(Some(format!("{}", result))),
+    }
 }
 
-fn main() {
-    let mut env = Environment::new();
+// The error followed by the procedures it unwound through
+fn describe_error(error: &LispError, env: &mut Environment) -> String {
+    let mut message = error.to_string();
+    for frame in env.take_backtrace() {
+        message.push_str(&format!("\n  {}", frame));
+    }
+    message
+}
+
+// Evaluates every form in the file, reporting errors with the line the failing form starts on
+fn run_file(path: &str, env: &mut Environment) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let positioned = tokenize_with_positions(&source);
+    let tokens: Vec<String> = positioned.iter().map(|(token, _, _)| token.clone()).collect();
+
+    let mut remaining = &tokens[..];
+    while !remaining.is_empty() {
+        let (_, line, column) = positioned[tokens.len() - remaining.len()];
+        let (expr, rest) = parse(remaining).map_err(|e| format!("{}:{}: {}", path, line, e))?;
+        env.take_backtrace();
+        env.set_source_location(Some(SourceLocation { file: path.to_string(), line, column }));
+        let result = eval(&expr, env);
+        env.set_source_location(None);
+        result.map_err(|e| format!("{}:{}: {}", path, line, describe_error(&e, env)))?;
+        remaining = rest;
+    }
+
+    Ok(())
+}
+
+fn repl(env: &mut Environment) {
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        if io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        // `,step expr` is shorthand for `(step expr)`, and `,help name` for `(describe 'name)`
+        if let Some(expr) = input.trim().strip_prefix(",step") {
+            input = format!("(step {})", expr);
+        } else if let Some(name) = input.trim().strip_prefix(",help") {
+            input = format!("(describe '{})", name.trim());
+        }
 
-        match interpret(&input, &mut env) {
-            Ok(result) => println!("{}", result),
-            Err(e) => eprintln!("Error: {}", e),
+        match interpret(&input, env) {
+            Ok(Some(result)) => println!("{}", result),
+            Ok(None) => {}
+            Err(e) => eprintln!("Error: {}", describe_error(&e, env)),
         }
     }
 }
+
+enum Action {
+    Eval(String),
+    Script(String, Vec<String>),
+}
+
+// `-e expr` / `--eval expr` and `--script file args...` may be repeated and run in order, sharing one
+// environment; a bare file name runs that file with everything after it as its arguments
+fn parse_actions(args: &[String]) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    let mut rest = args;
+
+    while let Some((arg, tail)) = rest.split_first() {
+        match arg.as_str() {
+            "-e" | "--eval" => {
+                let (expr, tail) = tail.split_first().ok_or_else(|| format!("{} requires an expression", arg))?;
+                actions.push(Action::Eval(expr.clone()));
+                rest = tail;
+            }
+            "--script" => {
+                let (path, tail) = tail.split_first().ok_or("--script requires a file name")?;
+                let script_args = tail.iter().take_while(|arg| !arg.starts_with('-')).count();
+                actions.push(Action::Script(path.clone(), tail[..script_args].to_vec()));
+                rest = &tail[script_args..];
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option: {}", flag)),
+            path => {
+                actions.push(Action::Script(path.to_string(), tail.to_vec()));
+                rest = &[];
+            }
+        }
+    }
+
+    Ok(actions)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut env = Environment::new();
+
+    let actions = match parse_actions(&args[1..]) {
+        Ok(actions) => actions,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Usage: {} [-e expr]... [[--script] file [args...]]", args[0]);
+            std::process::exit(2);
+        }
+    };
+
+    if actions.is_empty() {
+        repl(&mut env);
+        return;
+    }
+
+    let mut failed = false;
+    for action in actions {
+        let result = match action {
+            Action::Eval(expr) => interpret(&expr, &mut env)
+                .map(|result| result.into_iter().for_each(|result| println!("{}", result)))
+                .map_err(|e| describe_error(&e, &mut env)),
+            Action::Script(path, script_args) => {
+                // The script sees its own name and arguments as (command-line)
+                env.set_command_line(std::iter::once(path.clone()).chain(script_args).collect());
+                run_file(&path, &mut env)
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            failed = true;
+            break;
+        }
+    }
+
+    env.run_exit_handlers();
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,246 @@
+; The part of the standard library written in Lisp itself. It is compiled into the interpreter
+; and evaluated by Environment::new, after the native procedures are registered. A string ahead
+; of a procedure's body is its documentation, as describe shows it.
+
+; Closures over state that set! updates
+
+(define (make-accumulator total)
+  "A procedure that adds its argument to a running total and returns the total."
+  (lambda (amount)
+    (set! total (+ total amount))
+    total))
+
+(define (make-counter)
+  "A procedure returning 0, 1, 2, ... on successive calls."
+  (let ((next 0))
+    (lambda ()
+      (let ((current next))
+        (set! next (+ next 1))
+        current))))
+
+; (define s (make-stack)) (s 'push 1) (s 'pop) => 1
+(define (make-stack)
+  "A stack answering 'push, 'pop, 'peek, 'empty? and 'size messages."
+  (let ((items '()))
+    (lambda (message . args)
+      (cond ((equal? message 'push) (set! items (cons (car args) items)))
+            ((equal? message 'pop)
+             (if (= (list-length items) 0)
+                 (error "Cannot pop an empty stack")
+                 (let ((top (car items)))
+                   (set! items (cdr items))
+                   top)))
+            ((equal? message 'peek)
+             (if (= (list-length items) 0) (error "Cannot peek at an empty stack") (car items)))
+            ((equal? message 'empty?) (= (list-length items) 0))
+            ((equal? message 'size) (list-length items))
+            (else (error "Unknown stack operation" message))))))
+
+; (define q (make-queue)) (q 'enqueue 1) (q 'dequeue) => 1
+(define (make-queue)
+  "A queue answering 'enqueue, 'dequeue, 'peek, 'empty? and 'size messages."
+  (let ((items '()))
+    (define (add-last items item)
+      (if (= (list-length items) 0)
+          (list item)
+          (cons (car items) (add-last (cdr items) item))))
+    (lambda (message . args)
+      (cond ((equal? message 'enqueue) (set! items (add-last items (car args))))
+            ((equal? message 'dequeue)
+             (if (= (list-length items) 0)
+                 (error "Cannot dequeue from an empty queue")
+                 (let ((front (car items)))
+                   (set! items (cdr items))
+                   front)))
+            ((equal? message 'peek)
+             (if (= (list-length items) 0) (error "Cannot peek at an empty queue") (car items)))
+            ((equal? message 'empty?) (= (list-length items) 0))
+            ((equal? message 'size) (list-length items))
+            (else (error "Unknown queue operation" message))))))
+
+; List operations. Recursion that is not in tail position still grows the stack, so these lean
+; on the native vector and SRFI-1 procedures for their loops rather than recursing once per element
+
+(define (null? obj) "Whether obj is the empty list." (equal? obj '()))
+
+(define (length lst) "The number of elements in a list." (list-length lst))
+
+(define (caar pair) (car (car pair)))
+(define (cadr pair) (car (cdr pair)))
+(define (cdar pair) (cdr (car pair)))
+(define (cddr pair) (cdr (cdr pair)))
+(define (caddr pair) (car (cddr pair)))
+(define (cdddr pair) (cdr (cddr pair)))
+(define (cadddr pair) (car (cdddr pair)))
+
+(define (list-tail lst k) "All but the first k elements of a list." (drop lst k))
+
+(define (list-ref lst k) "The element at index k." (car (drop lst k)))
+
+(define (last lst) "The final element of a list." (car (drop lst (- (length lst) 1))))
+
+(define (append . lists)
+  "The elements of the lists, one after another."
+  (if (any circular-list? lists) (error "A circular list has no end") (flatten lists 1)))
+
+(define (concatenate lists) "The elements of a list of lists, one after another." (flatten 1 lists))
+
+(define (reverse lst)
+  "The elements of a list in the opposite order."
+  (vector->list (vector-reverse (list->vector lst))))
+
+(define (map f lst . lists)
+  "The results of f on the elements at each position of the lists."
+  (vector->list (apply vector-map f (list->vector lst) (%map1 list->vector lists))))
+
+(define (%map1 f lst) (vector->list (vector-map f (list->vector lst))))
+
+(define (for-each f lst . lists)
+  "Calls f on the elements at each position of the lists, in order."
+  (apply vector-for-each f (list->vector lst) (%map1 list->vector lists)))
+
+(define (filter pred lst)
+  "The elements that satisfy pred."
+  (call-with-values (lambda () (partition pred lst)) (lambda (in out) in)))
+
+(define (remove pred lst)
+  "The elements that do not satisfy pred."
+  (call-with-values (lambda () (partition pred lst)) (lambda (in out) out)))
+
+; (kons element accumulator), left to right
+(define (fold kons knil lst . lists)
+  "Combines the elements left to right with (kons element acc), starting from knil."
+  (if (null? lists)
+      (reduce kons knil (cons knil lst))
+      (reduce (lambda (elements acc) (apply kons (append elements (list acc))))
+              knil
+              (cons knil (apply zip lst lists)))))
+
+(define (fold-right kons knil lst . lists)
+  "Combines the elements right to left with (kons element acc), starting from knil."
+  (apply fold kons knil (reverse lst) (%map1 reverse lists)))
+
+(define (find-tail pred lst)
+  "The list from the first element satisfying pred, or #f."
+  (let ((tail (drop-while (lambda (item) (not (pred item))) lst)))
+    (if (null? tail) #f tail)))
+
+(define (find pred lst)
+  "The first element satisfying pred, or #f."
+  (let ((tail (find-tail pred lst)))
+    (if tail (car tail) #f)))
+
+(define (member x lst . compare)
+  "The list from the first element equal to x, or #f."
+  (let ((same? (if (null? compare) equal? (car compare))))
+    (find-tail (lambda (item) (same? x item)) lst)))
+
+(define (assoc key alist . compare)
+  "The first entry of an association list whose key is equal to key, or #f."
+  (let ((same? (if (null? compare) equal? (car compare))))
+    (find (lambda (entry) (same? key (car entry))) alist)))
+
+(define (alist-delete key alist . compare)
+  "The association list without the entries whose key is equal to key."
+  (let ((same? (if (null? compare) equal? (car compare))))
+    (remove (lambda (entry) (same? key (car entry))) alist)))
+
+(define (alist-update key value alist . compare)
+  "The association list with key bound to value, in a new entry at the front."
+  (cons (cons key value) (apply alist-delete key alist compare)))
+
+(define (alist-copy alist)
+  "A copy of an association list with fresh entries."
+  (%map1 (lambda (entry) (cons (car entry) (cdr entry))) alist))
+
+; Lists and strings are copied values rather than shared objects, so there is no finer
+; notion of identity for eq? and eqv? to check than equal?
+(define (memq x lst) (member x lst))
+(define (memv x lst) (member x lst))
+(define (assq key alist) (assoc key alist))
+(define (assv key alist) (assoc key alist))
+
+; Combining procedures
+
+(define (compose . procs)
+  "A procedure applying the procedures right to left: ((compose f g) x) is (f (g x))."
+  (cond ((null? procs) (lambda (x) x))
+        ((null? (cdr procs)) (car procs))
+        (else (let ((first (car procs))
+                    (rest (apply compose (cdr procs))))
+                (lambda args (first (apply rest args)))))))
+
+(define (compose-list procs)
+  "The composition of a list of procedures, applied right to left."
+  (apply compose procs))
+
+(define (iterate f n)
+  "A procedure applying f n times: ((iterate f 2) x) is (f (f x))."
+  (if (not (and (integer? n) (>= n 0)))
+      (error "iterate requires a non-negative integer count" n))
+  (lambda (x) (fold (lambda (i acc) (f acc)) x (list-tabulate n (lambda (i) i)))))
+
+(define (pipe x . procs)
+  "Passes x through the procedures left to right: (pipe x f g) is (g (f x))."
+  (fold (lambda (proc acc) (proc acc)) x procs))
+
+(define (curry proc)
+  "A procedure taking the first argument of proc and returning one that takes the rest."
+  (lambda (first)
+    (lambda rest (apply proc first rest))))
+
+(define (partial proc . fixed)
+  "proc with its first arguments fixed: ((partial f a) b) is (f a b)."
+  (lambda rest (apply proc (append fixed rest))))
+
+(define (negate pred)
+  "A predicate true exactly when pred is false."
+  (lambda args (not (apply pred args))))
+
+(define (conjoin . preds)
+  "A predicate true when every one of preds is."
+  (lambda args (if (every (lambda (pred) (apply pred args)) preds) #t #f)))
+
+(define (disjoin . preds)
+  "A predicate true when any one of preds is."
+  (lambda args (if (any (lambda (pred) (apply pred args)) preds) #t #f)))
+
+; Errors. The value a handler returns becomes the value of with-exception-handler
+
+(define (ignore-errors thunk)
+  "The value of thunk, or #f if it raises."
+  (with-exception-handler (lambda (condition) #f) thunk))
+
+(define (ignore-errors/default default thunk)
+  "The value of thunk, or default if it raises."
+  (with-exception-handler (lambda (condition) default) thunk))
+
+; Number predicates
+
+(define (zero? x) "Whether x is zero." (= x 0))
+(define (positive? x) "Whether x is greater than zero." (> x 0))
+(define (negative? x) "Whether x is less than zero." (< x 0))
+
+; Output
+
+(define (%cell->string cell)
+  (call-with-string-output-port (lambda (port) (display cell port))))
+
+; (format-table '(("Alice" 30) ("Bob" 25)) '("Name" "Age"))
+(define (format-table rows headers)
+  "Displays rows under headers in aligned columns, and returns the table as a string."
+  (let* ((cells (%map1 (lambda (row) (%map1 %cell->string row)) (cons headers rows)))
+         (widths (apply map
+                        (lambda column
+                          (fold (lambda (cell widest) (if (> (string-length cell) widest) (string-length cell) widest))
+                                0
+                                column))
+                        cells))
+         (line (lambda (row)
+                 (string-trim-right (string-join (map string-pad-right row widths) " | "))))
+         (separator (string-join (map (lambda (width) (make-string width #\-)) widths) "-+-"))
+         (table (string-append
+                 (string-join (cons (line (car cells)) (cons separator (%map1 line (cdr cells)))) "\n")
+                 "\n")))
+    (display table)
+    table))
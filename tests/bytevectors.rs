@@ -0,0 +1,59 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn bytevector_append_concatenates() {
+    assert_eq!(run("(bytevector-append (bytevector 1) (bytevector 2 3) (bytevector))"), "#u8(1 2 3)");
+}
+
+#[test]
+fn bytevector_copy_bang_copies_a_range_into_place() {
+    assert_eq!(run("(define b (bytevector 1 2 3 4 5)) (bytevector-copy! b 0 (bytevector 9 8 7) 1) b"), "#u8(8 7 3 4 5)");
+    assert_eq!(run("(define b (bytevector 1 2 3 4 5)) (bytevector-copy! b 1 b 0 3) b"), "#u8(1 1 2 3 5)");
+}
+
+#[test]
+fn utf8_conversions_take_a_range() {
+    assert_eq!(run("(utf8->string (string->utf8 \"héllo\"))"), "\"héllo\"");
+    assert_eq!(run("(utf8->string (bytevector 104 105 106) 1 3)"), "\"ij\"");
+    assert_eq!(run("(string->utf8 \"abcd\" 1 3)"), "#u8(98 99)");
+}
+
+#[test]
+fn utf8_to_string_rejects_invalid_utf8() {
+    assert_eq!(
+        run_err("(utf8->string (bytevector 255))"),
+        "Invalid UTF-8 in utf8->string: invalid utf-8 sequence of 1 bytes from index 0"
+    );
+}
+
+#[test]
+fn s8_ref_reads_signed_bytes() {
+    assert_eq!(run("(list (bytevector-s8-ref (bytevector 255 1) 0) (bytevector-s8-ref (bytevector 255 1) 1))"), "(-1 1)");
+}
+
+#[test]
+fn multi_byte_access_follows_the_endianness() {
+    assert_eq!(
+        run("(list (bytevector-u16-ref (bytevector 1 2) 0 'big) (bytevector-u16-ref (bytevector 1 2) 0 'little))"),
+        "(258 513)"
+    );
+    assert_eq!(
+        run("(define b (make-bytevector 4 0)) (bytevector-u32-set! b 0 305419896 'big) (list b (bytevector-u32-ref b 0 'little))"),
+        "(#u8(18 52 86 120) 2018915346)"
+    );
+    assert_eq!(
+        run("(define b (make-bytevector 8 0)) (bytevector-u64-set! b 0 258 'little) (list b (bytevector-u64-ref b 0 'little))"),
+        "(#u8(2 1 0 0 0 0 0 0) 258)"
+    );
+}
+
+#[test]
+fn multi_byte_access_is_checked() {
+    assert_eq!(run_err("(bytevector-u16-ref (bytevector 1) 0 'big)"), "Index out of range for bytevector-u16-ref: 0");
+    assert_eq!(
+        run_err("(bytevector-u16-ref (bytevector 1 2) 0 'middle)"),
+        "'bytevector-u16-ref' requires the endianness 'big or 'little"
+    );
+}
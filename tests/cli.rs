@@ -0,0 +1,181 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn interpreter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lisp_interpreter")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn exit_ends_the_process_with_its_code() {
+    let output = interpreter(&["-e", "(exit 3)", "-e", "(display \"not reached\")"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "");
+
+    assert_eq!(interpreter(&["-e", "(exit)"]).status.code(), Some(0));
+    assert_eq!(interpreter(&["-e", "(exit #f)"]).status.code(), Some(1));
+}
+
+#[test]
+fn exit_runs_the_exit_handlers_most_recent_first() {
+    let output = interpreter(&[
+        "-e",
+        "(at-exit (lambda () (display \"first\")))",
+        "-e",
+        "(at-exit (lambda () (display \"second \")))",
+        "-e",
+        "(exit 0)",
+    ]);
+    assert_eq!(stdout(&output), "second first");
+}
+
+#[test]
+fn emergency_exit_skips_the_exit_handlers() {
+    let output = interpreter(&["-e", "(at-exit (lambda () (display \"handler\")))", "-e", "(emergency-exit 4)"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(stdout(&output), "");
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+// Writes `source` to a script file of its own and returns the path
+fn script(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(format!("lisp-interpreter-{}-{}.lisp", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn a_script_runs_without_echoing_results() {
+    let path = script("echo", "(define x 2)\n(+ x 1)\n(display (* x 21))\n");
+    let output = interpreter(&["--script", &path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42");
+    assert_eq!(stdout(&interpreter(&[&path])), "42");
+}
+
+#[test]
+fn a_script_sees_its_name_and_arguments() {
+    let path = script("args", "(write (command-line))");
+    let output = interpreter(&[&path, "one", "--two"]);
+    assert_eq!(stdout(&output), format!("({:?} \"one\" \"--two\")", path));
+    // After --script, the arguments end at the next option
+    let output = interpreter(&["--script", &path, "one", "-e", "(newline)"]);
+    assert_eq!(stdout(&output), format!("({:?} \"one\")\n", path));
+}
+
+#[test]
+fn a_failing_script_reports_the_line_and_exits_with_1() {
+    let path = script("failing", "(define (inner x) (car x))\n\n(display \"before\")\n(inner 5)\n(display \"after\")\n");
+    let output = interpreter(&["--script", &path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "before");
+    assert!(stderr(&output).starts_with(&format!("Error: {}:4: Invalid argument type for car\n  in inner defined at {}:1:1", path, path)), "{}", stderr(&output));
+}
+
+#[test]
+fn eval_prints_each_result() {
+    let output = interpreter(&["-e", "(+ 40 2)", "--eval", "'(a b)", "-e", "(define x 1)"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n(a b)\nx\n");
+}
+
+#[test]
+fn eval_and_scripts_share_one_environment() {
+    let path = script("prelude", "(define (main) (display \"main ran\"))");
+    let output = interpreter(&["--script", &path, "-e", "(main)"]);
+    assert_eq!(stdout(&output), "main ran");
+}
+
+#[test]
+fn eval_stops_at_the_first_error() {
+    let output = interpreter(&["-e", "(car 1)", "-e", "(display \"not reached\")"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "Error: Invalid argument type for car\n");
+}
+
+#[test]
+fn bad_options_print_the_usage() {
+    let output = interpreter(&["-e"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("Error: -e requires an expression\nUsage: "));
+    assert_eq!(interpreter(&["--bogus"]).status.code(), Some(2));
+}
+
+#[test]
+fn void_results_are_not_printed() {
+    let output = interpreter(&["-e", "(void)", "-e", "(vector-set! (vector 1) 0 2)", "-e", "(+ 1 1)"]);
+    assert_eq!(stdout(&output), "2\n");
+}
+
+// Timings are kept for the whole process, so these run in a process of their own
+#[test]
+fn with_timing_reports_to_stderr_and_returns_the_result() {
+    let output = interpreter(&["-e", "(with-timing \"work\" (lambda () 42))"]);
+    assert_eq!(stdout(&output), "42\n");
+    assert!(stderr(&output).starts_with("work: ") && stderr(&output).ends_with("ms\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn timing_report_lists_each_label_until_reset() {
+    let output = interpreter(&[
+        "-e",
+        "(with-timing \"work\" (lambda () 1))",
+        "-e",
+        "(with-timing \"work\" (lambda () 2))",
+        "-e",
+        "(timing-report)",
+        "-e",
+        "(reset-timings!)",
+        "-e",
+        "(timing-report)",
+    ]);
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5, "{:?}", lines);
+    assert!(lines[2].starts_with("label   calls"));
+    assert!(lines[3].starts_with("work        2"));
+    assert!(lines[4].starts_with("label   calls"));
+}
+
+#[test]
+fn long_list_results_are_pretty_printed_at_the_terminal_width() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lisp_interpreter"))
+        .args(["-e", "'(define (f x) (if (> x 0) (* x 2) (- x 1)))"])
+        .env("COLUMNS", "25")
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "(define (f x)\n  (if (> x 0)\n      (* x 2)\n      (- x 1)))\n");
+}
+
+#[test]
+fn the_repl_describes_a_name_after_help() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lisp_interpreter"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b",help car\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(stdout(&output), "> (car list) → obj: The first element of a list.\n> ");
+}
+
+// The gensym counter is shared by the whole process
+#[test]
+fn reset_gensym_counter_restarts_the_numbering() {
+    let output = interpreter(&[
+        "-e",
+        "(symbol-gensym \"a\")",
+        "-e",
+        "(reset-gensym-counter!)",
+        "-e",
+        "(list (symbol-gensym \"tmp\") (symbol-gensym 'x))",
+    ]);
+    assert_eq!(stdout(&output), "a#0\n(tmp#0 x#1)\n");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn an_accumulator_keeps_its_running_total() {
+    assert_eq!(run("(define acc (make-accumulator 5)) (list (acc 10) (acc 10))"), "(15 25)");
+}
+
+#[test]
+fn accumulators_do_not_share_state() {
+    assert_eq!(run("(define a (make-accumulator 0)) (define b (make-accumulator 100)) (a 1) (list (a 1) (b 1))"), "(2 101)");
+}
+
+#[test]
+fn a_counter_counts_from_zero() {
+    assert_eq!(run("(define c (make-counter)) (list (c) (c) (c))"), "(0 1 2)");
+    assert_eq!(run("(define c (make-counter)) (define d (make-counter)) (c) (c) (d)"), "0");
+}
+
+#[test]
+fn a_stack_pops_the_last_item_pushed() {
+    assert_eq!(
+        run("(define s (make-stack))
+             (s 'push 1)
+             (s 'push 2)
+             (list (s 'size) (s 'peek) (s 'pop) (s 'pop) (s 'empty?))"),
+        "(2 2 2 1 #t)"
+    );
+}
+
+#[test]
+fn a_queue_dequeues_the_first_item_enqueued() {
+    assert_eq!(
+        run("(define q (make-queue))
+             (q 'enqueue 1)
+             (q 'enqueue 2)
+             (list (q 'size) (q 'peek) (q 'dequeue) (q 'dequeue) (q 'empty?))"),
+        "(2 1 1 2 #t)"
+    );
+}
+
+#[test]
+fn empty_stacks_and_queues_refuse_to_give_items() {
+    assert_eq!(run_err("((make-stack) 'pop)"), "Cannot pop an empty stack");
+    assert_eq!(run_err("((make-queue) 'dequeue)"), "Cannot dequeue from an empty queue");
+    assert_eq!(run_err("((make-stack) 'shuffle)"), "Unknown stack operation shuffle");
+}
+
+#[test]
+fn cons_onto_a_non_list_builds_a_dotted_pair() {
+    assert_eq!(run("(define p (cons 1 2)) (list p (car p) (cdr p))"), "((1 . 2) 1 2)");
+    assert_eq!(run("(list (cdr '(a . b)) (cdr '(a b . c)) (cons 0 '(1 2)))"), "(b (b . c) (0 1 2))");
+}
+
+#[test]
+fn dotted_lists_have_no_length() {
+    assert_eq!(run_err("(length (cons 1 2))"), "A dotted list has no length");
+    assert_eq!(run_err("(list-length '(1 2 . 3))"), "A dotted list has no length");
+}
@@ -0,0 +1,40 @@
+// Helpers shared by the integration tests. Each test file uses only some of them
+#![allow(dead_code)]
+
+use lisp_interpreter::interpreter::{display_string, eval_all_str, Environment, Expr, LispError};
+
+// Evaluates every form of `input` in a fresh environment and writes the last value as the REPL does
+pub fn run(input: &str) -> String {
+    eval_in(input, &mut Environment::new())
+}
+
+pub fn eval_in(input: &str, env: &mut Environment) -> String {
+    match eval_all_str(input, env) {
+        Ok(value) => value.to_string(),
+        Err(error) => panic!("{} raised {}", input, error),
+    }
+}
+
+pub fn try_run(input: &str) -> Result<Expr, LispError> {
+    eval_all_str(input, &mut Environment::new())
+}
+
+// The message of the error `input` raises
+pub fn run_err(input: &str) -> String {
+    match try_run(input) {
+        Ok(value) => panic!("{} returned {} instead of raising", input, value),
+        Err(error) => error.to_string(),
+    }
+}
+
+// What `input` writes to current-output-port
+pub fn output(input: &str) -> String {
+    let wrapped = format!(
+        "(define %port (open-output-string)) (with-output-redirection %port (lambda () {})) (get-output-string %port)",
+        input
+    );
+    match eval_all_str(&wrapped, &mut Environment::new()) {
+        Ok(text) => display_string(&text),
+        Err(error) => panic!("{} raised {}", input, error),
+    }
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::Environment;
+
+#[test]
+fn thread_join_returns_the_thunk_result() {
+    assert_eq!(run("(define t (make-thread (lambda () (+ 40 2)))) (thread-start! t) (thread-join! t)"), "42");
+    assert_eq!(run("(thread? (make-thread (lambda () 1)))"), "#t");
+}
+
+#[test]
+fn thread_join_reraises_the_thread_error() {
+    assert_eq!(run_err("(define t (make-thread (lambda () (car 1)))) (thread-start! t) (thread-join! t)"), "Invalid argument type for car");
+}
+
+#[test]
+fn threads_must_be_started_once_before_joining() {
+    assert_eq!(run_err("(thread-join! (make-thread (lambda () 1)))"), "Thread has not been started");
+    assert_eq!(
+        run_err("(define t (make-thread (lambda () 1))) (thread-start! t) (thread-start! t)"),
+        "Thread has already been started"
+    );
+}
+
+#[test]
+fn threads_share_globals_through_a_mutex() {
+    let increment = "(mutex-lock! m) (set! total (+ total 1)) (mutex-unlock! m) ".repeat(100);
+    assert_eq!(
+        run(&format!(
+            "(define total 0)
+             (define m (make-mutex))
+             (define work (lambda () {increment}))
+             (define t1 (make-thread work)) (define t2 (make-thread work))
+             (define t3 (make-thread work)) (define t4 (make-thread work))
+             (thread-start! t1) (thread-start! t2) (thread-start! t3) (thread-start! t4)
+             (thread-join! t1) (thread-join! t2) (thread-join! t3) (thread-join! t4)
+             total"
+        )),
+        "400"
+    );
+}
+
+#[test]
+fn thread_sleep_takes_milliseconds() {
+    let start = std::time::Instant::now();
+    run("(thread-sleep! 20)");
+    assert!(start.elapsed() >= std::time::Duration::from_millis(20));
+    run_err("(thread-sleep! -1)");
+}
+
+#[test]
+fn coroutines_yield_values_to_resume() {
+    assert_eq!(
+        run("(define gen (make-coroutine (lambda () (yield 1) (yield 2) 'ignored)))
+             (list (resume gen) (resume gen) (resume gen) (resume gen))"),
+        "(1 2 #f #f)"
+    );
+}
+
+#[test]
+fn resume_sends_a_value_back_to_yield() {
+    assert_eq!(
+        run("(define echo (make-coroutine (lambda ()
+                                            (define first (yield 'ready))
+                                            (define second (yield (+ first first)))
+                                            (yield (+ second second)))))
+             (list (resume echo) (resume echo 5) (resume echo 21))"),
+        "(ready 10 42)"
+    );
+}
+
+#[test]
+fn coroutine_errors_reach_resume() {
+    assert_eq!(run_err("(resume (make-coroutine (lambda () (car 1))))"), "Invalid argument type for car");
+    assert_eq!(run_err("(yield 1)"), "'yield' used outside of a coroutine");
+}
+
+#[test]
+fn dropping_an_unfinished_coroutine_stops_its_thread() {
+    // Each coroutine is abandoned after its first value; dropping it must not leave the test waiting
+    let mut env = Environment::new();
+    eval_in(
+        "(define count-from (lambda (i) (yield i) (count-from (+ i 1))))
+         (define first-value (lambda () (resume (make-coroutine (lambda () (count-from 0))))))",
+        &mut env,
+    );
+    for _ in 0..20 {
+        assert_eq!(eval_in("(first-value)", &mut env), "0");
+    }
+    assert_eq!(eval_in("(coroutine? (make-coroutine (lambda () 1)))", &mut env), "#t");
+}
+
+#[test]
+fn dropping_a_coroutine_that_catches_errors_around_yield_still_stops_it() {
+    assert_eq!(
+        run("(define co (make-coroutine (lambda ()
+                                          (define (spin) (with-exception-handler (lambda (e) #f) (lambda () (yield 1))) (spin))
+                                          (spin))))
+             (resume co)
+             (set! co #f)
+             'done"),
+        "done"
+    );
+}
+
+#[test]
+fn with_mutex_holds_the_mutex_only_during_the_thunk() {
+    assert_eq!(run("(define m (make-mutex)) (list (with-mutex m (lambda () (mutex-locked? m))) (mutex-locked? m))"), "(#t #f)");
+}
+
+#[test]
+fn with_mutex_releases_the_mutex_when_the_thunk_raises() {
+    assert_eq!(
+        run("(define m (make-mutex))
+             (with-exception-handler (lambda (e) 0) (lambda () (with-mutex m (lambda () (error \"boom\")))))
+             (mutex-locked? m)"),
+        "#f"
+    );
+}
+
+#[test]
+fn recursive_mutexes_can_be_locked_again_by_their_owner() {
+    assert_eq!(
+        run("(define m (make-recursive-mutex))
+             (with-mutex m (lambda () (list (with-mutex m (lambda () 'nested)) (mutex-locked? m))))"),
+        "(nested #t)"
+    );
+}
+
+#[test]
+fn with_mutex_serializes_threads() {
+    assert_eq!(
+        run("(define m (make-mutex))
+             (define total 0)
+             (define (work) (for-each (lambda (i) (with-mutex m (lambda () (set! total (+ total 1))))) (list-tabulate 100 (lambda (i) i))))
+             (define threads (list (make-thread work) (make-thread work)))
+             (for-each thread-start! threads)
+             (for-each thread-join! threads)
+             total"),
+        "200"
+    );
+}
+
+#[test]
+fn only_the_owner_can_unlock_a_mutex() {
+    assert_eq!(run_err("(define m (make-mutex)) (mutex-unlock! m)"), "Mutex is not locked by this thread");
+    assert_eq!(
+        run_err("(define m (make-mutex)) (mutex-lock! m)
+                 (define t (make-thread (lambda () (mutex-unlock! m))))
+                 (thread-start! t)
+                 (thread-join! t)"),
+        "Mutex is not locked by this thread"
+    );
+}
+
+#[test]
+fn the_thunk_must_not_unlock_the_mutex() {
+    assert_eq!(
+        run_err("(define m (make-mutex)) (with-mutex m (lambda () (mutex-unlock! m)))"),
+        "The thunk of 'with-mutex' unlocked the mutex"
+    );
+}
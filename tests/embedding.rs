@@ -0,0 +1,213 @@
+use lisp_interpreter::interpreter::{eval_all_str, eval_str, Environment, Expr, LispError};
+
+#[test]
+fn eval_str_evaluates_one_expression() {
+    let mut env = Environment::new();
+    eval_all_str("(define a 5) (define b 6)", &mut env).unwrap();
+    assert_eq!(eval_str("(+ a b)", &mut env), Ok(Expr::Number(11.0)));
+}
+
+#[test]
+fn eval_str_rejects_trailing_input() {
+    let mut env = Environment::new();
+    assert!(matches!(eval_str("(+ 1 2) (+ 3 4)", &mut env), Err(LispError::Read(_))));
+    assert!(matches!(eval_str("(+ 1", &mut env), Err(LispError::Read(_))));
+}
+
+#[test]
+fn eval_str_reports_evaluation_errors() {
+    let mut env = Environment::new();
+    assert_eq!(eval_str("undefined-name", &mut env), Err(LispError::Eval("Undefined symbol: undefined-name".to_string())));
+}
+
+fn twice(args: &[Expr], _env: &mut Environment) -> Result<Expr, LispError> {
+    match args {
+        [Expr::Number(n)] => Ok(Expr::Number(n * 2.0)),
+        _ => Err("twice expects a number".into()),
+    }
+}
+
+#[test]
+fn define_native_makes_a_rust_function_callable() {
+    let mut env = Environment::new();
+    env.define_native("twice", twice);
+    assert_eq!(eval_str("(twice 21)", &mut env), Ok(Expr::Number(42.0)));
+    assert_eq!(eval_str("(map twice '(1 2))", &mut env), eval_str("'(2 4)", &mut env));
+    assert_eq!(eval_str("(twice \"a\")", &mut env), Err(LispError::Eval("twice expects a number".to_string())));
+}
+
+#[test]
+fn define_native_closure_can_capture_state() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let mut env = Environment::new();
+    env.define_native_closure(
+        "tick",
+        Box::new(move |_args, _env| Ok(Expr::Number((counter.fetch_add(1, Ordering::SeqCst) + 1) as f64))),
+    );
+    eval_all_str("(tick) (tick)", &mut env).unwrap();
+    assert_eq!(eval_str("(tick)", &mut env), Ok(Expr::Number(3.0)));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn define_native_replaces_an_earlier_native() {
+    let mut env = Environment::new();
+    env.define_native_closure("twice", Box::new(|_args, _env| Ok(Expr::Number(0.0))));
+    env.define_native("twice", twice);
+    assert_eq!(eval_str("(twice 2)", &mut env), Ok(Expr::Number(4.0)));
+}
+
+#[test]
+fn get_symbol_sees_lisp_definitions() {
+    let mut env = Environment::new();
+    eval_all_str("(define answer 42)", &mut env).unwrap();
+    assert_eq!(env.get_symbol("answer"), Some(Expr::Number(42.0)));
+    assert_eq!(env.get_symbol("no-such-symbol"), None);
+}
+
+#[test]
+fn set_symbol_is_visible_to_lisp() {
+    let mut env = Environment::new();
+    env.set_symbol("greeting", Expr::Str("hi".to_string()));
+    assert_eq!(eval_str("(string-append greeting \"!\")", &mut env), Ok(Expr::Str("hi!".to_string())));
+}
+
+#[test]
+fn remove_symbol_undefines_it() {
+    let mut env = Environment::new();
+    env.set_symbol("x", Expr::Number(1.0));
+    assert_eq!(env.remove_symbol("x"), Some(Expr::Number(1.0)));
+    assert!(eval_str("x", &mut env).is_err());
+}
+
+#[test]
+fn defined_symbols_and_functions_are_sorted() {
+    let mut env = Environment::new();
+    eval_all_str("(define zzz-last 1) (define aaa-first 2)", &mut env).unwrap();
+    let symbols = env.defined_symbols();
+    assert!(symbols.contains(&"zzz-last".to_string()) && symbols.contains(&"aaa-first".to_string()));
+    assert!(symbols.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let functions = env.defined_functions();
+    assert!(functions.contains(&"car") && functions.contains(&"string-append"));
+    assert!(functions.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn fork_sees_the_parent_bindings() {
+    let mut parent = Environment::new();
+    eval_all_str("(define x 1) (define (f) x)", &mut parent).unwrap();
+    let mut child = parent.fork();
+    assert_eq!(eval_str("(f)", &mut child), Ok(Expr::Number(1.0)));
+}
+
+#[test]
+fn fork_keeps_definitions_local_but_set_reaches_the_parent() {
+    let mut parent = Environment::new();
+    eval_all_str("(define x 1)", &mut parent).unwrap();
+    let mut child = parent.fork();
+    eval_all_str("(define y 2) (set! x 10)", &mut child).unwrap();
+    assert_eq!(parent.get_symbol("y"), None);
+    assert_eq!(parent.get_symbol("x"), Some(Expr::Number(10.0)));
+}
+
+#[test]
+fn local_definitions_shadow_natives_in_call_position() {
+    let mut parent = Environment::new();
+    let mut child = parent.fork();
+    eval_all_str("(define (car x) 'shadowed)", &mut child).unwrap();
+    assert_eq!(eval_str("(car '(1 2))", &mut child), eval_str("'shadowed", &mut child));
+    assert_eq!(eval_str("(car '(1 2))", &mut parent), Ok(Expr::Number(1.0)));
+    assert_eq!(eval_str("(let ((list (lambda args 'mine))) (list 1 2))", &mut parent), eval_str("'mine", &mut parent));
+}
+
+#[test]
+fn symbols_are_interned_once() {
+    use lisp_interpreter::interpreter::SymbolId;
+
+    let a = SymbolId::intern("interned-symbol");
+    assert_eq!(a, SymbolId::from("interned-symbol"));
+    assert_ne!(a, SymbolId::intern("another-symbol"));
+    assert_eq!(a.as_str(), "interned-symbol");
+    assert_eq!(a, "interned-symbol");
+}
+
+#[test]
+fn read_symbols_are_the_interned_ones() {
+    use lisp_interpreter::interpreter::SymbolId;
+
+    let mut env = Environment::new();
+    assert_eq!(eval_str("'read-symbol", &mut env), Ok(Expr::Symbol(SymbolId::intern("read-symbol"))));
+    assert_eq!(eval_str("(equal? 'same 'same)", &mut env), Ok(Expr::Bool(true)));
+}
+
+#[test]
+fn the_debugger_sees_each_compound_expression_and_its_depth() {
+    use lisp_interpreter::interpreter::StepCommand;
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let mut env = Environment::new();
+    env.set_debugger(Box::new(move |expr, depth| {
+        log.lock().unwrap().push((expr.to_string(), depth));
+        StepCommand::Next
+    }));
+
+    assert_eq!(eval_str("(step (+ 1 (* 2 3)))", &mut env), Ok(Expr::Number(7.0)));
+    assert_eq!(*seen.lock().unwrap(), vec![("(+ 1 (* 2 3))".to_string(), 0), ("(* 2 3)".to_string(), 1)]);
+}
+
+#[test]
+fn continue_leaves_the_stepper() {
+    use lisp_interpreter::interpreter::StepCommand;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let stops = Arc::new(AtomicUsize::new(0));
+    let count = stops.clone();
+    let mut env = Environment::new();
+    env.set_debugger(Box::new(move |_expr, _depth| {
+        count.fetch_add(1, Ordering::SeqCst);
+        StepCommand::Continue
+    }));
+
+    assert_eq!(eval_str("(step (+ 1 (* 2 3)))", &mut env), Ok(Expr::Number(7.0)));
+    assert_eq!(stops.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn the_interrupt_handle_stops_a_running_evaluation() {
+    use std::sync::atomic::Ordering;
+
+    let mut env = Environment::new();
+    let interrupt = env.interrupt_handle();
+    let setter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        interrupt.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(eval_str("(let loop () (loop))", &mut env), Err(LispError::Interrupted));
+    setter.join().unwrap();
+    // The flag is cleared once it has stopped an evaluation
+    assert_eq!(eval_str("(+ 1 2)", &mut env), Ok(Expr::Number(3.0)));
+}
+
+#[test]
+fn exception_handlers_do_not_catch_an_interrupt() {
+    use std::sync::atomic::Ordering;
+
+    let mut env = Environment::new();
+    let interrupt = env.interrupt_handle();
+    let setter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        interrupt.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(eval_str("(ignore-errors (lambda () (let loop () (loop))))", &mut env), Err(LispError::Interrupted));
+    setter.join().unwrap();
+}
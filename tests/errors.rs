@@ -0,0 +1,120 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn assert_passes_true_conditions() {
+    assert_eq!(run("(assert (= 1 1))"), "#t");
+    assert_eq!(run("(assert 0 \"zero is true\")"), "#t");
+}
+
+#[test]
+fn assert_reports_the_message_or_the_expression() {
+    assert_eq!(run_err("(assert (= 1 2) \"numbers differ\")"), "Assertion failed: numbers differ");
+    assert_eq!(run_err("(assert (= 1 2))"), "Assertion failed: (= 1 2)");
+}
+
+#[test]
+fn assert_equal_compares_structurally() {
+    assert_eq!(run("(assert-equal? '(1 (2)) (list 1 (list 2)))"), "#t");
+    assert_eq!(run_err("(assert-equal? 1 2)"), "Assertion failed: expected 1 but got 2");
+}
+
+#[test]
+fn assert_raises_checks_the_error_kind() {
+    assert_eq!(run("(assert-raises? 'eval (lambda () (car 1)))"), "#t");
+    assert_eq!(run("(assert-raises? 'error (lambda () (raise 'oops)))"), "#t");
+    assert_eq!(
+        run_err("(assert-raises? 'file (lambda () (car 1)))"),
+        "Assertion failed: expected an error of type file but got one of type eval: Invalid argument type for car"
+    );
+    assert_eq!(run_err("(assert-raises? 'any (lambda () 5))"), "Assertion failed: expected an error but got 5");
+}
+
+#[test]
+fn the_handler_receives_the_raised_object() {
+    assert_eq!(
+        run("(with-exception-handler (lambda (e) (list 'handled e)) (lambda () (raise 'oops)))"),
+        "(handled oops)"
+    );
+}
+
+#[test]
+fn raise_continuable_returns_the_handler_result() {
+    assert_eq!(run("(with-exception-handler (lambda (e) 10) (lambda () (+ 1 (raise-continuable 'more))))"), "11");
+}
+
+#[test]
+fn raising_from_a_handler_reaches_the_outer_handler() {
+    assert_eq!(
+        run("(with-exception-handler (lambda (e) (list 'outer e))
+               (lambda () (with-exception-handler (lambda (e) (raise (list 'inner e))) (lambda () (raise 'x)))))"),
+        "(outer (inner x))"
+    );
+}
+
+#[test]
+fn interpreter_errors_reach_the_handler() {
+    assert_eq!(
+        run("(with-exception-handler (lambda (e) (error-object-message e)) (lambda () (car 1)))"),
+        "\"Invalid argument type for car\""
+    );
+}
+
+#[test]
+fn an_unhandled_raise_is_an_uncaught_exception() {
+    assert_eq!(run_err("(raise 'oops)"), "Uncaught exception: oops");
+}
+
+#[test]
+fn handlers_do_not_catch_failed_assertions() {
+    assert_eq!(run_err("(with-exception-handler (lambda (e) #f) (lambda () (assert #f \"still fails\")))"), "Assertion failed: still fails");
+}
+
+#[test]
+fn error_objects_carry_the_message_and_irritants() {
+    assert_eq!(
+        run("(define e (with-exception-handler (lambda (e) e) (lambda () (error \"bad thing\" 1 'two))))
+             (list (error-object? e) (error-object-message e) (error-object-irritants e) (error-object? 'x))"),
+        "(#t \"bad thing\" (1 two) #f)"
+    );
+}
+
+#[test]
+fn read_and_file_errors_are_told_apart() {
+    assert_eq!(
+        run("(define (catch thunk) (with-exception-handler (lambda (e) e) thunk))
+             (define r (catch (lambda () (read-from-string \"(1 2\"))))
+             (define f (catch (lambda () (delete-file \"/nonexistent/file\"))))
+             (define e (catch (lambda () (error \"plain\"))))
+             (list (read-error? r) (file-error? r) (read-error? f) (file-error? f) (read-error? e) (file-error? e))"),
+        "(#t #f #f #t #f #f)"
+    );
+}
+
+#[test]
+fn condition_report_string_describes_any_condition() {
+    assert_eq!(
+        run("(condition/report-string (with-exception-handler (lambda (e) e) (lambda () (error \"bad thing\" 1 'two))))"),
+        "\"bad thing 1 two\""
+    );
+    assert_eq!(run("(condition/report-string 'sym)"), "\"sym\"");
+}
+
+#[test]
+fn error_object_accessors_require_an_error_object() {
+    assert_eq!(run_err("(error-object-message 5)"), "Invalid argument type for error-object-message");
+}
+
+#[test]
+fn ignore_errors_gives_false_on_error() {
+    assert_eq!(
+        run("(list (ignore-errors (lambda () (error \"oops\") 42)) (ignore-errors (lambda () (raise 'x))) (ignore-errors (lambda () 42)))"),
+        "(#f #f 42)"
+    );
+}
+
+#[test]
+fn ignore_errors_default_gives_the_default_on_error() {
+    assert_eq!(run("(list (ignore-errors/default 'd (lambda () (car 1))) (ignore-errors/default 'd (lambda () 1)))"), "(d 1)");
+}
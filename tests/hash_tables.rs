@@ -0,0 +1,61 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn alists_and_hash_tables_convert_both_ways() {
+    assert_eq!(run("(hash-table->alist (alist->hash-table '((k . 1))))"), "((k . 1))");
+    assert_eq!(
+        run("(define alist (hash-table->alist (alist->hash-table '((a . 1) (b . 2)))))
+             (list (length alist) (assoc 'a alist) (assoc 'b alist))"),
+        "(2 (a . 1) (b . 2))"
+    );
+}
+
+#[test]
+fn alist_to_hash_table_keeps_the_first_duplicate() {
+    assert_eq!(run("(hash-table-ref (alist->hash-table '((k . 1) (k . 2))) 'k)"), "1");
+}
+
+#[test]
+fn hash_table_walk_visits_every_entry() {
+    assert_eq!(
+        run("(define total 0)
+             (hash-table-walk (alist->hash-table '((a . 1) (b . 2))) (lambda (k v) (set! total (+ total v))))
+             total"),
+        "3"
+    );
+}
+
+#[test]
+fn hash_table_merge_prefers_the_second_table() {
+    assert_eq!(
+        run("(define h (alist->hash-table '((a . 1) (b . 2))))
+             (hash-table-merge! h (alist->hash-table '((b . 20) (c . 30))))
+             (list (hash-table-ref h 'a) (hash-table-ref h 'b) (hash-table-ref h 'c))"),
+        "(1 20 30)"
+    );
+}
+
+#[test]
+fn hash_table_copy_is_independent() {
+    assert_eq!(
+        run("(define h (alist->hash-table '((a . 1))))
+             (define c (hash-table-copy h))
+             (hash-table-set! c 'a 100)
+             (list (hash-table-ref h 'a) (hash-table-ref c 'a))"),
+        "(1 100)"
+    );
+}
+
+#[test]
+fn hash_table_update_falls_back_to_the_default() {
+    assert_eq!(
+        run("(define h (alist->hash-table '((a . 1))))
+             (hash-table-update! h 'a (lambda (v) (+ v 1)) (lambda () 0))
+             (hash-table-update! h 'z (lambda (v) (+ v 1)) (lambda () 0))
+             (hash-table-update!/default h 'q (lambda (v) (+ v 1)) 10)
+             (list (hash-table-ref h 'a) (hash-table-ref h 'z) (hash-table-ref h 'q))"),
+        "(2 1 11)"
+    );
+}
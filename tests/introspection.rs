@@ -0,0 +1,187 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::{eval_all_str, Environment, SourceLocation};
+
+#[test]
+fn profile_counts_calls_by_name() {
+    assert_eq!(
+        run("(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+             (define stats (profile (lambda () (fib 10))))
+             (list (cdr (assoc 'fib (cdr (assoc 'calls stats)))) (>= (cdr (assoc 'total-ms stats)) 0))"),
+        "(177 #t)"
+    );
+}
+
+#[test]
+fn profile_counts_tail_calls_too() {
+    assert_eq!(
+        run("(define (countdown n) (if (= n 0) 'done (countdown (- n 1))))
+             (cdr (assoc 'countdown (cdr (assoc 'calls (profile (lambda () (countdown 50)))))))"),
+        "51"
+    );
+}
+
+#[test]
+fn profile_statistics_accumulate_until_reset() {
+    assert_eq!(
+        run("(define (f) 1)
+             (profile (lambda () (f)))
+             (define twice (profile (lambda () (f))))
+             (profile-reset!)
+             (define once (profile (lambda () (f))))
+             (list (assoc 'f (cdr (assoc 'calls twice))) (assoc 'f (cdr (assoc 'calls once))))"),
+        "((f . 2) (f . 1))"
+    );
+}
+
+fn located(file: &str, line: usize) -> Option<SourceLocation> {
+    Some(SourceLocation { file: file.to_string(), line, column: 1 })
+}
+
+#[test]
+fn source_location_reports_where_a_procedure_was_defined() {
+    let mut env = Environment::new();
+    env.set_source_location(located("shapes.lisp", 42));
+    eval_in("(define (area r) (* r r))", &mut env);
+    env.set_source_location(None);
+    assert_eq!(eval_in("(source-location 'area)", &mut env), "(\"shapes.lisp\" 42 1)");
+    assert_eq!(eval_in("(source-location area)", &mut env), "(\"shapes.lisp\" 42 1)");
+}
+
+#[test]
+fn procedures_without_a_source_file_have_no_location() {
+    assert_eq!(run("(define (f) 1) (source-location f)"), "#f");
+    assert_eq!(run_err("(source-location car)"), "'source-location' requires a procedure defined in Lisp");
+}
+
+#[test]
+fn procedure_source_returns_the_lambda_expression() {
+    assert_eq!(run("(define (f x) (car x)) (procedure-source f)"), "(lambda (x) (car x))");
+    assert_eq!(run_err("(procedure-source car)"), "'procedure-source' requires a procedure defined in Lisp");
+}
+
+#[test]
+fn the_backtrace_names_each_frame_and_its_location() {
+    let mut env = Environment::new();
+    env.set_source_location(located("inner.lisp", 1));
+    eval_in("(define (inner x) (car x))", &mut env);
+    env.set_source_location(located("outer.lisp", 7));
+    eval_in("(define (outer) (list (inner 5)))", &mut env);
+    env.set_source_location(None);
+    assert!(eval_all_str("(outer)", &mut env).is_err());
+    let frames: Vec<String> = env
+        .take_backtrace()
+        .iter()
+        .map(|frame| format!("{:?} {}", frame.name, frame.location.as_ref().unwrap()))
+        .collect();
+    assert_eq!(frames, ["Some(\"inner\") inner.lisp:1:1", "Some(\"outer\") outer.lisp:7:1"]);
+}
+
+#[test]
+fn interaction_environment_is_the_top_level() {
+    assert_eq!(run("(define x 5) (eval 'x (interaction-environment))"), "5");
+    assert_eq!(run("(eval '(define y 6) (interaction-environment)) y"), "6");
+}
+
+#[test]
+fn scheme_report_environment_has_the_standard_procedures_only() {
+    assert_eq!(run("(eval '(+ 1 2) (scheme-report-environment 7))"), "3");
+    assert_eq!(run_err("(define x 5) (eval 'x (scheme-report-environment 7))"), "Undefined symbol: x");
+}
+
+#[test]
+fn definitions_in_a_report_environment_stay_there() {
+    assert_eq!(run_err("(eval '(define z 1) (scheme-report-environment 7)) z"), "Undefined symbol: z");
+}
+
+#[test]
+fn null_environment_has_special_forms_but_no_procedures() {
+    assert_eq!(run("(eval '(if #t 1 2) (null-environment 7))"), "1");
+    assert_eq!(run_err("(eval '(+ 1 2) (null-environment 7))"), "Undefined function: +");
+}
+
+#[test]
+fn eval_requires_an_environment() {
+    assert_eq!(run_err("(eval 1 5)"), "Invalid argument type for eval");
+}
+
+#[test]
+fn apropos_finds_names_containing_the_pattern() {
+    assert_eq!(run("(apropos \"string->ve\")"), "(string->vector)");
+    assert_eq!(run("(apropos \"^vector-c\")"), "()");
+}
+
+#[test]
+fn apropos_functions_skips_other_values() {
+    assert_eq!(
+        run("(define my-string-thing 1) (define (my-string-fn) 1) (list (apropos \"my-string\") (apropos-functions \"my-string\"))"),
+        "((my-string-fn my-string-thing) (my-string-fn))"
+    );
+}
+
+#[test]
+fn apropos_takes_a_regexp() {
+    assert_eq!(run("(apropos (make-regexp \"^vector-c\"))"), "(vector-copy vector-copy!)");
+    assert_eq!(run_err("(apropos 5)"), "'apropos' requires a pattern string or regexp");
+}
+
+#[test]
+fn describe_prints_the_documentation_of_a_builtin() {
+    assert_eq!(output("(describe '+)"), "(+ num ...) → num: Adds zero or more numbers.\n");
+    assert_eq!(run_err("(describe 'nope)"), "Undefined symbol: nope");
+}
+
+#[test]
+fn procedure_documentation_returns_the_docstring() {
+    assert_eq!(run("(procedure-documentation car)"), "\"(car list) → obj: The first element of a list.\"");
+    assert_eq!(run("(define (f x) \"Doubles x.\" (* 2 x)) (procedure-documentation f)"), "\"Doubles x.\"");
+    assert_eq!(run("(define (g) 1) (procedure-documentation g)"), "#f");
+}
+
+#[test]
+fn procedure_arity_of_natives_and_lambdas() {
+    assert_eq!(
+        run("(list (procedure-arity +) (procedure-arity car) (procedure-arity (lambda (x y) x))
+                   (procedure-arity (lambda (x . rest) x)) (procedure-arity (lambda args args)))"),
+        "((0 . #f) (1 . 1) (2 . 2) (1 . #f) (0 . #f))"
+    );
+}
+
+#[test]
+fn wrapped_procedures_report_their_own_arity() {
+    assert_eq!(run("(list (procedure-arity (memoize car)) (procedure-arity (make-parameter 1)))"), "((1 . 1) (0 . 1))");
+}
+
+#[test]
+fn procedure_arity_valid_checks_a_count() {
+    assert_eq!(
+        run("(list (procedure-arity-valid? + 3) (procedure-arity-valid? car 2) (procedure-arity-valid? (lambda (x . r) x) 0))"),
+        "(#t #f #f)"
+    );
+    assert_eq!(run_err("(procedure-arity 5)"), "Invalid argument type for procedure-arity");
+}
+
+#[test]
+fn define_names_procedures() {
+    assert_eq!(
+        run("(define (square x) (* x x)) (define f (lambda (x) x))
+             (list (procedure-name square) (procedure-name f) (procedure-name (lambda (x) x)) (procedure-name car))"),
+        "(\"square\" \"f\" #f \"car\")"
+    );
+}
+
+#[test]
+fn set_procedure_name_renames_a_lambda() {
+    assert_eq!(run("(define g (lambda (x) x)) (set-procedure-name! g \"foo\") (procedure-name g)"), "\"foo\"");
+    assert_eq!(run_err("(set-procedure-name! car \"x\")"), "Only a lambda can be renamed with set-procedure-name!");
+}
+
+#[test]
+fn arity_errors_use_the_procedure_name() {
+    assert_eq!(run_err("(define (square x) (* x x)) (square 1 2)"), "Wrong number of arguments to square: expected 1, got 2");
+    assert_eq!(
+        run_err("(define g (lambda (x) x)) (set-procedure-name! g \"foo\") (g)"),
+        "Wrong number of arguments to foo: expected 1, got 0"
+    );
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn json_objects_become_hash_tables_keyed_by_strings() {
+    assert_eq!(run("(hash-table-ref (json->lisp \"{\\\"a\\\":1,\\\"b\\\":[2,3]}\") \"b\")"), "(2 3)");
+    assert_eq!(run("(hash-table? (json->lisp \"{}\"))"), "#t");
+}
+
+#[test]
+fn json_scalars_map_to_lisp_values() {
+    assert_eq!(run("(json->lisp \"[1, 2.5, \\\"s\\\", true, false, null]\")"), "(1 2.5 \"s\" #t #f null)");
+}
+
+#[test]
+fn lisp_to_json_writes_objects_from_tables_and_alists() {
+    assert_eq!(run("(lisp->json '((\"a\" . 1) (\"b\" . (2 3))))"), "\"{\\\"a\\\":1,\\\"b\\\":[2,3]}\"");
+    assert_eq!(run("(lisp->json (json->lisp \"{\\\"a\\\":{}}\"))"), "\"{\\\"a\\\":{}}\"");
+    assert_eq!(run("(lisp->json (vector 1.5 \"x\" #t 'null))"), "\"[1.5,\\\"x\\\",true,null]\"");
+}
+
+#[test]
+fn json_round_trips() {
+    assert_eq!(
+        run("(define text \"[1,{\\\"k\\\":\\\"v\\\"},[true,null]]\")
+             (lisp->json (json->lisp text))"),
+        "\"[1,{\\\"k\\\":\\\"v\\\"},[true,null]]\""
+    );
+}
+
+#[test]
+fn json_errors_are_reported() {
+    run_err("(json->lisp \"{\")");
+    run_err("(lisp->json (lambda (x) x))");
+}
+
+#[test]
+fn a_structure_that_contains_itself_cannot_be_converted() {
+    assert_eq!(
+        run_err("(define v (vector 1)) (vector-set! v 0 v) (lisp->json v)"),
+        "Cannot convert a circular structure to JSON"
+    );
+    assert_eq!(
+        run_err("(define t (make-hash-table)) (hash-table-set! t \"self\" t) (lisp->json t)"),
+        "Cannot convert a circular structure to JSON"
+    );
+}
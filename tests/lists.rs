@@ -0,0 +1,301 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn reduce_folds_from_the_head_of_the_list() {
+    assert_eq!(run("(reduce + 0 '(1 2 3))"), "6");
+    // SRFI-1 calls (f element acc), starting with the first element as acc
+    assert_eq!(run("(reduce - 0 '(1 2 3 4))"), "2");
+    assert_eq!(run("(reduce list 0 '(1 2 3))"), "(3 (2 1))");
+}
+
+#[test]
+fn reduce_returns_the_initial_value_for_an_empty_list() {
+    assert_eq!(run("(reduce + 0 '())"), "0");
+    assert_eq!(run("(reduce + 0 '(7))"), "7");
+}
+
+#[test]
+fn reduce_right_folds_from_the_end() {
+    assert_eq!(run("(reduce-right - 0 '(1 2 3 4))"), "-2");
+    assert_eq!(run("(reduce-right list 0 '(1 2 3))"), "(1 (2 3))");
+    assert_eq!(run("(reduce-right + 0 '())"), "0");
+}
+
+#[test]
+fn reduce_checks_its_arguments() {
+    assert_eq!(run_err("(reduce + 0)"), "'reduce' requires exactly 3 arguments");
+}
+
+#[test]
+fn every_returns_the_last_result_or_false() {
+    assert_eq!(run("(every car '((1) (2) (3)))"), "3");
+    assert_eq!(run("(every car '((1) (#f) (3)))"), "#f");
+    assert_eq!(run("(every car '())"), "#t");
+}
+
+#[test]
+fn any_returns_the_first_true_result() {
+    assert_eq!(run("(any car '((#f) (2) (3)))"), "2");
+    assert_eq!(run("(any car '((#f) (#f)))"), "#f");
+    assert_eq!(run("(any car '())"), "#f");
+}
+
+#[test]
+fn every_and_any_walk_several_lists_together() {
+    assert_eq!(run("(every = '(1 2 3) '(1 2 3))"), "#t");
+    assert_eq!(run("(any = '(1 2 3) '(4 5 3))"), "#t");
+    // The shortest list ends the walk
+    assert_eq!(run("(every = '(1 2) '(1 2 9))"), "#t");
+}
+
+#[test]
+fn partition_splits_by_a_predicate() {
+    assert_eq!(run("(partition odd? '(1 2 3 4 5))"), "(1 3 5) (2 4)");
+    assert_eq!(run("(partition odd? '())"), "() ()");
+}
+
+#[test]
+fn span_and_break_split_at_the_first_change() {
+    assert_eq!(run("(span odd? '(1 3 2 4 5))"), "(1 3) (2 4 5)");
+    assert_eq!(run("(break even? '(1 3 2 4 5))"), "(1 3) (2 4 5)");
+    assert_eq!(run("(span odd? '(2 4))"), "() (2 4)");
+    assert_eq!(run("(break odd? '(2 4))"), "(2 4) ()");
+}
+
+#[test]
+fn take_and_drop_split_at_an_index() {
+    assert_eq!(run("(take '(1 2 3 4) 2)"), "(1 2)");
+    assert_eq!(run("(drop '(1 2 3 4) 2)"), "(3 4)");
+    assert_eq!(run("(take '(1 2) 0)"), "()");
+    assert_eq!(run("(drop '(1 2) 2)"), "()");
+}
+
+#[test]
+fn take_and_drop_reject_an_index_past_the_end() {
+    run_err("(take '(1 2) 3)");
+    run_err("(drop '(1 2) 3)");
+}
+
+#[test]
+fn take_while_and_drop_while_stop_at_the_first_failure() {
+    assert_eq!(run("(take-while odd? '(1 3 4 5))"), "(1 3)");
+    assert_eq!(run("(drop-while odd? '(1 3 4 5))"), "(4 5)");
+    assert_eq!(run("(take-while odd? '(2))"), "()");
+    assert_eq!(run("(drop-while odd? '(1 3))"), "()");
+}
+
+#[test]
+fn zip_stops_at_the_shortest_list() {
+    assert_eq!(run("(zip '(1 2 3) '(a b c))"), "((1 a) (2 b) (3 c))");
+    assert_eq!(run("(zip '(1 2 3) '(a))"), "((1 a))");
+    assert_eq!(run("(zip '(1 2))"), "((1) (2))");
+}
+
+#[test]
+fn unzip_returns_one_list_per_position() {
+    assert_eq!(run("(unzip1 '((1 a) (2 b)))"), "(1 2)");
+    assert_eq!(run("(unzip2 '((1 a) (2 b)))"), "(1 2) (a b)");
+    assert_eq!(run("(unzip3 '((1 a x) (2 b y)))"), "(1 2) (a b) (x y)");
+    assert_eq!(run("(unzip5 '((1 2 3 4 5)))"), "(1) (2) (3) (4) (5)");
+}
+
+#[test]
+fn count_counts_matches_across_lists() {
+    assert_eq!(run("(count odd? '(1 2 3 4))"), "2");
+    assert_eq!(run("(count = '(1 5 3) '(1 4 3))"), "2");
+    assert_eq!(run("(count odd? '())"), "0");
+}
+
+#[test]
+fn flatten_splices_every_nested_list() {
+    assert_eq!(run("(flatten '(1 (2 (3 (4))) () 5))"), "(1 2 3 4 5)");
+    assert_eq!(run("(flatten '())"), "()");
+}
+
+#[test]
+fn deep_map_keeps_the_nesting() {
+    assert_eq!(run("(deep-map (lambda (x) (+ x 10)) '(1 (2 (3)) 4))"), "(11 (12 (13)) 14)");
+    assert_eq!(run("(deep-map (lambda (x) (+ x 10)) 5)"), "15");
+}
+
+#[test]
+fn car_and_cdr_go_round_a_circular_list() {
+    assert_eq!(
+        run("(define c (circular-list 1 2 3)) (list (car c) (car (cdr (cdr (cdr c)))) (car (cdr (cdr (cdr (cdr c))))))"),
+        "(1 1 2)"
+    );
+}
+
+#[test]
+fn circular_list_detects_cycles() {
+    assert_eq!(run("(list (circular-list? (circular-list 1 2)) (circular-list? '(1 2 3)) (circular-list? 5))"), "(#t #f #f)");
+}
+
+#[test]
+fn circular_lists_are_written_with_labels() {
+    assert_eq!(run("(write-to-string (circular-list 'a))"), "\"#0=(a . #0#)\"");
+    assert_eq!(run("(circular-list 1 2)"), "#0=(1 2 . #0#)");
+}
+
+#[test]
+fn circular_lists_have_no_length() {
+    assert_eq!(run_err("(list-length (circular-list 1 2))"), "A circular list has no length");
+    assert_eq!(run_err("(length (circular-list 1 2))"), "A circular list has no length");
+}
+
+#[test]
+fn map_for_each_and_append_refuse_circular_lists() {
+    assert_eq!(run_err("(map (lambda (x) x) (circular-list 1 2))"), "A circular list has no end");
+    assert_eq!(run_err("(for-each display (circular-list 1 2))"), "A circular list has no end");
+    assert_eq!(run_err("(append '(0) (circular-list 1 2))"), "A circular list has no end");
+    assert_eq!(run_err("(list->vector (circular-list 1 2))"), "A circular list has no end");
+}
+
+#[test]
+fn list_operations_refuse_circular_lists_instead_of_looping() {
+    assert!(try_run("(map (lambda (x) x) (circular-list 1 2))").is_err());
+    assert!(try_run("(for-each display (circular-list 1 2))").is_err());
+}
+
+#[test]
+fn circular_list_requires_an_element() {
+    assert_eq!(run_err("(circular-list)"), "'circular-list' requires at least 1 element");
+}
+
+#[test]
+fn stdlib_list_basics() {
+    assert_eq!(
+        run("(list (null? '()) (length '(1 2 3)) (cadr '(1 2 3)) (list-tail '(1 2 3 4) 2) (list-ref '(a b c) 1) (last '(1 2 3)))"),
+        "(#t 3 2 (3 4) b 3)"
+    );
+    assert_eq!(run("(list (append '(1) '(2 3) '() '(4)) (reverse '(1 2 3)))"), "((1 2 3 4) (3 2 1))");
+}
+
+#[test]
+fn stdlib_higher_order_procedures() {
+    assert_eq!(
+        run("(list (map + '(1 2) '(10 20)) (filter odd? '(1 2 3 4 5)) (remove odd? '(1 2 3)) (find even? '(1 3 4 5)))"),
+        "((11 22) (1 3 5) (2) 4)"
+    );
+    assert_eq!(run("(list (fold + 0 '(1 2 3)) (fold cons '() '(1 2 3)) (fold-right cons '() '(1 2 3)))"), "(6 (3 2 1) (1 2 3))");
+}
+
+#[test]
+fn stdlib_searches() {
+    assert_eq!(run("(list (member 2 '(1 2 3)) (member 9 '(1 2 3)) (assoc 'b '((a . 1) (b . 2))))"), "((2 3) #f (b . 2))");
+}
+
+#[test]
+fn stdlib_procedures_can_be_redefined() {
+    assert_eq!(run("(define (last lst) 'mine) (last '(1 2 3))"), "mine");
+}
+
+#[test]
+fn compose_applies_right_to_left() {
+    assert_eq!(run("(list ((compose sqrt abs) -4) ((compose list +) 1 2) ((compose) 5))"), "(2 (3) 5)");
+    assert_eq!(run("((compose-list (list (lambda (x) (* x 10)) (lambda (x) (+ x 1)))) 1)"), "20");
+}
+
+#[test]
+fn pipe_applies_left_to_right() {
+    assert_eq!(run("(list (pipe -4 abs sqrt) (pipe -4 abs (lambda (x) (* x 10))) (pipe 1))"), "(2 40 1)");
+}
+
+#[test]
+fn curry_and_partial_fix_leading_arguments() {
+    assert_eq!(run("(list (((curry +) 1) 2) (((curry list) 1) 2 3) ((partial + 1 2) 3))"), "(3 (1 2 3) 6)");
+}
+
+#[test]
+fn negate_inverts_a_predicate() {
+    assert_eq!(run("(list ((negate even?) 3) ((negate even?) 2) ((negate <) 1 2))"), "(#t #f #f)");
+}
+
+#[test]
+fn conjoin_needs_every_predicate() {
+    assert_eq!(run("(list ((conjoin positive? integer?) 3) ((conjoin positive? integer?) -3) ((conjoin positive? integer?) 1.5))"), "(#t #f #f)");
+    assert_eq!(run("((conjoin) 'anything)"), "#t");
+}
+
+#[test]
+fn disjoin_needs_any_predicate() {
+    assert_eq!(run("(list ((disjoin positive? integer?) -3) ((disjoin positive? integer?) -1.5))"), "(#t #f)");
+    assert_eq!(run("(filter (disjoin odd? zero?) '(0 1 2 3))"), "(0 1 3)");
+}
+
+#[test]
+fn list_tabulate_builds_from_indexes() {
+    assert_eq!(run("(list (list-tabulate 5 (lambda (i) (* i i))) (list-tabulate 0 (lambda (i) i)))"), "((0 1 4 9 16) ())");
+    assert_eq!(run_err("(list-tabulate -1 (lambda (i) i))"), "Invalid index for list-tabulate: -1");
+}
+
+#[test]
+fn list_index_finds_the_first_match() {
+    assert_eq!(run("(list (list-index odd? '(2 4 3 6)) (list-index odd? '(2 4)))"), "(2 #f)");
+    assert_eq!(run("(list-index < '(3 2 1) '(1 2 3))"), "2");
+}
+
+#[test]
+fn concatenate_appends_a_list_of_lists() {
+    assert_eq!(run("(list (concatenate '((1 2) (3 4) (5))) (concatenate '()))"), "((1 2 3 4 5) ())");
+}
+
+#[test]
+fn flatten_takes_an_optional_depth() {
+    assert_eq!(run("(flatten 1 '(1 (2 3 (4))))"), "(1 2 3 (4))");
+    assert_eq!(run("(flatten 0 '(1 (2)))"), "(1 (2))");
+    assert_eq!(run_err("(flatten -1 '(1))"), "Depth for flatten must be a non-negative integer");
+}
+
+#[test]
+fn delete_removes_every_equal_element() {
+    assert_eq!(run("(list (delete 3 '(1 2 3 4 3)) (delete '(a) '((a) b)))"), "((1 2 4) (b))");
+    assert_eq!(run("(delete 2 '(1 2 3) <)"), "(1 2)");
+}
+
+#[test]
+fn delete_duplicates_keeps_the_first_occurrence() {
+    assert_eq!(run("(delete-duplicates '(1 2 1 3 2))"), "(1 2 3)");
+    assert_eq!(run("(delete-duplicates '(1 2 3 4) (lambda (a b) (equal? (even? a) (even? b))))"), "(1 2)");
+}
+
+#[test]
+fn lset_operations() {
+    assert_eq!(
+        run("(list (lset-union equal? '(1 2) '(2 3 4)) (lset-intersection equal? '(1 2 3) '(2 3 4))
+                   (lset-difference equal? '(1 2 3) '(2 3)) (lset-union equal?))"),
+        "((1 2 3 4) (2 3) (1) ())"
+    );
+}
+
+#[test]
+fn alist_delete_drops_every_entry_for_the_key() {
+    assert_eq!(run("(alist-delete 'b '((a . 1) (b . 2) (c . 3) (b . 4)))"), "((a . 1) (c . 3))");
+}
+
+#[test]
+fn alist_update_puts_the_new_entry_first() {
+    assert_eq!(
+        run("(list (alist-update 'b 99 '((a . 1) (b . 2))) (alist-update 'z 1 '((a . 1))))"),
+        "(((b . 99) (a . 1)) ((z . 1) (a . 1)))"
+    );
+}
+
+#[test]
+fn alist_copy_copies_the_entries() {
+    assert_eq!(run("(define a '((a . 1))) (list (alist-copy a) (equal? (alist-copy a) a))"), "(((a . 1)) #t)");
+}
+
+#[test]
+fn iterate_applies_a_procedure_n_times() {
+    assert_eq!(run("(list ((iterate (lambda (x) (+ x 3)) 3) 0) ((iterate car 0) 'x))"), "(9 x)");
+    assert_eq!(run_err("(iterate car -1)"), "iterate requires a non-negative integer count -1");
+    assert_eq!(run_err("(iterate car 1.5)"), "iterate requires a non-negative integer count 1.5");
+}
+
+#[test]
+fn compose_list_composes_right_to_left() {
+    assert_eq!(run("(list ((compose-list (list sqrt abs)) -9) ((compose-list '()) 7))"), "(3 7)");
+}
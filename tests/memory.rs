@@ -0,0 +1,102 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::Environment;
+
+// The value of `key` in the (gc-stats) association list
+fn gc_stat(key: &str, env: &mut Environment) -> f64 {
+    let stats = eval_in("(gc-stats)", env);
+    let entry = format!("({} . ", key);
+    let start = stats.find(&entry).unwrap() + entry.len();
+    stats[start..].split(')').next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn finished_calls_leave_no_live_scopes() {
+    let mut env = Environment::new();
+    eval_in("(define add1 (lambda (x) (+ x 1)))", &mut env);
+    for n in 0..5000 {
+        eval_in(&format!("(add1 {})", n), &mut env);
+    }
+    assert!(gc_stat("live-scopes", &mut env) < 10.0);
+}
+
+#[test]
+fn scopes_captured_by_closures_stay_alive() {
+    assert_eq!(
+        run("(define make-adder (lambda (n) (lambda (x) (+ x n))))
+             (define add5 (make-adder 5))
+             (define add7 (make-adder 7))
+             (values (add5 1) (add7 1) (add5 2))"),
+        "6 8 7"
+    );
+}
+
+#[test]
+fn gc_frees_scopes_kept_alive_only_by_cycles() {
+    // Each call's scope holds a procedure that holds the scope: a cycle reference counting can't free
+    let mut env = Environment::new();
+    eval_in("(define make-loop (lambda () (define self (lambda () self)) self)) (define kept (make-loop))", &mut env);
+    for _ in 0..50 {
+        eval_in("(make-loop)", &mut env);
+    }
+    let freed: f64 = eval_in("(gc)", &mut env).parse().unwrap();
+    assert!(freed >= 50.0);
+    assert_eq!(eval_in("((kept))", &mut env), "#<procedure>");
+    assert_eq!(gc_stat("collections", &mut env), 1.0);
+}
+
+#[test]
+fn gc_keeps_everything_reachable() {
+    assert_eq!(
+        run("(define make-counter-at (lambda (n) (lambda () (set! n (+ n 1)) n)))
+             (define c (make-counter-at 10))
+             (c)
+             (gc)
+             (c)"),
+        "12"
+    );
+    assert_eq!(run("(gc) (gc)"), "0");
+}
+
+#[test]
+fn weak_hash_tables_drop_entries_only_they_refer_to() {
+    assert_eq!(
+        run("(define h (make-weak-hash-table))
+             (define key (vector 1 2))
+             (hash-table-set! h key 'kept)
+             (hash-table-set! h (vector 3 4) 'temp)
+             (define before (hash-table-size h))
+             (gc)
+             (list before (hash-table-size h) (hash-table-ref h key))"),
+        "(2 1 kept)"
+    );
+}
+
+#[test]
+fn a_weak_hash_table_made_on_another_thread_is_swept_by_gc() {
+    assert_eq!(
+        run("(define h (thread-join! (thread-start! (make-thread (lambda () (make-weak-hash-table))))))
+             (hash-table-set! h (vector 1) 'temp)
+             (define before (hash-table-size h))
+             (gc)
+             (list before (hash-table-size h))"),
+        "(1 0)"
+    );
+}
+
+#[test]
+fn weak_hash_tables_keep_their_entries_until_gc_runs() {
+    assert_eq!(run("(define h (make-weak-hash-table)) (hash-table-set! h (vector 1) 'temp) (hash-table-size h)"), "1");
+}
+
+#[test]
+fn weak_car_becomes_false_once_its_object_is_freed() {
+    assert_eq!(run("(define w (weak-cons (vector 'a) 'b)) (list (weak-pair? w) (weak-car w) (weak-cdr w))"), "(#t #f b)");
+    assert_eq!(run("(define strong (vector 'x)) (define w (weak-cons strong 1)) (gc) (weak-car w)"), "#(x)");
+}
+
+#[test]
+fn values_without_identity_are_held_as_they_are() {
+    assert_eq!(run("(define w (weak-cons (list 'a) 1)) (gc) (list (weak-car w) (weak-car (weak-cons 5 1)))"), "((a) 5)");
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::Environment;
+
+// Evaluates `call` `times` times in `env` and collects the numbers it returns
+fn samples(call: &str, times: usize, env: &mut Environment) -> Vec<f64> {
+    (0..times).map(|_| eval_in(call, env).parse().unwrap()).collect()
+}
+
+#[test]
+fn random_stays_below_its_limit() {
+    let mut env = Environment::new();
+    assert!(samples("(random 6)", 200, &mut env).iter().all(|n| n.fract() == 0.0 && (0.0..6.0).contains(n)));
+    assert!(samples("(random-real)", 200, &mut env).iter().all(|x| (0.0..1.0).contains(x)));
+    assert!(samples("(random 2.5)", 200, &mut env).iter().all(|x| *x < 2.5));
+}
+
+#[test]
+fn random_requires_a_positive_limit() {
+    assert_eq!(run_err("(random 0)"), "'random' requires a positive number and an optional random source");
+}
+
+#[test]
+fn new_random_sources_repeat_the_same_sequence() {
+    let mut env = Environment::new();
+    eval_in("(define a (make-random-source)) (define b (make-random-source)) (define c (make-random-source))", &mut env);
+    eval_in("(define next (random-source-make-integers c))", &mut env);
+    let first = samples("(random 1000 a)", 10, &mut env);
+    assert_eq!(samples("(random 1000 b)", 10, &mut env), first);
+    assert_eq!(samples("(next 1000)", 10, &mut env), first);
+}
+
+#[test]
+fn randomized_sources_diverge() {
+    let mut env = Environment::new();
+    eval_in("(define a (make-random-source)) (define b (make-random-source)) (random-source-randomize! b)", &mut env);
+    assert_ne!(samples("(random 1000000 a)", 10, &mut env), samples("(random 1000000 b)", 10, &mut env));
+}
+
+#[test]
+fn integer_accepts_integral_floats() {
+    assert_eq!(run("(list (integer? 3) (integer? 3.0) (integer? 3.5) (integer? 'a))"), "(#t #t #f #f)");
+}
+
+#[test]
+fn each_tower_predicate_accepts_the_levels_below_it() {
+    assert_eq!(run("(list (rational? 1.5) (rational? 2) (real? 1.5) (complex? 2) (number? 1.5))"), "(#t #t #t #t #t)");
+    assert_eq!(run("(list (number? 'a) (real? \"1\") (rational? '()))"), "(#f #f #f)");
+}
+
+#[test]
+fn infinity_is_real_but_not_rational() {
+    assert_eq!(run("(define huge (* 1e300 1e300)) (list (integer? huge) (rational? huge) (real? huge))"), "(#f #f #t)");
+}
+
+#[test]
+fn exact_integer_cannot_tell_3_from_3_point_0() {
+    assert_eq!(run("(list (exact-integer? 3) (exact-integer? 3.0) (exact-integer? 2.5) (exact-integer? \"3\"))"), "(#t #t #f #f)");
+    assert_eq!(run_err("(exact-integer?)"), "'exact-integer?' requires exactly 1 argument");
+}
+
+#[test]
+fn tower_predicates_take_one_argument() {
+    assert_eq!(run_err("(integer? 1 2)"), "'integer?' requires exactly 1 argument");
+}
+
+#[test]
+fn exact_integer_sqrt_returns_the_root_and_remainder() {
+    assert_eq!(run("(call-with-values (lambda () (exact-integer-sqrt 14)) list)"), "(3 5)");
+    assert_eq!(run("(call-with-values (lambda () (exact-integer-sqrt 16)) list)"), "(4 0)");
+    assert_eq!(run("(call-with-values (lambda () (exact-integer-sqrt 4503599627370495)) list)"), "(67108863 134217726)");
+}
+
+#[test]
+fn exact_integer_sqrt_requires_a_non_negative_integer() {
+    assert_eq!(run_err("(exact-integer-sqrt -1)"), "'exact-integer-sqrt' requires a non-negative integer");
+    assert_eq!(run_err("(exact-integer-sqrt 2.5)"), "'exact-integer-sqrt' requires a non-negative integer");
+}
+
+#[test]
+fn square_multiplies_a_number_by_itself() {
+    assert_eq!(run("(list (square -3) (square 1.5))"), "(9 2.25)");
+}
+
+#[test]
+fn pretty_numbers_group_thousands() {
+    assert_eq!(run("(number->string/pretty 1234567.89)"), "\"1,234,567.89\"");
+    assert_eq!(run("(number->string/pretty -1234567)"), "\"-1,234,567\"");
+    assert_eq!(run("(number->string/pretty 999)"), "\"999\"");
+}
+
+#[test]
+fn pretty_numbers_take_the_separator_and_decimal_point() {
+    assert_eq!(run("(number->string/pretty 1234567.5 \".\" \",\")"), "\"1.234.567,5\"");
+}
+
+#[test]
+fn scientific_notation_keeps_the_significant_digits() {
+    assert_eq!(run("(number->string/scientific 0.000123)"), "\"1.23e-4\"");
+    assert_eq!(run("(number->string/scientific 12345)"), "\"1.2345e4\"");
+}
+
+#[test]
+fn fixed_notation_rounds_or_pads_to_the_places() {
+    assert_eq!(run("(number->string/fixed 3.14159 2)"), "\"3.14\"");
+    assert_eq!(run("(number->string/fixed 2 3)"), "\"2.000\"");
+    assert_eq!(run_err("(number->string/fixed 1 -1)"), "Invalid index for number->string/fixed: -1");
+}
+
+#[test]
+fn integers_format_in_other_radixes() {
+    assert_eq!(
+        run("(list (number->string/hex 255) (number->string/hex -255) (number->string/bin 10) (number->string/oct 8))"),
+        "(\"FF\" \"-FF\" \"1010\" \"10\")"
+    );
+    assert_eq!(run_err("(number->string/hex 1.5)"), "'number->string/hex' requires an integer");
+}
+
+#[test]
+fn strings_parse_in_other_radixes() {
+    assert_eq!(
+        run("(list (hex->number \"FF\") (hex->number \"ff\") (bin->number \"1010\") (oct->number \"10\") (hex->number \"zz\"))"),
+        "(255 255 10 8 #f)"
+    );
+    assert_eq!(run_err("(hex->number 5)"), "'hex->number' requires exactly 1 string");
+}
+
+#[test]
+fn object_hash_agrees_with_equal() {
+    assert_eq!(
+        run("(list (= (object-hash '(1 2 3)) (object-hash (list 1 2 3)))
+                   (= (object-hash \"ab\") (object-hash (string-append \"a\" \"b\")))
+                   (= (object-hash (vector 1)) (object-hash (vector 1)))
+                   (= (object-hash 1) (object-hash 2)))"),
+        "(#t #t #t #f)"
+    );
+}
+
+#[test]
+fn object_hash_is_stable_across_environments() {
+    assert_eq!(run("(object-hash '(a \"b\" 3))"), run("(object-hash '(a \"b\" 3))"));
+}
+
+#[test]
+fn object_hash_stays_below_the_bound() {
+    assert_eq!(run("(every (lambda (i) (< (object-hash i 10) 10)) (list-tabulate 50 (lambda (i) i)))"), "#t");
+    assert_eq!(run_err("(object-hash 1 0)"), "The bound for object-hash must be positive");
+}
+
+#[test]
+fn string_parse_number_reads_every_literal_format() {
+    assert_eq!(
+        run("(list (string-parse-number \"3.14\") (string-parse-number \"42\") (string-parse-number \"#xAB\")
+                   (string-parse-number \"-2e3\") (string-parse-number \" 7 \"))"),
+        "(3.14 42 171 -2000 7)"
+    );
+}
+
+#[test]
+fn string_parse_number_returns_false_for_non_numbers() {
+    assert_eq!(run("(list (string-parse-number \"bad\") (string-parse-number \"\"))"), "(#f #f)");
+    assert_eq!(run_err("(string-parse-number 5)"), "'string-parse-number' requires exactly 1 string");
+}
+
+#[test]
+fn there_are_no_rationals_to_parse() {
+    assert_eq!(run("(string-parse-number \"1/3\")"), "#f");
+}
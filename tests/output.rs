@@ -0,0 +1,168 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn call_with_string_output_port_returns_what_was_written() {
+    assert_eq!(
+        run("(call-with-string-output-port (lambda (p) (write \"hello\" p) (display 42 p) (newline p)))"),
+        "\"\\\"hello\\\"42\\n\""
+    );
+}
+
+#[test]
+fn with_string_output_port_is_the_same_procedure() {
+    assert_eq!(run("(with-string-output-port (lambda (p) (display 'sym p)))"), "\"sym\"");
+}
+
+#[test]
+fn string_output_ports_collect_writes() {
+    assert_eq!(run("(define p (open-output-string)) (write 'a p) (display \" b\" p) (get-output-string p)"), "\"a b\"");
+}
+
+#[test]
+fn display_writes_to_current_output_port() {
+    assert_eq!(output("(display \"x\") (write \"y\") (newline)"), "x\"y\"\n");
+}
+
+#[test]
+fn pretty_print_keeps_short_forms_on_one_line() {
+    assert_eq!(output("(pretty-print '(1 2 3))"), "(1 2 3)\n");
+}
+
+#[test]
+fn pretty_print_breaks_forms_wider_than_the_width() {
+    assert_eq!(
+        output("(pretty-print '(define (f x) (if (> x 10) (list x x) (list x))) 20)"),
+        "(define (f x)\n  (if (> x 10)\n      (list x x)\n      (list x)))\n"
+    );
+}
+
+#[test]
+fn write_to_string_uses_the_written_representation() {
+    assert_eq!(run("(write-to-string '(1 \"two\" #\\3 sym))"), "\"(1 \\\"two\\\" #\\\\3 sym)\"");
+}
+
+#[test]
+fn read_from_string_reads_one_datum() {
+    assert_eq!(run("(read-from-string \"(a (b . c) #(1 2))\")"), "(a (b . c) #(1 2))");
+    assert_eq!(run("(car (read-from-string \"(+ 1 2)\"))"), "+");
+}
+
+#[test]
+fn write_and_read_round_trip() {
+    assert_eq!(
+        run("(define data '(1 2.5 \"a \\\"quoted\\\" string\" #\\space (nested (list)) #t))
+             (equal? data (read-from-string (write-to-string data)))"),
+        "#t"
+    );
+}
+
+#[test]
+fn with_output_redirection_sends_output_to_the_port() {
+    assert_eq!(
+        run("(define p (open-output-string))
+             (with-output-redirection p (lambda () (display \"hi\") (write 'x (current-output-port))))
+             (get-output-string p)"),
+        "\"hix\""
+    );
+}
+
+#[test]
+fn output_redirection_is_undone_after_an_error() {
+    assert_eq!(
+        output("(define inner (open-output-string))
+                (with-exception-handler (lambda (e) 0)
+                  (lambda () (with-output-redirection inner (lambda () (display \"lost\") (error \"x\")))))
+                (display \"kept\")"),
+        "kept"
+    );
+}
+
+#[test]
+fn with_input_redirection_reads_from_the_port() {
+    assert_eq!(
+        run("(with-input-redirection (open-input-string \"abc\")
+               (lambda () (list (read-char) (read-char (current-input-port)))))"),
+        "(#\\a #\\b)"
+    );
+}
+
+#[test]
+fn read_all_reads_every_datum_from_a_port() {
+    let path = std::env::temp_dir().join(format!("lisp-interpreter-read-all-{}.lisp", std::process::id()));
+    std::fs::write(&path, "; data\n(a 1)\n\"two\" ; trailing\n#(3)\n").unwrap();
+    let path = path.to_string_lossy().replace('\\', "/");
+    assert_eq!(run(&format!("(read-all (open-input-file \"{}\"))", path)), "((a 1) \"two\" #(3))");
+}
+
+#[test]
+fn read_all_starts_where_the_port_is() {
+    assert_eq!(run("(define p (open-input-string \"1 2 3\")) (read-char p) (read-all p)"), "(2 3)");
+}
+
+#[test]
+fn read_all_from_string_skips_comments_and_stops_at_eof() {
+    assert_eq!(run("(read-all-from-string \"1 (2 3) ; comment\n x\")"), "(1 (2 3) x)");
+    assert_eq!(run("(read-all-from-string \"\")"), "()");
+    assert_eq!(run_err("(read-all-from-string \"(1 2\")"), "Unexpected EOF");
+}
+
+#[test]
+fn special_forms_indent_their_body() {
+    assert_eq!(
+        run("(pretty-print-to-string '(lambda (x) (let ((y (* x x))) (+ y 1))) 20)"),
+        "\"(lambda (x)\\n  (let ((y (* x x)))\\n    (+ y 1)))\""
+    );
+}
+
+#[test]
+fn calls_align_their_arguments() {
+    assert_eq!(
+        run("(pretty-print-to-string '(foo aaaa bbbb cccc dddd) 12)"),
+        "\"(foo aaaa\\n     bbbb\\n     cccc\\n     dddd)\""
+    );
+}
+
+#[test]
+fn the_indent_width_is_configurable() {
+    assert_eq!(
+        run("(pp->string '(lambda (x) (let ((y (* x x))) (+ y 1))) 20 4)"),
+        "\"(lambda (x)\\n    (let ((y (* x x)))\\n        (+ y 1)))\""
+    );
+    assert_eq!(run_err("(pp->string 1 0)"), "Invalid width or indent for pretty-print-to-string");
+}
+
+#[test]
+fn pp_prints_with_a_newline() {
+    assert_eq!(output("(pp '(1 2))"), "(1 2)\n");
+}
+
+#[test]
+fn object_to_string_and_repr_write_the_object() {
+    assert_eq!(run("(object->string '(1 \"a\" #\\b))"), "\"(1 \\\"a\\\" #\\\\b)\"");
+    assert_eq!(run("(repr \"x\")"), "\"\\\"x\\\"\"");
+}
+
+#[test]
+fn string_to_object_reads_one_datum() {
+    assert_eq!(run("(string->object \"(+ 1 2)\")"), "(+ 1 2)");
+    assert_eq!(run("(car (string->object \"(+ 1 2)\"))"), "+");
+    assert_eq!(run_err("(string->object \"1 2\")"), "Unexpected input after datum: 2");
+}
+
+#[test]
+fn format_table_aligns_columns_and_displays_the_table() {
+    assert_eq!(
+        output("(format-table '((\"Alice\" 30) (\"Bob\" 25)) '(\"Name\" \"Age\"))"),
+        "Name  | Age\n------+----\nAlice | 30\nBob   | 25\n"
+    );
+}
+
+#[test]
+fn format_table_returns_the_table() {
+    assert_eq!(
+        run("(define p (open-output-string)) (with-output-redirection p (lambda () (format-table '((x 1)) '(\"Symbol\" \"N\"))))"),
+        "\"Symbol | N\\n-------+--\\nx      | 1\\n\""
+    );
+}
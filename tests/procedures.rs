@@ -0,0 +1,131 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn memoize_caches_results_by_argument() {
+    assert_eq!(
+        run("(define calls 0)
+             (define square (memoize (lambda (x) (set! calls (+ calls 1)) (* x x))))
+             (list (square 3) (square 3) (square 4) calls)"),
+        "(9 9 16 2)"
+    );
+}
+
+#[test]
+fn memoize_compares_arguments_structurally() {
+    assert_eq!(
+        run("(define calls 0)
+             (define total (memoize (lambda (xs) (set! calls (+ calls 1)) (apply + xs))))
+             (total (list 1 2 3))
+             (total (list 1 2 3))
+             calls"),
+        "1"
+    );
+}
+
+#[test]
+fn memoize_handles_several_arguments() {
+    assert_eq!(
+        run("(define calls 0)
+             (define add (memoize (lambda (a b) (set! calls (+ calls 1)) (+ a b))))
+             (list (add 1 2) (add 1 2) (add 2 1) calls)"),
+        "(3 3 3 2)"
+    );
+}
+
+#[test]
+fn memoized_recursion_uses_the_cache() {
+    assert_eq!(
+        run("(define fib-memo (memoize (lambda (n) (if (< n 2) n (+ (fib-memo (- n 1)) (fib-memo (- n 2)))))))
+             (fib-memo 60)"),
+        "1548008755920"
+    );
+}
+
+#[test]
+fn memoize_clear_empties_the_cache() {
+    assert_eq!(
+        run("(define calls 0)
+             (define f (memoize (lambda (x) (set! calls (+ calls 1)) x)))
+             (f 1)
+             (memoize-clear! f)
+             (f 1)
+             (list calls (memoized? f) (memoized? car))"),
+        "(2 #t #f)"
+    );
+}
+
+#[test]
+fn memoize_rejects_non_procedures() {
+    assert_eq!(run_err("(memoize 5)"), "'memoize' requires a procedure");
+}
+
+#[test]
+fn void_is_its_own_value() {
+    assert_eq!(run("(list (void? (void)) (void? 0) (void? '()) (void? #f))"), "(#t #f #f #f)");
+    assert_eq!(run("(void)"), "#<void>");
+}
+
+#[test]
+fn mutation_returns_void() {
+    assert_eq!(
+        run("(define x 0)
+             (define v (vector 1 2))
+             (list (void? (set! x 1)) (void? (vector-set! v 0 5)) (void? (for-each car '())))"),
+        "(#t #t #t)"
+    );
+}
+
+#[test]
+fn void_rejects_the_wrong_number_of_arguments() {
+    assert_eq!(run_err("(void?)"), "'void?' requires exactly 1 argument");
+}
+
+#[test]
+fn a_parameter_returns_its_value() {
+    assert_eq!(run("(define p (make-parameter 10)) (p)"), "10");
+}
+
+#[test]
+fn parameterize_rebinds_for_the_body_only() {
+    assert_eq!(
+        run("(define p (make-parameter 10))
+             (define (show) (p))
+             (list (parameterize ((p 20)) (show)) (show))"),
+        "(20 10)"
+    );
+}
+
+#[test]
+fn parameterize_restores_the_value_after_an_error() {
+    assert_eq!(
+        run("(define p (make-parameter 1))
+             (with-exception-handler (lambda (e) 'caught) (lambda () (parameterize ((p 2)) (error \"boom\"))))
+             (p)"),
+        "1"
+    );
+}
+
+#[test]
+fn the_converter_applies_to_the_default_and_each_binding() {
+    assert_eq!(
+        run("(define p (make-parameter 10 (lambda (x) (* x 2))))
+             (list (p) (parameterize ((p 3)) (p)))"),
+        "(20 6)"
+    );
+}
+
+#[test]
+fn parameterize_nests() {
+    assert_eq!(
+        run("(define p (make-parameter 'outer))
+             (parameterize ((p 'middle)) (list (p) (parameterize ((p 'inner)) (p)) (p)))"),
+        "(middle inner middle)"
+    );
+}
+
+#[test]
+fn parameterize_requires_parameters() {
+    assert_eq!(run_err("(define x 1) (parameterize ((x 2)) x)"), "Not a parameter: 1");
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::CountingAllocator;
+
+// As in the interpreter binary, so memory-limit-bytes can be enforced
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn sandbox_eval_evaluates_in_the_given_environment() {
+    assert_eq!(run("(sandbox-eval '(+ 1 2) (scheme-report-environment 7))"), "3");
+    assert_eq!(run_err("(sandbox-eval '(+ 1 2) (null-environment 7))"), "Undefined function: +");
+}
+
+#[test]
+fn sandbox_eval_stops_at_the_time_limit() {
+    assert_eq!(
+        run_err("(sandbox-eval '(let loop () (loop)) (scheme-report-environment 7) '((time-limit-ms . 50)))"),
+        "Sandboxed evaluation exceeded its time limit of 50ms"
+    );
+}
+
+#[test]
+fn sandbox_eval_stops_at_the_memory_limit() {
+    assert_eq!(
+        run_err("(sandbox-eval '(let ((v (make-vector 200000 0))) (vector-length v)) (scheme-report-environment 7)
+                               '((memory-limit-bytes . 1048576)))"),
+        "Sandboxed evaluation exceeded its memory limit of 1048576 bytes"
+    );
+}
+
+#[test]
+fn deep_recursion_in_a_sandbox_is_an_error() {
+    assert!(try_run("(sandbox-eval '(begin (define (f n) (+ 1 (f n))) (f 0)) (scheme-report-environment 7))").is_err());
+}
+
+#[test]
+fn sandbox_errors_can_be_handled() {
+    assert_eq!(
+        run("(with-exception-handler (lambda (e) 'stopped)
+               (lambda () (sandbox-eval '(let loop () (loop)) (scheme-report-environment 7) '((time-limit-ms . 10)))))"),
+        "stopped"
+    );
+}
+
+#[test]
+fn sandbox_eval_rejects_unknown_options() {
+    assert_eq!(
+        run_err("(sandbox-eval 1 (null-environment 7) '((fuel . 10)))"),
+        "Invalid sandbox-eval option: (fuel . 10)"
+    );
+}
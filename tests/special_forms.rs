@@ -0,0 +1,293 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::Environment;
+
+#[test]
+fn cond_expand_takes_the_first_supported_feature() {
+    assert_eq!(run("(cond-expand (no-such-feature 1) (lisp-interpreter 2) (else 3))"), "2");
+    assert_eq!(run("(cond-expand (no-such-feature 1) (else 3))"), "3");
+    assert_eq!(run("(features)"), "(lisp-interpreter)");
+}
+
+#[test]
+fn cond_expand_combines_requirements() {
+    assert_eq!(run("(cond-expand ((and lisp-interpreter (not windows-95)) 1) (else 0))"), "1");
+    assert_eq!(run("(cond-expand ((or no-such-feature lisp-interpreter) 1) (else 0))"), "1");
+}
+
+#[test]
+fn cond_expand_sees_features_added_by_the_host() {
+    let mut env = Environment::new();
+    env.add_feature("my-feature");
+    assert_eq!(eval_in("(cond-expand (my-feature 1) (else 0))", &mut env), "1");
+}
+
+#[test]
+fn let_values_binds_every_value() {
+    assert_eq!(run("(let-values (((a b) (values 1 2)) ((c) (values 3))) (values a b c))"), "1 2 3");
+    assert_eq!(run("(let-values (((a . rest) (values 1 2 3))) (values a rest))"), "1 (2 3)");
+}
+
+#[test]
+fn let_values_initializers_do_not_see_each_other() {
+    assert_eq!(run("(define a 10) (let-values (((a) (values 1)) ((b) (values a))) b)"), "10");
+}
+
+#[test]
+fn define_values_defines_each_name() {
+    assert_eq!(run("(define-values (a b) (values 4 5)) (+ a b)"), "9");
+    assert_eq!(run("(define-values (q r) (floor/ 17 5)) (list q r)"), "(3 2)");
+}
+
+#[test]
+fn let_values_rejects_the_wrong_number_of_values() {
+    run_err("(let-values (((a b) (values 1 2 3))) a)");
+}
+
+#[test]
+fn call_with_values_passes_every_value_to_the_consumer() {
+    assert_eq!(run("(call-with-values (lambda () (values 1 2 3)) list)"), "(1 2 3)");
+    assert_eq!(run("(call-with-values (lambda () (values 1 2)) +)"), "3");
+    assert_eq!(run("(call-with-values (lambda () (values)) list)"), "()");
+}
+
+#[test]
+fn a_single_value_is_not_wrapped() {
+    assert_eq!(run("(call-with-values (lambda () 5) list)"), "(5)");
+    assert_eq!(run("(+ 1 (values 2))"), "3");
+}
+
+#[test]
+fn call_with_values_works_with_native_producers() {
+    assert_eq!(run("(call-with-values (lambda () (values 3 2)) list)"), "(3 2)");
+    assert_eq!(run("(call-with-values (lambda () (partition odd? '(1 2 3))) (lambda (odds evens) evens))"), "(2)");
+}
+
+#[test]
+fn receive_binds_values_to_formals() {
+    assert_eq!(run("(receive (q r) (floor/ 17 5) (list q r))"), "(3 2)");
+    assert_eq!(run("(receive (first . rest) (values 1 2 3) (list first rest))"), "(1 (2 3))");
+    assert_eq!(run("(receive all (values 1 2) all)"), "(1 2)");
+}
+
+#[test]
+fn with_values_spreads_an_expression_over_the_receiver() {
+    assert_eq!(run("(with-values (values 1 2) +)"), "3");
+    assert_eq!(run("(with-values (floor/ 17 5) (lambda (q r) (list q r)))"), "(3 2)");
+    assert_eq!(run("(with-values 5 list)"), "(5)");
+    assert_eq!(run_err("(with-values 5 6)"), "'with-values' requires a value and a receiver procedure");
+}
+
+#[test]
+fn derived_forms_evaluate_like_their_expansions() {
+    assert_eq!(run("(and 1 2 3)"), "3");
+    assert_eq!(run("(and 1 #f 3)"), "#f");
+    assert_eq!(run("(and)"), "#t");
+    assert_eq!(run("(or #f 2 (car 1))"), "2");
+    assert_eq!(run("(or)"), "#f");
+    assert_eq!(run("(cond ((odd? 4) 'odd) (else 'even))"), "even");
+    assert_eq!(run("(let* ((x 1) (y (+ x 1))) (list x y))"), "(1 2)");
+    assert_eq!(run("(let loop ((i 0) (acc '())) (if (= i 3) acc (loop (+ i 1) (cons i acc))))"), "(2 1 0)");
+    assert_eq!(run("(define x 0) (when (= x 0) (set! x 1) (+ x 1))"), "2");
+    assert_eq!(run("(unless #f 'ran)"), "ran");
+}
+
+#[test]
+fn macroexpand_1_expands_one_step() {
+    assert_eq!(run("(macroexpand-1 '(and a b))"), "(if a (and b) #f)");
+    assert_eq!(run("(macroexpand-1 '(let ((x 1)) x))"), "((lambda (x) x) 1)");
+    assert_eq!(run("(macroexpand-1 '(+ 1 2))"), "(+ 1 2)");
+}
+
+#[test]
+fn macroexpand_expands_until_the_head_is_a_core_form() {
+    assert_eq!(run("(macroexpand '(and a b))"), "(if a b #f)");
+    assert_eq!(run("(macroexpand '(when x 1 2))"), "(if x (begin 1 2) (begin))");
+}
+
+#[test]
+fn define_syntax_macros_are_hygienic() {
+    assert_eq!(
+        run("(define-syntax swap! (syntax-rules () ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))
+             (define tmp 1)
+             (define y 2)
+             (swap! tmp y)
+             (list tmp y)"),
+        "(2 1)"
+    );
+}
+
+#[test]
+fn let_syntax_scopes_the_macro_to_its_body() {
+    assert_eq!(run("(let-syntax ((double (syntax-rules () ((_ x) (* 2 x))))) (double 5))"), "10");
+    assert_eq!(
+        run_err("(let-syntax ((double (syntax-rules () ((_ x) (* 2 x))))) 1) (double 2)"),
+        "Undefined function: double"
+    );
+}
+
+#[test]
+fn letrec_syntax_macros_can_refer_to_themselves() {
+    assert_eq!(
+        run("(letrec-syntax ((my-or (syntax-rules ()
+                                      ((_) #f)
+                                      ((_ e) e)
+                                      ((_ e r ...) (let ((t e)) (if t t (my-or r ...)))))))
+               (let ((t 5)) (my-or #f t)))"),
+        "5"
+    );
+}
+
+#[test]
+fn ellipsis_patterns_bind_sequences() {
+    assert_eq!(
+        run("(define-syntax my-let (syntax-rules () ((_ ((var val) ...) body ...) ((lambda (var ...) body ...) val ...))))
+             (my-let ((a 1) (b 2)) (+ a b))"),
+        "3"
+    );
+}
+
+#[test]
+fn nested_ellipses_replicate_subtemplates() {
+    assert_eq!(
+        run("(define-syntax groups (syntax-rules () ((_ (a b ...) ...) '((a (b ...)) ...))))
+             (groups (1 2 3) (4) (5 6))"),
+        "((1 (2 3)) (4 ()) (5 (6)))"
+    );
+}
+
+#[test]
+fn wildcards_and_literals_match() {
+    assert_eq!(run("(define-syntax second (syntax-rules () ((_ _ x) x))) (second 1 2)"), "2");
+    assert_eq!(
+        run("(define-syntax arrow (syntax-rules (=>) ((_ a => b) (list a b)) ((_ a b) 'no-arrow)))
+             (list (arrow 1 => 2) (arrow 1 2))"),
+        "((1 2) no-arrow)"
+    );
+}
+
+#[test]
+fn unmatched_uses_are_errors() {
+    assert_eq!(
+        run_err("(define-syntax m (syntax-rules () ((_ x) x))) (m 1 2)"),
+        "No syntax-rules pattern matches (m 1 2)"
+    );
+}
+
+#[test]
+fn identifier_syntax_expands_a_bare_identifier() {
+    assert_eq!(run("(define-syntax pi (identifier-syntax 3.5)) (* 2 pi)"), "7");
+}
+
+#[test]
+fn identifier_syntax_can_handle_set() {
+    assert_eq!(
+        run("(define store 0)
+             (define-syntax counter (identifier-syntax (_ store) ((set! _ v) (set! store (* v 10)))))
+             (set! counter 2)
+             (list counter store)"),
+        "(20 20)"
+    );
+    assert_eq!(
+        run_err("(define-syntax pi (identifier-syntax 3)) (set! pi 4)"),
+        "Cannot set! a macro without a set! clause: (set! pi 4)"
+    );
+}
+
+#[test]
+fn a_variable_transformer_receives_the_whole_form() {
+    assert_eq!(
+        run("(define-syntax seen (make-variable-transformer (lambda (form) (list 'quote form))))
+             (list seen (seen 1 2) (set! seen 3))"),
+        "(seen (seen 1 2) (set! seen 3))"
+    );
+}
+
+// Non-tail recursion 10000 calls deep overflows the stack of a test thread
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    assert_eq!(run("(define (loop n) (if (= n 0) 'done (loop (- n 1)))) (loop 10000)"), "done");
+    assert_eq!(run("(let loop ((i 0)) (if (< i 10000) (loop (+ i 1)) i))"), "10000");
+}
+
+#[test]
+fn mutually_recursive_tail_calls_run_in_constant_stack() {
+    assert_eq!(
+        run("(define (my-even? n) (if (= n 0) #t (my-odd? (- n 1))))
+             (define (my-odd? n) (if (= n 0) #f (my-even? (- n 1))))
+             (my-even? 10001)"),
+        "#f"
+    );
+}
+
+#[test]
+fn tail_position_reaches_through_cond_let_begin_and_and() {
+    assert_eq!(
+        run("(define (f n acc) (cond ((= n 0) acc) (else (let ((m (- n 1))) (begin (f m (+ acc 1)))))))
+             (f 10000 0)"),
+        "10000"
+    );
+    assert_eq!(run("(define (a n) (and #t (if (= n 0) 'ok (a (- n 1))))) (a 10000)"), "ok");
+}
+
+#[test]
+fn the_returns_values_that_satisfy_the_predicate() {
+    assert_eq!(run("(the integer? (+ 1 2))"), "3");
+    assert_eq!(run("(define x 1.5) (the (lambda (n) (> n 0)) x)"), "1.5");
+}
+
+#[test]
+fn the_reports_the_expression_and_its_value() {
+    assert_eq!(run_err("(define x 'a) (the number? x)"), "Type check failed: x is a, which does not satisfy number?");
+    assert_eq!(run_err("(the (lambda (n) (> n 0)) -1)"), "Type check failed: -1 does not satisfy (lambda (n) (> n 0))");
+}
+
+#[test]
+fn when_let_runs_the_body_only_for_true_values() {
+    assert_eq!(run("(when-let ((r (assoc 'b '((a . 1) (b . 2))))) (cdr r))"), "2");
+    assert_eq!(run("(void? (when-let ((r #f)) 'no))"), "#t");
+}
+
+#[test]
+fn if_let_chooses_a_branch() {
+    assert_eq!(run("(list (if-let ((r (member 2 '(1 2 3)))) r 'none) (if-let ((r #f)) r 'none))"), "((2 3) none)");
+}
+
+#[test]
+fn let_bindings_are_sequential_and_stop_at_the_first_false() {
+    assert_eq!(run("(when-let ((a 1) (b (+ a 1))) b)"), "2");
+    assert_eq!(run("(if-let ((a #f) (b (car 5))) 'both 'not-both)"), "not-both");
+    assert_eq!(run_err("(when-let (x 1) x)"), "Invalid binding in 'when-let': x");
+}
+
+#[test]
+fn fluid_let_rebinds_for_procedures_called_from_the_body() {
+    assert_eq!(run("(define x 1) (define (show) x) (list (fluid-let ((x 10)) (show)) x)"), "(10 1)");
+}
+
+#[test]
+fn fluid_let_restores_the_value_after_an_error_or_set() {
+    assert_eq!(
+        run("(define x 1) (with-exception-handler (lambda (e) 0) (lambda () (fluid-let ((x 10)) (error \"boom\")))) x"),
+        "1"
+    );
+    assert_eq!(run("(define x 1) (list (fluid-let ((x 10)) (set! x 20) x) x)"), "(20 1)");
+}
+
+#[test]
+fn fluid_let_values_are_evaluated_before_any_binding() {
+    assert_eq!(run("(define x 1) (define y 2) (fluid-let ((x y) (y x)) (list x y))"), "(2 1)");
+    assert_eq!(run_err("(fluid-let ((nope 1)) nope)"), "'fluid-let' requires a bound variable: nope");
+}
+
+#[test]
+fn gensyms_are_fresh_and_unreadable() {
+    assert_eq!(
+        run("(define g (symbol-gensym \"tmp\"))
+             (list (gensym? g) (gensym? 'tmp) (gensym? 5) (equal? g (symbol-gensym \"tmp\"))
+                   (equal? (read-from-string (write-to-string g)) g))"),
+        "(#t #f #f #f #f)"
+    );
+    assert_eq!(run_err("(symbol-gensym 5)"), "'symbol-gensym' requires a prefix string");
+}
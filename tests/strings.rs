@@ -0,0 +1,272 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn string_contains_gives_the_character_index() {
+    assert_eq!(run("(string-contains \"hello world\" \"world\")"), "6");
+    assert_eq!(run("(string-contains \"hello\" \"xyz\")"), "#f");
+    assert_eq!(run("(string-contains \"héllo wörld\" \"wö\")"), "6");
+    assert_eq!(run("(string-contains-ci \"Hello World\" \"WORLD\")"), "6");
+}
+
+#[test]
+fn prefix_and_suffix_take_the_affix_first() {
+    assert_eq!(run("(string-prefix? \"hel\" \"hello\")"), "#t");
+    assert_eq!(run("(string-prefix? \"hello\" \"hel\")"), "#f");
+    assert_eq!(run("(string-suffix? \"llo\" \"hello\")"), "#t");
+    assert_eq!(run("(string-prefix-ci? \"HEL\" \"hello\")"), "#t");
+    assert_eq!(run("(string-suffix-ci? \"LLO\" \"hello\")"), "#t");
+}
+
+#[test]
+fn string_split_defaults_to_whitespace() {
+    assert_eq!(run("(string-split \"  a b\\tc  \")"), "(\"a\" \"b\" \"c\")");
+    assert_eq!(run("(string-split \"a,b,,c\" #\\,)"), "(\"a\" \"b\" \"\" \"c\")");
+    assert_eq!(run("(string-split \"a::b\" \"::\")"), "(\"a\" \"b\")");
+}
+
+#[test]
+fn string_join_undoes_string_split() {
+    assert_eq!(run("(string-join '(\"a\" \"b\" \"c\") \", \")"), "\"a, b, c\"");
+    assert_eq!(run("(string-join '(\"a\" \"b\"))"), "\"ab\"");
+    assert_eq!(run("(string-join (string-split \"x-y-z\" #\\-) #\\-)"), "\"x-y-z\"");
+    assert_eq!(run_err("(string-join '(\"a\" 1) \",\")"), "Invalid element type for string-join");
+}
+
+#[test]
+fn string_trim_follows_srfi_13_sides() {
+    assert_eq!(run("(string-trim \"  ab  \")"), "\"ab  \"");
+    assert_eq!(run("(string-trim-right \"  ab  \")"), "\"  ab\"");
+    assert_eq!(run("(string-trim-both \"  ab  \")"), "\"ab\"");
+}
+
+#[test]
+fn string_trim_takes_a_character_or_predicate() {
+    assert_eq!(run("(string-trim-both \"xxabxx\" #\\x)"), "\"ab\"");
+    assert_eq!(run("(string-trim \"123abc\" char-numeric?)"), "\"abc\"");
+}
+
+#[test]
+fn string_pad_keeps_the_end_and_string_pad_right_the_start() {
+    assert_eq!(run("(string-pad \"42\" 5)"), "\"   42\"");
+    assert_eq!(run("(string-pad \"42\" 5 #\\0)"), "\"00042\"");
+    assert_eq!(run("(string-pad \"12345\" 3)"), "\"345\"");
+    assert_eq!(run("(string-pad-right \"ab\" 4 #\\.)"), "\"ab..\"");
+    assert_eq!(run("(string-pad-right \"12345\" 3)"), "\"123\"");
+}
+
+#[test]
+fn string_contains_regexp_gives_the_character_index_of_the_match() {
+    assert_eq!(run("(string-contains-regexp \"order 66\" \"[0-9]+\")"), "6");
+    assert_eq!(run("(string-contains-regexp \"né 42\" \"\\\\d\")"), "3");
+    assert_eq!(run("(string-contains-regexp \"no digits\" \"[0-9]\")"), "#f");
+}
+
+#[test]
+fn regexp_match_returns_the_match_and_its_groups() {
+    assert_eq!(run("(regexp-match \"(\\\\d+)-(\\\\d+)\" \"tel 123-456\")"), "(\"123-456\" \"123\" \"456\")");
+    assert_eq!(run("(regexp-match \"(a)|(b)\" \"b\")"), "(\"b\" #f \"b\")");
+    assert_eq!(run("(regexp-match \"x\" \"abc\")"), "#f");
+}
+
+#[test]
+fn regexp_replace_expands_group_references() {
+    assert_eq!(run("(regexp-replace \"o\" \"foo\" \"0\")"), "\"f0o\"");
+    assert_eq!(run("(regexp-replace-all \"o\" \"foo\" \"0\")"), "\"f00\"");
+    assert_eq!(run("(regexp-replace-all \"(\\\\w+)@(\\\\w+)\" \"a@b c@d\" \"\\\\2 at \\\\1\")"), "\"b at a d at c\"");
+}
+
+#[test]
+fn make_regexp_compiles_once_with_flags() {
+    assert_eq!(run("(define r (make-regexp \"hello\" \"i\")) (values (regexp? r) (regexp-match r \"say HeLLo\"))"), "#t (\"HeLLo\")");
+    assert_eq!(run_err("(make-regexp \"a\" \"x\")"), "Unknown regexp flag: x");
+    assert!(run_err("(make-regexp \"(\")").starts_with("Invalid regexp \"(\""));
+}
+
+#[test]
+fn regexps_match_in_linear_time() {
+    // Catastrophic for a backtracking matcher
+    let subject = format!("{}!", "a".repeat(5000));
+    assert_eq!(run(&format!("(regexp-match \"^(a+)+$\" \"{}\")", subject)), "#f");
+}
+
+#[test]
+fn predefined_char_sets_are_unicode_aware() {
+    assert_eq!(
+        run("(list (char-set-member? char-set:alphabetic #\\a) (char-set-member? char-set:alphabetic #\\é)
+                   (char-set-member? char-set:numeric #\\a) (char-set-member? char-set:whitespace #\\space))"),
+        "(#t #t #f #t)"
+    );
+}
+
+#[test]
+fn char_sets_combine() {
+    assert_eq!(
+        run("(define vowels (string->char-set \"aeiou\"))
+             (list (char-set-member? (char-set-intersection vowels char-set:alphabetic) #\\e)
+                   (char-set-member? (char-set-union vowels char-set:numeric) #\\5)
+                   (char-set-member? (char-set-difference char-set:alphabetic vowels) #\\e)
+                   (char-set-member? (char-set-complement vowels) #\\e))"),
+        "(#t #t #f #f)"
+    );
+}
+
+#[test]
+fn char_set_builds_a_set_from_characters() {
+    assert_eq!(run("(list (char-set-size (char-set #\\a #\\b #\\a)) (char-set? (char-set)) (char-set? \"a\"))"), "(2 #t #f)");
+}
+
+#[test]
+fn string_filter_takes_a_char_set_or_a_predicate() {
+    assert_eq!(run("(string-filter char-set:numeric \"a1b2c3\")"), "\"123\"");
+    assert_eq!(run("(string-filter char-alphabetic? \"a1b2c3\")"), "\"abc\"");
+}
+
+#[test]
+fn char_set_member_requires_a_char_set() {
+    assert_eq!(run_err("(char-set-member? 5 #\\a)"), "Invalid argument type for char-set-member?");
+}
+
+#[test]
+fn string_number_radix_reads_the_prefix() {
+    assert_eq!(
+        run("(list (string-number-radix \"#xFF\") (string-number-radix \"#o17\") (string-number-radix \"#b1010\")
+                   (string-number-radix \"#d42\") (string-number-radix \"12\"))"),
+        "(255 15 10 42 12)"
+    );
+}
+
+#[test]
+fn string_number_radix_returns_false_for_non_numbers() {
+    assert_eq!(run("(list (string-number-radix \"zz\") (string-number-radix \"#xZZ\"))"), "(#f #f)");
+    assert_eq!(run_err("(string-number-radix 5)"), "'string-number-radix' requires exactly 1 string");
+}
+
+#[test]
+fn prefixed_number_literals_are_read_as_numbers() {
+    assert_eq!(run("(list #xff #x-1A #b101 #o10 #e15 #i3 #i1.5 #E#x10 '#x10)"), "(255 -26 5 8 15 3 1.5 16 16)");
+}
+
+#[test]
+fn an_exact_prefix_only_admits_integers() {
+    assert_eq!(run_err("#e1.5"), "Cannot read #e1.5 as an exact number: only integers are exact");
+    assert_eq!(run_err("#d#e0.5"), "Cannot read #d#e0.5 as an exact number: only integers are exact");
+    assert_eq!(run("(list (string-number-radix \"#e1.5\") (string-number-radix \"#e1.0\"))"), "(#f 1)");
+}
+
+#[test]
+fn string_ref_counts_characters() {
+    assert_eq!(run("(list (string-ref \"hello\" 1) (string-ref \"héllo\" 1))"), "(#\\e #\\é)");
+    assert_eq!(run_err("(string-ref \"abc\" 3)"), "Index 3 is out of range for a string of length 3");
+}
+
+#[test]
+fn string_contains_finds_substrings() {
+    assert_eq!(run("(list (string-contains? \"hello world\" \"world\") (string-contains? \"hello\" \"z\"))"), "(#t #f)");
+}
+
+#[test]
+fn string_index_searches_from_either_end() {
+    assert_eq!(
+        run("(list (string-index \"a b c\" char-whitespace?) (string-index-right \"a b c\" char-whitespace?)
+                   (string-index \"abc\" char-numeric?) (string-index \"abc\" #\\b))"),
+        "(1 3 #f 1)"
+    );
+    assert_eq!(run_err("(string-index \"abc\" 5)"), "Invalid argument type for string-index");
+}
+
+#[test]
+fn string_replace_replaces_a_character_range() {
+    assert_eq!(run("(string-replace \"Hello World\" \"LISP\" 6 11)"), "\"Hello LISP\"");
+    assert_eq!(run("(string-replace \"héllo\" \"E\" 1 2)"), "\"hEllo\"");
+    assert_eq!(run_err("(string-replace \"abc\" \"x\" 2 1)"), "Invalid range for string-replace: 2 to 1 of 3");
+    assert_eq!(run_err("(string-replace \"abc\" \"x\" 0 9)"), "Invalid range for string-replace: 0 to 9 of 3");
+}
+
+#[test]
+fn string_substitute_replaces_every_occurrence() {
+    assert_eq!(run("(list (string-substitute \"a,b,c\" \",\" \" \") (string-substitute \"abc\" \"x\" \"y\"))"), "(\"a b c\" \"abc\")");
+    assert_eq!(run_err("(string-substitute \"abc\" \"\" \"x\")"), "'string-substitute' cannot replace the empty string");
+}
+
+#[test]
+fn string_replace_first_replaces_one_occurrence() {
+    assert_eq!(run("(list (string-replace-first \"a-b-c\" \"-\" \"+\") (string-replace-first \"abc\" \"x\" \"y\"))"), "(\"a+b-c\" \"abc\")");
+}
+
+#[test]
+fn character_class_predicates() {
+    assert_eq!(
+        run("(list (char-alphabetic? #\\a) (char-alphabetic? #\\é) (char-alphabetic? #\\1) (char-numeric? #\\7)
+                   (char-whitespace? #\\space) (char-whitespace? #\\a))"),
+        "(#t #t #f #t #t #f)"
+    );
+}
+
+#[test]
+fn character_case_predicates_and_conversions() {
+    assert_eq!(
+        run("(list (char-upper-case? #\\A) (char-upper-case? #\\a) (char-lower-case? #\\a) (char-lower-case? #\\1))"),
+        "(#t #f #t #f)"
+    );
+    assert_eq!(run("(list (char-upcase #\\a) (char-downcase #\\Q) (char-upcase #\\1) (char-upcase #\\ß))"), "(#\\A #\\q #\\1 #\\ß)");
+}
+
+#[test]
+fn character_procedures_take_one_character() {
+    assert_eq!(run_err("(char-alphabetic? \"a\")"), "'char-alphabetic?' requires exactly 1 character");
+    assert_eq!(run_err("(char-upcase #\\a #\\b)"), "'char-upcase' requires exactly 1 character");
+}
+
+#[test]
+fn list_to_string_join_puts_the_separator_between_strings() {
+    assert_eq!(run("(list->string/join '(\"a\" \"b\" \"c\") \", \")"), "\"a, b, c\"");
+    assert_eq!(run("(list->string/join '() \",\")"), "\"\"");
+    assert_eq!(run_err("(list->string/join '(1 2) \",\")"), "Invalid element for list->string/join: 1");
+}
+
+#[test]
+fn string_to_words_and_lines_split_text() {
+    assert_eq!(run("(string->words \"  a  b\\tc\\n\")"), "(\"a\" \"b\" \"c\")");
+    assert_eq!(run("(string->lines \"x\\ny\\r\\nz\\n\")"), "(\"x\" \"y\" \"z\")");
+}
+
+#[test]
+fn strings_build_from_characters() {
+    assert_eq!(run("(list (char->string #\\a) (string #\\h #\\i) (string) (apply string (string->list \"abc\")))"), "(\"a\" \"hi\" \"\" \"abc\")");
+    assert_eq!(run_err("(string 1)"), "Invalid element for string: 1");
+    assert_eq!(run_err("(char->string \"a\")"), "'char->string' requires exactly 1 character");
+}
+
+#[test]
+fn make_string_fills_with_a_character() {
+    assert_eq!(run("(list (make-string 3 #\\x) (make-string 2))"), "(\"xxx\" \"  \")");
+    assert_eq!(run_err("(make-string -1 #\\a)"), "Invalid index for make-string: -1");
+}
+
+#[test]
+fn string_count_counts_matching_characters() {
+    assert_eq!(
+        run("(list (string-count \"hello world\" char-whitespace?) (string-count \"abc123\" char-numeric?)
+                   (string-count \"banana\" #\\a) (string-count \"a1b2\" char-set:numeric))"),
+        "(1 3 3 2)"
+    );
+    assert_eq!(run_err("(string-count \"abc\" 5)"), "Invalid argument type for string-count");
+}
+
+#[test]
+fn string_every_and_string_any() {
+    assert_eq!(
+        run("(list (string-every char-numeric? \"123\") (string-every char-numeric? \"12a\") (string-every char-numeric? \"\"))"),
+        "(#t #f #t)"
+    );
+    assert_eq!(
+        run("(list (string-any char-numeric? \"ab1\") (string-any char-numeric? \"\") (string-any #\\z \"abc\"))"),
+        "(#t #f #f)"
+    );
+    assert_eq!(
+        run_err("(string-every \"abc\" char-numeric?)"),
+        "'string-every' requires a char-set, character or predicate and a string"
+    );
+}
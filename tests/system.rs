@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use lisp_interpreter::interpreter::LispError;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+// Answers one HTTP request with `status` and `body`, and hands back the request line and body it got
+fn serve_once(status: &str, body: &'static str) -> (String, JoinHandle<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/path", listener.local_addr().unwrap());
+    let status = status.to_string();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut request_body = vec![0; content_length];
+        reader.read_exact(&mut request_body).unwrap();
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nX-Test: yes\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        (request_line.trim().to_string(), String::from_utf8(request_body).unwrap())
+    });
+    (url, server)
+}
+
+#[test]
+fn http_get_returns_status_body_and_headers() {
+    let (url, server) = serve_once("200 OK", "hello");
+    let result = run(&format!("(http-get \"{}\")", url));
+    assert!(result.starts_with("(200 \"hello\" ("), "{}", result);
+    assert!(result.contains("(\"x-test\" . \"yes\")"), "{}", result);
+    assert_eq!(server.join().unwrap().0, "GET /path HTTP/1.1");
+}
+
+#[test]
+fn http_post_sends_the_body() {
+    let (url, server) = serve_once("201 Created", "made");
+    assert_eq!(run(&format!("(car (http-post \"{}\" \"payload\"))", url)), "201");
+    assert_eq!(server.join().unwrap(), ("POST /path HTTP/1.1".to_string(), "payload".to_string()));
+}
+
+#[test]
+fn http_errors_name_the_url_and_status() {
+    let (url, server) = serve_once("404 Not Found", "missing");
+    assert_eq!(run_err(&format!("(http-get \"{}\")", url)), format!("HTTP request to {} failed with status 404", url));
+    server.join().unwrap();
+    assert!(run_err("(http-get \"http://127.0.0.1:1/\")").starts_with("HTTP request to http://127.0.0.1:1/ failed: "));
+}
+
+// A fresh directory for one test, written with forward slashes so it can go in a Lisp string
+fn scratch_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("lisp-interpreter-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    dir.to_string_lossy().replace('\\', "/")
+}
+
+#[test]
+fn files_can_be_listed_renamed_and_deleted() {
+    let dir = scratch_dir("files");
+    std::fs::write(format!("{}/b.txt", dir), "12345").unwrap();
+    std::fs::write(format!("{}/a.txt", dir), "").unwrap();
+
+    let result = run(&format!(
+        "(define dir \"{dir}\")
+         (define before (directory-files dir))
+         (rename-file \"{dir}/a.txt\" \"{dir}/c.txt\")
+         (delete-file \"{dir}/b.txt\")
+         (values before (directory-files dir) (file-exists? \"{dir}/b.txt\"))"
+    ));
+    assert_eq!(result, "(\"a.txt\" \"b.txt\") (\"c.txt\") #f");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file_metadata_is_available() {
+    let dir = scratch_dir("metadata");
+    std::fs::write(format!("{}/data", dir), "12345").unwrap();
+
+    let result = run(&format!(
+        "(define path \"{dir}/data\")
+         (make-directory \"{dir}/sub\")
+         (values (file-exists? path) (file-size path) (directory-files \"{dir}\"))"
+    ));
+    assert_eq!(result, "#t 5 (\"data\" \"sub\")");
+    let modified: f64 = run(&format!("(file-modification-time \"{dir}/data\")")).parse().unwrap();
+    assert!(modified > 0.0);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file_errors_are_file_errors() {
+    let dir = scratch_dir("errors");
+    let message = run_err(&format!("(delete-file \"{}/missing\")", dir));
+    assert!(message.starts_with(&format!("Cannot delete {}/missing", dir)), "{}", message);
+    assert!(matches!(
+        try_run(&format!("(delete-file \"{}/missing\")", dir)),
+        Err(LispError::FileError(_))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn command_line_lists_the_arguments_the_host_set() {
+    use lisp_interpreter::interpreter::Environment;
+
+    let mut env = Environment::new();
+    env.set_command_line(vec!["script.lisp".to_string(), "--flag".to_string()]);
+    assert_eq!(eval_in("(command-line)", &mut env), "(\"script.lisp\" \"--flag\")");
+}
+
+#[test]
+fn environment_variables_are_read_from_the_process() {
+    let path = std::env::var("PATH").unwrap();
+    assert_eq!(run("(get-environment-variable \"PATH\")"), format!("{:?}", path));
+    assert_eq!(run("(get-environment-variable \"LISP_INTERPRETER_SURELY_UNSET\")"), "#f");
+    assert!(run("(get-environment-variables)").contains(&format!("(\"PATH\" . {:?})", path)));
+}
+
+#[test]
+fn dates_convert_to_seconds_since_the_epoch() {
+    assert_eq!(run("(time->seconds (date->time 1970 1 1))"), "0");
+    assert_eq!(run("(time->seconds (date->time 2000 3 1 12 30 15))"), "951913815");
+    assert_eq!(run("(time->date (date->time 2024 2 29 23 59 30))"), "(2024 2 29 23 59 30)");
+    assert_eq!(run("(time->date (date->time 1969 12 31 23 0 0))"), "(1969 12 31 23 0 0)");
+}
+
+#[test]
+fn date_to_time_rejects_dates_that_do_not_exist() {
+    assert_eq!(run_err("(date->time 2023 2 29)"), "Invalid date for date->time: 2023-2-29");
+    run_err("(date->time 2023 13 1)");
+}
+
+#[test]
+fn time_difference_gives_a_duration() {
+    assert_eq!(run("(time->seconds (time-difference (date->time 2000 1 2) (date->time 2000 1 1)))"), "86400");
+    let elapsed: f64 = run("(define start (current-time)) (time->seconds (time-difference (current-time) start))")
+        .parse()
+        .unwrap();
+    assert!(elapsed >= 0.0);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn test_end_returns_and_reports_the_counts() {
+    assert_eq!(
+        output("(test-begin \"arith\")
+                (test-equal \"sum\" 4 (+ 2 2))
+                (test-equal \"product\" 5 (* 2 2))
+                (test-assert (odd? 3))
+                (test-error (car 1))
+                (display (test-end \"arith\"))"),
+        "FAIL product: expected 5 but got 4\n%%%% Test suite arith: 3 passed, 1 failed\n((pass . 3) (fail . 1))"
+    );
+}
+
+#[test]
+fn unnamed_tests_are_reported_by_their_expression() {
+    assert_eq!(
+        output("(test-begin \"s\") (test-assert (even? 3)) (test-error 'eval (+ 1 1)) (test-end)"),
+        "FAIL (even? 3): (even? 3) returned #f\nFAIL (+ 1 1): expected an error but got 2\n%%%% Test suite s: 0 passed, 2 failed\n"
+    );
+}
+
+#[test]
+fn test_error_checks_the_error_type() {
+    assert_eq!(
+        output("(test-begin \"s\") (test-error \"kind\" 'file (car 1)) (test-error 'eval (car 1)) (test-end)"),
+        "FAIL kind: expected an error of type file but got Invalid argument type for car\n%%%% Test suite s: 1 passed, 1 failed\n"
+    );
+}
+
+#[test]
+fn nested_suites_count_towards_the_outer_one() {
+    assert_eq!(
+        run("(define out (open-output-string))
+             (with-output-redirection out (lambda ()
+               (test-begin \"outer\")
+               (test-assert #t)
+               (test-begin \"inner\")
+               (test-assert #f)
+               (test-end \"inner\")
+               (define current (test-runner-current))
+               (test-end \"outer\")))"),
+        "((pass . 1) (fail . 1))"
+    );
+}
+
+#[test]
+fn test_runner_current_describes_the_open_suite() {
+    assert_eq!(run("(test-runner-current)"), "#f");
+    assert_eq!(
+        output("(test-begin \"s\") (test-assert #t) (write (test-runner-current)) (test-end)"),
+        "((suite . \"s\") (pass . 1) (fail . 0))%%%% Test suite s: 1 passed, 0 failed\n"
+    );
+}
+
+#[test]
+fn test_end_must_match_test_begin() {
+    assert_eq!(run_err("(test-end)"), "'test-end' without a matching 'test-begin'");
+    assert_eq!(run_err("(test-begin \"a\") (test-end \"b\")"), "'test-end' of b does not match 'test-begin' of a");
+}
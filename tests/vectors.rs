@@ -0,0 +1,137 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn string_for_each_visits_each_character() {
+    assert_eq!(output("(string-for-each (lambda (c) (display (char-upcase c))) \"abc\")"), "ABC");
+    assert_eq!(output("(string-for-each (lambda (a b) (display a) (display b)) \"ab\" \"xyz\")"), "axby");
+}
+
+#[test]
+fn string_map_builds_a_new_string() {
+    assert_eq!(run("(string-map char-upcase \"abc\")"), "\"ABC\"");
+    assert_eq!(run("(string-map (lambda (a b) b) \"ab\" \"xyz\")"), "\"xy\"");
+}
+
+#[test]
+fn vector_for_each_visits_each_element() {
+    assert_eq!(output("(vector-for-each display #(1 2 3))"), "123");
+    assert_eq!(output("(vector-for-each (lambda (x y) (display (+ x y))) #(1 2) #(3 4))"), "46");
+}
+
+#[test]
+fn vector_map_stops_at_the_shortest_vector() {
+    assert_eq!(run("(vector-map (lambda (x) (* x x)) #(1 2 3))"), "#(1 4 9)");
+    assert_eq!(run("(vector-map + #(1 2) #(10 20 30))"), "#(11 22)");
+}
+
+#[test]
+fn vector_map_rejects_non_vectors() {
+    assert_eq!(run_err("(vector-map car 5)"), "Invalid argument type for vector-map");
+}
+
+#[test]
+fn a_vector_that_contains_itself_is_written_with_a_datum_label() {
+    assert_eq!(run("(define v (vector 1)) (vector-set! v 0 v) v"), "#0=#(#0#)");
+    assert_eq!(run("(define v (vector 1 (vector 2))) (vector-set! (vector-ref v 1) 0 v) v"), "#0=#(1 #(#0#))");
+    assert_eq!(run("(define v (vector 1 2)) (vector v v)"), "#(#(1 2) #(1 2))");
+}
+
+#[test]
+fn vectors_that_contain_themselves_compare_and_hash_in_finite_time() {
+    let setup = "(define v (vector 1 2)) (vector-set! v 0 v) (define w (vector 1 2)) (vector-set! w 0 w) ";
+    assert_eq!(run(&format!("{setup}(list (equal? v w) (equal? v v) (equal? v (vector 1 2)))")), "(#t #t #f)");
+    assert_eq!(run(&format!("{setup}(= (object-hash v) (object-hash w))")), "#t");
+    assert_eq!(run(&format!("{setup}(define h (make-hash-table)) (hash-table-set! h v 'x) (hash-table-ref h w)")), "x");
+}
+
+#[test]
+fn vector_copy_bang_copies_into_place() {
+    assert_eq!(run("(define v (vector 1 2 3 4 5)) (vector-copy! v 0 #(a b c) 1) v"), "#(b c 3 4 5)");
+    assert_eq!(run("(define v (vector 1 2 3 4 5)) (vector-copy! v 3 #(a b c) 0 2) v"), "#(1 2 3 a b)");
+}
+
+#[test]
+fn vector_copy_bang_handles_overlap() {
+    assert_eq!(run("(define v (vector 1 2 3 4 5)) (vector-copy! v 1 v 0 3) v"), "#(1 1 2 3 5)");
+    assert_eq!(run("(define v (vector 1 2 3 4 5)) (vector-copy! v 0 v 2) v"), "#(3 4 5 4 5)");
+}
+
+#[test]
+fn vector_copy_bang_rejects_a_short_destination() {
+    assert_eq!(
+        run_err("(vector-copy! (vector 1 2) 1 #(1 2 3))"),
+        "Destination too short for vector-copy!: 1 + 3 > 2"
+    );
+}
+
+#[test]
+fn string_copy_takes_a_range() {
+    assert_eq!(
+        run("(list (string-copy \"hello\") (string-copy \"hello\" 1 3) (string-copy \"hello\" 3))"),
+        "(\"hello\" \"el\" \"lo\")"
+    );
+}
+
+#[test]
+fn there_is_no_string_copy_bang_for_immutable_strings() {
+    assert_eq!(run_err("(string-copy! \"abc\" 0 \"x\")"), "Undefined function: string-copy!");
+}
+
+#[test]
+fn list_and_vector_convert_both_ways() {
+    assert_eq!(run("(list->vector '(a b c))"), "#(a b c)");
+    assert_eq!(run("(vector->list #(1 2 3))"), "(1 2 3)");
+    assert_eq!(run("(list->vector '())"), "#()");
+}
+
+#[test]
+fn vector_to_list_and_vector_copy_take_a_range() {
+    assert_eq!(run("(vector->list #(1 2 3 4 5) 1 3)"), "(2 3)");
+    assert_eq!(run("(vector->list #(1 2 3) 1)"), "(2 3)");
+    assert_eq!(run("(vector-copy #(1 2 3 4) 1 3)"), "#(2 3)");
+    assert_eq!(run("(define v (vector 1 2)) (define w (vector-copy v)) (vector-set! w 0 9) v"), "#(1 2)");
+}
+
+#[test]
+fn vector_ranges_are_checked() {
+    assert_eq!(run_err("(vector->list #(1 2) 1 5)"), "Invalid range for vector->list: 1 to 5 of 2");
+}
+
+#[test]
+fn strings_and_character_vectors_convert_both_ways() {
+    assert_eq!(run("(string->vector \"abc\")"), "#(#\\a #\\b #\\c)");
+    assert_eq!(run("(vector->string #(#\\a #\\b #\\c))"), "\"abc\"");
+}
+
+#[test]
+fn string_and_vector_conversions_take_a_range() {
+    assert_eq!(run("(string->vector \"abcd\" 1 3)"), "#(#\\b #\\c)");
+    assert_eq!(run("(vector->string #(#\\a #\\b #\\c) 1)"), "\"bc\"");
+}
+
+#[test]
+fn vector_to_string_requires_characters() {
+    assert_eq!(run_err("(vector->string #(1 2))"), "'vector->string' requires a vector of characters, got 1");
+}
+
+#[test]
+fn vector_append_concatenates() {
+    assert_eq!(run("(list (vector-append #(1 2) #(3 4) #()) (vector-append))"), "(#(1 2 3 4) #())");
+    assert_eq!(run_err("(vector-append #(1) (list 2))"), "Invalid argument type for vector-append");
+}
+
+#[test]
+fn subvector_slices_a_checked_range() {
+    assert_eq!(run("(subvector #(1 2 3 4 5) 1 3)"), "#(2 3)");
+    assert_eq!(run_err("(subvector #(1 2 3) 2 1)"), "Invalid range for subvector: 2 to 1 of 3");
+    assert_eq!(run_err("(subvector #(1 2 3) 0 4)"), "Invalid range for subvector: 0 to 4 of 3");
+    assert_eq!(run_err("(subvector #(1 2 3) -1 2)"), "Invalid index for subvector: -1");
+}
+
+#[test]
+fn vector_reverse_copies_and_vector_reverse_bang_reverses_in_place() {
+    assert_eq!(run("(define v (vector 1 2 3)) (list (vector-reverse v) v)"), "(#(3 2 1) #(1 2 3))");
+    assert_eq!(run("(define v (vector 1 2 3)) (vector-reverse! v) v"), "#(3 2 1)");
+}